@@ -0,0 +1,205 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use crate::ast::AggregateFunction;
+use crate::expression::Expression;
+use crate::table::Row;
+use crate::value::Value;
+
+
+/// An aggregate function applied to an expression over the table row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    /// `None` for `count(*)`, which counts every row.
+    pub argument: Option<Expression>,
+}
+
+
+/// GROUP BY and aggregates of a select. Every group produces one row holding the
+/// GROUP BY values followed by the aggregate results, which HAVING, the projection
+/// and ORDER BY are evaluated against.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Aggregation {
+    pub group_by: Vec<Expression>,
+    pub aggregates: Vec<Aggregate>,
+    pub having: Option<Expression>,
+}
+
+
+impl Aggregation {
+    /// Whether every aggregate is `count(*)` over a single group, so the result only
+    /// needs the number of rows.
+    pub fn counts_all_rows(&self) -> bool {
+        self.group_by.is_empty()
+            && self.aggregates.iter().all(|a| a.function == AggregateFunction::Count && a.argument.is_none())
+    }
+}
+
+
+/// Hash aggregation: rows are added one by one and assigned to their group by the
+/// encoding of their GROUP BY values. Groups come out in the order they were first seen.
+pub struct HashAggregator<'a> {
+    aggregation: &'a Aggregation,
+    group_indexes: HashMap<Vec<u8>, usize>,
+    groups: Vec<(Vec<Value>, Vec<Accumulator>)>,
+}
+
+
+impl<'a> HashAggregator<'a> {
+    pub fn new(aggregation: &'a Aggregation) -> Self {
+        HashAggregator {
+            aggregation,
+            group_indexes: HashMap::new(),
+            groups: Vec::new(),
+        }
+    }
+
+
+    /// Adds the row to its group. `parameters` are the values of the statement's parameters.
+    pub fn add(&mut self, row: &Row, parameters: &[Value]) -> Result<(), String> {
+        let keys: Vec<Value> = self.aggregation.group_by.iter()
+            .map(|e| e.evaluate_with(&row.values, parameters))
+            .collect::<Result<_, _>>()?;
+
+        let keys = Row::new(keys);
+        let mut encoded = vec![0; keys.serialized_size()];
+        keys.serialize_row(&mut encoded);
+
+        let i = match self.group_indexes.get(&encoded) {
+            Some(&i) => i,
+            None => {
+                self.group_indexes.insert(encoded, self.groups.len());
+                self.groups.push((keys.values, self.new_accumulators()));
+                self.groups.len() - 1
+            }
+        };
+
+        let (_, accumulators) = &mut self.groups[i];
+        for (aggregate, accumulator) in self.aggregation.aggregates.iter().zip(accumulators) {
+            // count(*) counts every row, so give it a value that is never NULL
+            let value = match &aggregate.argument {
+                Some(argument) => argument.evaluate_with(&row.values, parameters)?,
+                None => Value::Boolean(true),
+            };
+            accumulator.add(value)?;
+        }
+
+        Ok(())
+    }
+
+
+    fn new_accumulators(&self) -> Vec<Accumulator> {
+        self.aggregation.aggregates.iter().map(|a| Accumulator::new(a.function)).collect()
+    }
+
+
+    /// Returns one row per group. Without GROUP BY there is always exactly one group,
+    /// even when no row was added.
+    pub fn finish(mut self) -> Vec<Row> {
+        if self.groups.is_empty() && self.aggregation.group_by.is_empty() {
+            self.groups.push((Vec::new(), self.new_accumulators()));
+        }
+
+        self.groups
+            .into_iter()
+            .map(|(mut values, accumulators)| {
+                values.extend(accumulators.into_iter().map(Accumulator::finish));
+                Row::new(values)
+            })
+            .collect()
+    }
+}
+
+
+/// Running state of one aggregate in one group. NULL values are ignored by all of them.
+enum Accumulator {
+    Count(i64),
+    /// Stays integer until a real is added. `None` until a value is added.
+    Sum(Option<Value>),
+    Min(Option<Value>),
+    Max(Option<Value>),
+    Avg { sum: f64, count: i64 },
+}
+
+
+impl Accumulator {
+    fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum(None),
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+            AggregateFunction::Avg => Accumulator::Avg { sum: 0.0, count: 0 },
+        }
+    }
+
+
+    fn add(&mut self, value: Value) -> Result<(), String> {
+        if value.is_null() {
+            return Ok(());
+        }
+
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => {
+                let value = Self::number(value, AggregateFunction::Sum)?;
+                *sum = Some(match (sum.take(), value) {
+                    (None, value) => value,
+                    (Some(Value::Integer(a)), Value::Integer(b)) => {
+                        Value::Integer(a.checked_add(b).ok_or_else(|| "integer overflow".to_string())?)
+                    }
+                    (Some(a), b) => Value::Real(Self::as_real(&a) + Self::as_real(&b)),
+                });
+            }
+            Accumulator::Min(min) => Self::keep(min, value, Ordering::Less)?,
+            Accumulator::Max(max) => Self::keep(max, value, Ordering::Greater)?,
+            Accumulator::Avg { sum, count } => {
+                *sum += Self::as_real(&Self::number(value, AggregateFunction::Avg)?);
+                *count += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /// Replaces `current` with `value` if it orders as `wanted` against it.
+    fn keep(current: &mut Option<Value>, value: Value, wanted: Ordering) -> Result<(), String> {
+        let replace = match current {
+            None => true,
+            Some(current) => Expression::compare(&value, current)? == wanted,
+        };
+        if replace {
+            *current = Some(value);
+        }
+
+        Ok(())
+    }
+
+
+    fn number(value: Value, function: AggregateFunction) -> Result<Value, String> {
+        match value {
+            Value::Integer(_) | Value::Real(_) => Ok(value),
+            value => Err(format!("cannot apply {}() to {}", function, Expression::type_name(&value))),
+        }
+    }
+
+
+    fn as_real(value: &Value) -> f64 {
+        match value {
+            Value::Integer(i) => *i as f64,
+            Value::Real(r) => *r,
+            _ => unreachable!(),
+        }
+    }
+
+
+    fn finish(self) -> Value {
+        match self {
+            Accumulator::Count(count) => Value::Integer(count),
+            Accumulator::Sum(sum) | Accumulator::Min(sum) | Accumulator::Max(sum) => sum.unwrap_or(Value::Null),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => Value::Real(sum / count as f64),
+        }
+    }
+}
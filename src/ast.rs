@@ -0,0 +1,369 @@
+use std::fmt;
+use crate::value::Value;
+
+
+/// A parsed SQL statement, before it is checked against the table schema.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    CreateTable(CreateTable),
+    CreateIndex(CreateIndex),
+    Insert(Insert),
+    Select(Box<Select>),
+    Update(Update),
+    Delete(Delete),
+    /// `explain <statement>`
+    Explain(Box<Statement>),
+}
+
+
+impl Statement {
+    /// Calls `visit` on every expression of the statement, see [`Expr::visit_mut`].
+    pub fn visit_exprs_mut(&mut self, visit: &mut dyn FnMut(&mut Expr)) {
+        let mut visit_all = |exprs: &mut dyn Iterator<Item = &mut Expr>| exprs.for_each(|expr| expr.visit_mut(visit));
+
+        match self {
+            Statement::CreateTable(_) | Statement::CreateIndex(_) => (),
+            Statement::Insert(insert) => visit_all(&mut insert.values.iter_mut()),
+            Statement::Select(select) => {
+                let Select { projection, joins, where_clause, group_by, having, order_by, limit, offset, .. } = &mut **select;
+                visit_all(&mut projection.iter_mut().filter_map(|item| match item {
+                    SelectItem::Expr { expr, .. } => Some(expr),
+                    SelectItem::Wildcard => None,
+                }));
+                visit_all(&mut joins.iter_mut().filter_map(|join| join.constraint.as_mut()));
+                visit_all(&mut where_clause.iter_mut());
+                visit_all(&mut group_by.iter_mut());
+                visit_all(&mut having.iter_mut());
+                visit_all(&mut order_by.iter_mut().map(|term| &mut term.expr));
+                visit_all(&mut limit.iter_mut().chain(offset.iter_mut()));
+            }
+            Statement::Update(update) => {
+                visit_all(&mut update.assignments.iter_mut().map(|(_, expr)| expr));
+                visit_all(&mut update.where_clause.iter_mut());
+            }
+            Statement::Delete(delete) => visit_all(&mut delete.where_clause.iter_mut()),
+            Statement::Explain(statement) => statement.visit_exprs_mut(visit),
+        }
+    }
+}
+
+
+/// `create table [if not exists] <table> (<column definition>, ... [, primary key (<column>, ...)] [, unique (<column>, ...)] ...)`
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateTable {
+    pub table_name: String,
+    /// Whether an existing table of that name makes the statement do nothing, instead
+    /// of failing.
+    pub if_not_exists: bool,
+    pub columns: Vec<ColumnDefinition>,
+    /// Table constraint listing the primary key columns.
+    pub primary_key: Option<Vec<String>>,
+    /// Table constraints listing columns whose values must be unique together.
+    pub unique: Vec<Vec<String>>,
+}
+
+
+/// `create [unique] index <index> on <table> (<column>, ...)`
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateIndex {
+    pub unique: bool,
+    pub index_name: String,
+    pub table_name: String,
+    pub columns: Vec<String>,
+}
+
+
+/// `<name> <type> [primary key [autoincrement]] [not null] [unique]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub type_name: String,
+    pub primary_key: bool,
+    pub autoincrement: bool,
+    pub not_null: bool,
+    pub unique: bool,
+}
+
+
+/// `insert into <table> [(<column>, ...)] values (<expr>, ...)`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Insert {
+    pub table_name: String,
+    /// Explicit column list, or `None` to fill the columns in schema order.
+    pub columns: Option<Vec<String>>,
+    pub values: Vec<Expr>,
+}
+
+
+/// `select <item>, ... from <table> [<join>, ...] [where <expr>]
+/// [group by <expr>, ... [having <expr>]] [order by <term>, ...] [limit <expr> [offset <expr>]]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Select {
+    pub projection: Vec<SelectItem>,
+    pub from: TableReference,
+    pub joins: Vec<Join>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<Expr>,
+    pub offset: Option<Expr>,
+}
+
+
+/// `<table> [[as] <alias>]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct TableReference {
+    pub table_name: String,
+    pub alias: Option<String>,
+}
+
+
+impl TableReference {
+    /// The name columns of the table are qualified with.
+    pub fn name(&self) -> &str {
+        self.alias.as_ref().unwrap_or(&self.table_name)
+    }
+}
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JoinKind {
+    Inner,
+    /// Keeps the rows on the left without a match, with NULL for the joined columns.
+    Left,
+}
+
+
+/// `[inner | left [outer]] join <table> [on <expr>]`. Without ON every pair of rows matches.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: TableReference,
+    pub constraint: Option<Expr>,
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SelectItem {
+    /// `*`, every column in schema order.
+    Wildcard,
+    /// `<expr> [as <alias>]`
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+
+/// `<expr> [asc|desc]` in an ORDER BY clause.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+
+/// `update <table> set <column> = <expr>, ... [where <expr>]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Update {
+    pub table_name: String,
+    pub assignments: Vec<(String, Expr)>,
+    pub where_clause: Option<Expr>,
+}
+
+
+/// `delete from <table> [where <expr>]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Delete {
+    pub table_name: String,
+    pub where_clause: Option<Expr>,
+}
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Equal => "=",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Or => "OR",
+        };
+
+        f.write_str(symbol)
+    }
+}
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "COUNT" => Some(AggregateFunction::Count),
+            "SUM" => Some(AggregateFunction::Sum),
+            "MIN" => Some(AggregateFunction::Min),
+            "MAX" => Some(AggregateFunction::Max),
+            "AVG" => Some(AggregateFunction::Avg),
+            _ => None,
+        }
+    }
+}
+
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format!("{:?}", self).to_ascii_uppercase())
+    }
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Literal(Value),
+    /// `[<table>.]<column>`
+    Column { table: Option<String>, name: String },
+    Unary { operator: UnaryOperator, operand: Box<Expr> },
+    Binary { operator: BinaryOperator, left: Box<Expr>, right: Box<Expr> },
+    /// `<expr> IS NULL`, or `<expr> IS NOT NULL` when negated.
+    IsNull { operand: Box<Expr>, negated: bool },
+    /// `<expr> [NOT] LIKE <pattern>`
+    Like { operand: Box<Expr>, pattern: Box<Expr>, negated: bool },
+    /// `<expr> [NOT] IN (<expr>, ...)`
+    InList { operand: Box<Expr>, list: Vec<Expr>, negated: bool },
+    /// `<function>(<expr>)`, or `count(*)` when there is no argument.
+    Aggregate { function: AggregateFunction, argument: Option<Box<Expr>> },
+    /// `?` or `:name`, replaced by its bound value before the statement is prepared.
+    /// Parameters are numbered from 1 in order of appearance, and every use of a name
+    /// shares the number of its first use.
+    Parameter { index: usize, name: Option<String> },
+}
+
+
+impl Expr {
+    /// Calls `visit` on the expression, then on each of its operands in turn. Operands
+    /// are those of the expression as `visit` left it.
+    pub fn visit_mut(&mut self, visit: &mut dyn FnMut(&mut Expr)) {
+        visit(self);
+
+        match self {
+            Expr::Literal(_) | Expr::Column { .. } | Expr::Parameter { .. } | Expr::Aggregate { argument: None, .. } => (),
+            Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } => operand.visit_mut(visit),
+            Expr::Aggregate { argument: Some(operand), .. } => operand.visit_mut(visit),
+            Expr::Binary { left, right, .. } => {
+                left.visit_mut(visit);
+                right.visit_mut(visit);
+            }
+            Expr::Like { operand, pattern, .. } => {
+                operand.visit_mut(visit);
+                pattern.visit_mut(visit);
+            }
+            Expr::InList { operand, list, .. } => {
+                operand.visit_mut(visit);
+                list.iter_mut().for_each(|expr| expr.visit_mut(visit));
+            }
+        }
+    }
+
+
+    /// Whether an aggregate function appears anywhere in the expression.
+    pub fn contains_aggregate(&self) -> bool {
+        match self {
+            Expr::Literal(_) | Expr::Column { .. } | Expr::Parameter { .. } => false,
+            Expr::Aggregate { .. } => true,
+            Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } => operand.contains_aggregate(),
+            Expr::Binary { left, right, .. } => left.contains_aggregate() || right.contains_aggregate(),
+            Expr::Like { operand, pattern, .. } => operand.contains_aggregate() || pattern.contains_aggregate(),
+            Expr::InList { operand, list, .. } => {
+                operand.contains_aggregate() || list.iter().any(Expr::contains_aggregate)
+            }
+        }
+    }
+}
+
+
+/// Writes the expression back as SQL. Nested operations are parenthesized, so the
+/// text parses to the same tree.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let negation = |negated: &bool| if *negated { "NOT " } else { "" };
+
+        match self {
+            Expr::Literal(Value::Text(text)) => write!(f, "'{}'", text.replace('\'', "''")),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Column { table: Some(table), name } => write!(f, "{}.{}", table, name),
+            Expr::Column { table: None, name } => f.write_str(name),
+            Expr::Unary { operator: UnaryOperator::Negate, operand } => write!(f, "-{}", Operand(operand)),
+            Expr::Unary { operator: UnaryOperator::Not, operand } => write!(f, "NOT {}", Operand(operand)),
+            Expr::Binary { operator, left, right } => write!(f, "{} {} {}", Operand(left), operator, Operand(right)),
+            Expr::IsNull { operand, negated } => write!(f, "{} IS {}NULL", Operand(operand), negation(negated)),
+            Expr::Like { operand, pattern, negated } => {
+                write!(f, "{} {}LIKE {}", Operand(operand), negation(negated), Operand(pattern))
+            }
+            Expr::InList { operand, list, negated } => {
+                let list: Vec<String> = list.iter().map(Expr::to_string).collect();
+                write!(f, "{} {}IN ({})", Operand(operand), negation(negated), list.join(", "))
+            }
+            Expr::Aggregate { function, argument: None } => write!(f, "{}(*)", function),
+            Expr::Aggregate { function, argument: Some(argument) } => write!(f, "{}({})", function, argument),
+            Expr::Parameter { name: Some(name), .. } => write!(f, ":{}", name),
+            Expr::Parameter { name: None, .. } => f.write_str("?"),
+        }
+    }
+}
+
+
+/// An operand of an operator, parenthesized unless it is a literal, a column or a
+/// function call.
+struct Operand<'a>(&'a Expr);
+
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Expr::Literal(_) | Expr::Column { .. } | Expr::Aggregate { .. } | Expr::Parameter { .. } => {
+                write!(f, "{}", self.0)
+            }
+            expr => write!(f, "({})", expr),
+        }
+    }
+}
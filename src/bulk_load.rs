@@ -0,0 +1,254 @@
+use std::cmp::Ordering;
+use crate::cursor::Cursor;
+use crate::data_consts::{BULK_LOAD_FILL_FACTOR, PAGE_SIZE, TABLE_MAX_PAGES};
+use crate::internal_node::InternalNode;
+use crate::leaf_node::LeafNode;
+use crate::table::{ExecuteResult, Row, Table};
+use crate::value::Value;
+
+
+impl Table<'_> {
+    /// Fills an empty table with rows sorted by primary key, without searching the tree
+    /// for every row like [`Table::insert_row`]. Leaves are written left to right, each
+    /// filled to `BULK_LOAD_FILL_FACTOR`, and the internal levels are built above them.
+    /// Indexes are built the same way once their entries are sorted. A NULL rowid is
+    /// replaced by one above the rowid before it. The load stops at the first row that
+    /// is out of order or does not fit the table, leaving the table empty.
+    pub fn bulk_load(&mut self, rows: impl IntoIterator<Item = Row>) -> ExecuteResult {
+        if !Cursor::table_start(self).end_of_table {
+            return ExecuteResult::ExecuteTableNotEmpty;
+        }
+
+        let sequence = self.entry.sequence;
+        match self.load_sorted(rows) {
+            Ok(()) => ExecuteResult::ExecuteSuccess,
+            Err(result) => {
+                // The pages written so far are left unused, since the file has no free list
+                self.entry.sequence = sequence;
+                for i in 0..self.entry.indexes.len() {
+                    Self::clear_tree(&mut self.index_tree(i));
+                }
+                Self::clear_tree(self);
+                result
+            }
+        }
+    }
+
+
+    fn load_sorted(&mut self, rows: impl IntoIterator<Item = Row>) -> Result<(), ExecuteResult> {
+        let rowid_column = self.entry.schema.rowid_column();
+        let mut next_rowid = if self.entry.schema.autoincrement { self.entry.sequence } else { 0 }.checked_add(1);
+        let mut index_entries: Vec<Vec<(Vec<u8>, Row)>> = vec![Vec::new(); self.entry.indexes.len()];
+        let mut builder = TreeBuilder::default();
+
+        for mut row in rows {
+            if let Some(rowid_column) = rowid_column {
+                if row.values.get(rowid_column).is_some_and(Value::is_null) {
+                    let rowid = next_rowid.ok_or(ExecuteResult::ExecuteNoFreeRowid)?;
+                    row.values[rowid_column] = Value::Integer(rowid);
+                }
+                if let Some(&Value::Integer(rowid)) = row.values.get(rowid_column) {
+                    next_rowid = rowid.checked_add(1);
+                    if self.entry.schema.autoincrement {
+                        self.entry.sequence = self.entry.sequence.max(rowid);
+                    }
+                }
+            }
+
+            let key = self.check_row(&row)?;
+            for (i, index_row) in self.index_rows(&row).into_iter().enumerate() {
+                let index_key = self.index_tree(i).check_row(&index_row)?;
+                index_entries[i].push((index_key, index_row));
+            }
+            builder.add(self, key, &row)?;
+        }
+        builder.finish(self)?;
+
+        for (i, mut entries) in index_entries.into_iter().enumerate() {
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            self.check_unique_entries(i, &entries)?;
+
+            let mut index = self.index_tree(i);
+            let mut builder = TreeBuilder::default();
+            for (key, index_row) in entries {
+                builder.add(&mut index, key, &index_row)?;
+            }
+            builder.finish(&mut index)?;
+        }
+
+        Ok(())
+    }
+
+
+    /// Checks that no two sorted entries of a unique index share their indexed values.
+    fn check_unique_entries(&self, i: usize, entries: &[(Vec<u8>, Row)]) -> Result<(), ExecuteResult> {
+        let index = &self.entry.indexes[i];
+        if !index.unique {
+            return Ok(());
+        }
+
+        // Entries with equal indexed values are next to each other
+        let indexed = index.columns.len();
+        let repeated = entries.windows(2).any(|pair| {
+            let values = &pair[0].1.values[..indexed];
+            !values.iter().any(Value::is_null) && values == &pair[1].1.values[..indexed]
+        });
+        match repeated {
+            true => Err(ExecuteResult::ExecuteUniqueViolation(index.column_names(&self.entry.schema))),
+            false => Ok(()),
+        }
+    }
+
+
+    /// Turns the root back into an empty leaf.
+    fn clear_tree(table: &mut Table) {
+        let root = table.pager.get_page(table.entry.root_page_num).expect("Failed to retrieve page");
+        LeafNode::initialize_leaf_node(root);
+        LeafNode::set_node_root(root, true);
+    }
+}
+
+
+/// Writes the cells of an empty tree in key order: leaves from left to right, then each
+/// level of internal nodes above them, ending with the root in the tree's root page.
+#[derive(Default)]
+struct TreeBuilder {
+    /// The leaf being filled. It is written out when the next one starts, so a tree
+    /// with a single leaf can have it written into the root page.
+    leaf: Option<Box<[u8; PAGE_SIZE]>>,
+    num_cells: usize,
+    /// Page number and largest key of every leaf written.
+    leaves: Vec<(usize, Vec<u8>)>,
+    last_key: Option<Vec<u8>>,
+}
+
+
+impl TreeBuilder {
+    /// Number of cells of a node holding at most `max` that the builder fills.
+    fn fill(max: usize) -> usize {
+        ((max as f64 * BULK_LOAD_FILL_FACTOR) as usize).max(1)
+    }
+
+
+    fn add(&mut self, table: &mut Table, key: Vec<u8>, row: &Row) -> Result<(), ExecuteResult> {
+        if let Some(last_key) = &self.last_key {
+            match table.key_comparator.compare(last_key, &key) {
+                Ordering::Less => (),
+                Ordering::Equal => return Err(ExecuteResult::ExecuteDuplicateKey),
+                Ordering::Greater => return Err(ExecuteResult::ExecuteUnsortedKeys),
+            }
+        }
+
+        // The buffer of a leaf written out is reused for the next one
+        let mut leaf = match self.leaf.take() {
+            Some(full) if self.num_cells >= Self::fill(LeafNode::LEAF_NODE_MAX_CELLS) => {
+                self.write_leaf(table, &full[..], false)?;
+                self.num_cells = 0;
+                full
+            }
+            Some(leaf) => leaf,
+            None => Box::new([0; PAGE_SIZE]),
+        };
+        if self.num_cells == 0 {
+            LeafNode::initialize_leaf_node(&mut leaf[..]);
+        }
+
+        LeafNode::set_leaf_node_num_cells(&mut leaf[..], self.num_cells as u32 + 1);
+        LeafNode::set_leaf_node_key(&mut leaf[..], self.num_cells, &key);
+        row.serialize_row(LeafNode::leaf_node_value(&mut leaf[..], self.num_cells));
+        self.leaf = Some(leaf);
+        self.num_cells += 1;
+
+        self.last_key = Some(key);
+        Ok(())
+    }
+
+
+    /// Writes the last leaf and the internal levels. A tree without cells keeps its
+    /// empty root leaf.
+    fn finish(mut self, table: &mut Table) -> Result<(), ExecuteResult> {
+        let Some(leaf) = self.leaf.take() else {
+            return Ok(());
+        };
+        let is_root = self.leaves.is_empty();
+        self.write_leaf(table, &leaf[..], is_root)?;
+
+        let mut level = self.leaves;
+        while level.len() > 1 {
+            level = Self::write_internal_level(table, &level)?;
+        }
+
+        Ok(())
+    }
+
+
+    /// Writes a full leaf to a new page, or to the root page, and links the leaf
+    /// before it to it.
+    fn write_leaf(&mut self, table: &mut Table, leaf: &[u8], is_root: bool) -> Result<(), ExecuteResult> {
+        let num_cells = LeafNode::leaf_node_num_cells(leaf) as usize;
+        let max_key = LeafNode::leaf_node_key(leaf, num_cells - 1).to_vec();
+        let page_num = Self::write_node(table, leaf, is_root)?;
+
+        if let Some(&(previous, _)) = self.leaves.last() {
+            let previous = table.pager.get_page(previous).expect("Failed to retrieve page");
+            LeafNode::set_leaf_node_next_leaf(previous, page_num as u32);
+        }
+        self.leaves.push((page_num, max_key));
+        Ok(())
+    }
+
+
+    /// Writes the parents of the nodes of a level, spreading the nodes evenly among
+    /// them, and returns the new level. A single parent is written to the root page.
+    fn write_internal_level(table: &mut Table, level: &[(usize, Vec<u8>)]) -> Result<Vec<(usize, Vec<u8>)>, ExecuteResult> {
+        let max_children = Self::fill(InternalNode::INTERNAL_NODE_MAX_KEYS) + 1;
+        let num_parents = level.len().div_ceil(max_children);
+        let is_root = num_parents == 1;
+
+        let mut parents = Vec::with_capacity(num_parents);
+        let mut children = level.iter();
+        for i in 0..num_parents {
+            let count = level.len() / num_parents + usize::from(i < level.len() % num_parents);
+            let children: Vec<&(usize, Vec<u8>)> = children.by_ref().take(count).collect();
+            let (right_child, max_key) = children[count - 1];
+
+            let mut node = [0; PAGE_SIZE];
+            InternalNode::initialize_internal_node(&mut node);
+            InternalNode::set_internal_node_num_keys(&mut node, count as u32 - 1);
+            for (cell_num, (child, key)) in children[..count - 1].iter().enumerate() {
+                InternalNode::set_internal_node_child(&mut node, cell_num, *child as u32);
+                InternalNode::set_internal_node_key(&mut node, cell_num, key);
+            }
+            InternalNode::set_internal_node_right_child(&mut node, *right_child as u32);
+
+            let page_num = Self::write_node(table, &node, is_root)?;
+            for (child, _) in children {
+                let child = table.pager.get_page(*child).expect("Failed to retrieve page");
+                LeafNode::set_node_parent(child, page_num as u32);
+            }
+            parents.push((page_num, max_key.clone()));
+        }
+
+        Ok(parents)
+    }
+
+
+    /// Copies the node to a new page, or over the root page, and returns its page number.
+    fn write_node(table: &mut Table, node: &[u8], is_root: bool) -> Result<usize, ExecuteResult> {
+        let page_num = match is_root {
+            true => table.entry.root_page_num,
+            false => table.pager.get_unused_page_num(),
+        };
+        if page_num >= TABLE_MAX_PAGES {
+            return Err(ExecuteResult::ExecuteTableFull);
+        }
+
+        let page = table.pager.get_page(page_num).map_err(|e| {
+            eprintln!("Failed to write node: {:?}", e);
+            ExecuteResult::ExecuteFailed
+        })?;
+        page.copy_from_slice(node);
+        LeafNode::set_node_root(page, is_root);
+        Ok(page_num)
+    }
+}
@@ -0,0 +1,298 @@
+use std::io::{self, ErrorKind};
+use crate::data_consts::PAGE_SIZE;
+use crate::key::Key;
+use crate::schema::{Column, Schema};
+use crate::table::Row;
+use crate::value::{ColumnType, Value};
+
+
+/// Catalog entry describing one table stored in the database file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableEntry {
+    pub name: String,
+    pub root_page_num: usize,
+    pub schema: Schema,
+    /// Highest rowid ever handed out. Only maintained for AUTOINCREMENT tables.
+    pub sequence: i64,
+    pub indexes: Vec<IndexEntry>,
+}
+
+
+/// Catalog entry of a secondary index. The index is a B-tree of its own, whose rows
+/// hold the indexed columns followed by the primary key of the table row. All of them
+/// together form the key, so rows with equal indexed values are kept apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    /// The index tree. Its name is the name of the index.
+    pub tree: TableEntry,
+    /// Indexed columns of the table, in index order.
+    pub columns: Vec<usize>,
+    /// Whether no two rows may have the same indexed values, unless one of them is NULL.
+    pub unique: bool,
+}
+
+
+impl IndexEntry {
+    pub fn new(name: &str, root_page_num: usize, table_schema: &Schema, columns: Vec<usize>, unique: bool) -> Self {
+        let mut index_columns: Vec<Column> = columns
+            .iter()
+            .chain(&table_schema.primary_key)
+            .map(|&column| table_schema.columns[column].clone())
+            .collect();
+        // Only the primary key part is never NULL
+        for column in &mut index_columns[..columns.len()] {
+            column.not_null = false;
+        }
+
+        let primary_key = (0..index_columns.len()).collect();
+        IndexEntry {
+            tree: TableEntry {
+                name: name.to_string(),
+                root_page_num,
+                schema: Schema { columns: index_columns, primary_key, autoincrement: false },
+                sequence: 0,
+                indexes: Vec::new(),
+            },
+            columns,
+            unique,
+        }
+    }
+
+
+    pub fn name(&self) -> &str {
+        &self.tree.name
+    }
+
+
+    /// The encoded indexed values of a table row, which every entry for them starts
+    /// with. `None` if one of them is NULL, since NULLs are never equal.
+    pub fn unique_prefix(&self, values: &[Value]) -> Option<Vec<u8>> {
+        let values: Vec<Value> = self.columns.iter().map(|&column| values[column].clone()).collect();
+        (!values.iter().any(Value::is_null)).then(|| Key::encode(&values))
+    }
+
+
+    /// Names of the indexed columns, separated by commas.
+    pub fn column_names(&self, table_schema: &Schema) -> String {
+        let names: Vec<&str> = self.columns.iter().map(|&column| table_schema.columns[column].name.as_str()).collect();
+        names.join(", ")
+    }
+
+
+    /// The row stored in the index for a table row.
+    pub fn index_row(&self, table_schema: &Schema, values: &[Value]) -> Row {
+        let columns = self.columns.iter().chain(&table_schema.primary_key);
+        Row::new(columns.map(|&column| values[column].clone()).collect())
+    }
+}
+
+
+/// The catalog lives in page 0 and lists every table in the file, with its indexes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Catalog {
+    pub tables: Vec<TableEntry>,
+}
+
+
+impl Catalog {
+    pub const CATALOG_PAGE_NUM: usize = 0;
+
+    /// Catalog Page Layout
+    const MAGIC: &'static [u8; 8] = b"crablite";
+    const FORMAT_VERSION: u32 = 1;
+    const MAGIC_OFFSET: usize = 0;
+    const FORMAT_VERSION_OFFSET: usize = Self::MAGIC_OFFSET + Self::MAGIC.len();
+    const NUM_TABLES_OFFSET: usize = Self::FORMAT_VERSION_OFFSET + std::mem::size_of::<u32>();
+    const ENTRIES_OFFSET: usize = Self::NUM_TABLES_OFFSET + std::mem::size_of::<u32>();
+
+    const FLAG_AUTOINCREMENT: u8 = 1;
+    const FLAG_NOT_NULL: u8 = 1;
+    const FLAG_UNIQUE: u8 = 1;
+
+
+    pub fn find(&self, name: &str) -> Option<&TableEntry> {
+        self.tables.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut TableEntry> {
+        self.tables.iter_mut().find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+
+    /// Finds an index of any table.
+    pub fn find_index(&self, name: &str) -> Option<&IndexEntry> {
+        self.tables.iter().flat_map(|t| &t.indexes).find(|i| i.name().eq_ignore_ascii_case(name))
+    }
+
+
+    pub fn find_index_mut(&mut self, name: &str) -> Option<&mut IndexEntry> {
+        self.tables.iter_mut().flat_map(|t| &mut t.indexes).find(|i| i.name().eq_ignore_ascii_case(name))
+    }
+
+
+    pub fn serialize(&self, page: &mut [u8]) -> io::Result<()> {
+        let mut writer = PageWriter { page, offset: Self::ENTRIES_OFFSET };
+
+        for table in &self.tables {
+            writer.write_str(&table.name)?;
+            writer.write(&(table.root_page_num as u32).to_le_bytes())?;
+            writer.write(&table.sequence.to_le_bytes())?;
+            let flags = if table.schema.autoincrement { Self::FLAG_AUTOINCREMENT } else { 0 };
+            writer.write(&[flags])?;
+
+            writer.write(&(table.schema.primary_key.len() as u16).to_le_bytes())?;
+            for &column in &table.schema.primary_key {
+                writer.write(&(column as u16).to_le_bytes())?;
+            }
+
+            writer.write(&(table.schema.columns.len() as u16).to_le_bytes())?;
+            for column in &table.schema.columns {
+                writer.write_str(&column.name)?;
+                writer.write_str(column.column_type.name())?;
+                writer.write(&[if column.not_null { Self::FLAG_NOT_NULL } else { 0 }])?;
+            }
+
+            writer.write(&(table.indexes.len() as u16).to_le_bytes())?;
+            for index in &table.indexes {
+                writer.write_str(index.name())?;
+                writer.write(&(index.tree.root_page_num as u32).to_le_bytes())?;
+                writer.write(&[if index.unique { Self::FLAG_UNIQUE } else { 0 }])?;
+                writer.write(&(index.columns.len() as u16).to_le_bytes())?;
+                for &column in &index.columns {
+                    writer.write(&(column as u16).to_le_bytes())?;
+                }
+            }
+        }
+
+        let end = writer.offset;
+        page[Self::MAGIC_OFFSET..Self::FORMAT_VERSION_OFFSET].copy_from_slice(Self::MAGIC);
+        page[Self::FORMAT_VERSION_OFFSET..Self::NUM_TABLES_OFFSET].copy_from_slice(&Self::FORMAT_VERSION.to_le_bytes());
+        page[Self::NUM_TABLES_OFFSET..Self::ENTRIES_OFFSET].copy_from_slice(&(self.tables.len() as u32).to_le_bytes());
+        page[end..].fill(0);
+
+        Ok(())
+    }
+
+
+    pub fn deserialize(page: &[u8]) -> io::Result<Self> {
+        if &page[Self::MAGIC_OFFSET..Self::FORMAT_VERSION_OFFSET] != Self::MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Not a crablite database file."));
+        }
+
+        let mut reader = PageReader { page, offset: Self::FORMAT_VERSION_OFFSET };
+        let format_version = reader.read_u32()?;
+        if format_version != Self::FORMAT_VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported file format version {}.", format_version),
+            ));
+        }
+
+        let num_tables = reader.read_u32()?;
+        let mut tables = Vec::with_capacity(num_tables as usize);
+
+        for _ in 0..num_tables {
+            let name = reader.read_str()?;
+            let root_page_num = reader.read_u32()? as usize;
+            let sequence = i64::from_le_bytes(reader.read(8)?.try_into().unwrap());
+            let flags = reader.read(1)?[0];
+
+            let num_key_columns = reader.read_u16()?;
+            let primary_key = (0..num_key_columns)
+                .map(|_| reader.read_u16().map(|c| c as usize))
+                .collect::<io::Result<Vec<usize>>>()?;
+
+            let num_columns = reader.read_u16()?;
+            let mut columns = Vec::with_capacity(num_columns as usize);
+            for _ in 0..num_columns {
+                let column_name = reader.read_str()?;
+                let column_type = ColumnType::from_name(&reader.read_str()?)
+                    .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Unknown column type in catalog."))?;
+                let mut column = Column::new(&column_name, column_type);
+                column.not_null = reader.read(1)?[0] & Self::FLAG_NOT_NULL != 0;
+                columns.push(column);
+            }
+
+            let mut schema = Schema::with_primary_key(columns, primary_key);
+            schema.autoincrement = flags & Self::FLAG_AUTOINCREMENT != 0;
+
+            let num_indexes = reader.read_u16()?;
+            let mut indexes = Vec::with_capacity(num_indexes as usize);
+            for _ in 0..num_indexes {
+                let index_name = reader.read_str()?;
+                let index_root_page_num = reader.read_u32()? as usize;
+                let unique = reader.read(1)?[0] & Self::FLAG_UNIQUE != 0;
+                let num_index_columns = reader.read_u16()?;
+                let index_columns = (0..num_index_columns)
+                    .map(|_| reader.read_u16().map(|c| c as usize))
+                    .collect::<io::Result<Vec<usize>>>()?;
+                indexes.push(IndexEntry::new(&index_name, index_root_page_num, &schema, index_columns, unique));
+            }
+
+            tables.push(TableEntry { name, root_page_num, schema, sequence, indexes });
+        }
+
+        Ok(Catalog { tables })
+    }
+}
+
+
+struct PageWriter<'a> {
+    page: &'a mut [u8],
+    offset: usize,
+}
+
+
+impl PageWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.offset + bytes.len() > PAGE_SIZE {
+            return Err(io::Error::other("Catalog does not fit in a single page."));
+        }
+
+        self.page[self.offset..self.offset + bytes.len()].copy_from_slice(bytes);
+        self.offset += bytes.len();
+        Ok(())
+    }
+
+
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.write(&(s.len() as u16).to_le_bytes())?;
+        self.write(s.as_bytes())
+    }
+}
+
+
+struct PageReader<'a> {
+    page: &'a [u8],
+    offset: usize,
+}
+
+
+impl<'a> PageReader<'a> {
+    fn read(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if self.offset + length > self.page.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Corrupt catalog page."));
+        }
+
+        let bytes = &self.page[self.offset..self.offset + length];
+        self.offset += length;
+        Ok(bytes)
+    }
+
+
+    fn read_u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.read(2)?.try_into().unwrap()))
+    }
+
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.read(4)?.try_into().unwrap()))
+    }
+
+
+    fn read_str(&mut self) -> io::Result<String> {
+        let length = self.read_u16()? as usize;
+        Ok(String::from_utf8_lossy(self.read(length)?).into_owned())
+    }
+}
@@ -0,0 +1,556 @@
+use crate::ast::{BinaryOperator, JoinKind};
+use crate::catalog::Catalog;
+use crate::expression::Expression;
+use crate::select::IndexScan;
+use crate::statement::{Statement, StatementType};
+use crate::value::Value;
+use crate::vm::{Instruction, Program};
+
+
+impl Program {
+    /// Compiles a prepared statement. The catalog gives the width of the tables a select reads.
+    pub fn compile(statement: &Statement, catalog: &Catalog) -> Program {
+        let mut compiler = Compiler::default();
+        let mut program = Program { num_parameters: statement.num_parameters, ..Program::default() };
+
+        match statement.statement_type {
+            StatementType::StatementCreateTable => {
+                if let Some(schema) = &statement.table_schema {
+                    compiler.emit(Instruction::CreateTable {
+                        table: statement.table_name.clone(),
+                        schema: schema.clone(),
+                        unique: statement.unique_constraints.clone(),
+                    });
+                }
+            }
+            StatementType::StatementCreateIndex => {
+                compiler.emit(Instruction::CreateIndex {
+                    index: statement.index_name.clone(),
+                    table: statement.table_name.clone(),
+                    columns: statement.index_columns.clone(),
+                    unique: statement.index_unique,
+                });
+            }
+            StatementType::StatementInsert => compiler.compile_insert(statement, catalog, &mut program),
+            StatementType::StatementSelect => compiler.compile_select(statement, catalog, &mut program),
+            StatementType::StatementUpdate | StatementType::StatementDelete => {
+                compiler.compile_change(statement, catalog, &mut program);
+            }
+            StatementType::None => (),
+        }
+
+        compiler.finish(program)
+    }
+}
+
+
+/// Builds the instructions of a program. Jumps to places not emitted yet are given
+/// address 0 and patched once the place is reached.
+#[derive(Default)]
+struct Compiler {
+    instructions: Vec<Instruction>,
+    num_registers: usize,
+    /// Jumps to the end of the program.
+    exits: Vec<usize>,
+}
+
+
+impl Compiler {
+    /// Appends the instruction and returns its address.
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.instructions.push(instruction);
+        self.instructions.len() - 1
+    }
+
+
+    /// Address of the next instruction.
+    fn here(&self) -> usize {
+        self.instructions.len()
+    }
+
+
+    /// Points the jumps at the given addresses to the next instruction.
+    fn patch_here(&mut self, jumps: &[usize]) {
+        let here = self.here();
+        for &jump in jumps {
+            self.instructions[jump].set_jump(here);
+        }
+    }
+
+
+    /// Reserves `count` consecutive registers and returns the first one.
+    fn allocate(&mut self, count: usize) -> usize {
+        self.num_registers += count;
+        self.num_registers - count
+    }
+
+
+    /// Ends the program. The values of the parameters are loaded into the last registers.
+    fn finish(mut self, program: Program) -> Program {
+        let exits = std::mem::take(&mut self.exits);
+        self.patch_here(&exits);
+        self.emit(Instruction::Halt);
+        let parameters = self.allocate(program.num_parameters);
+
+        Program {
+            instructions: self.instructions,
+            num_registers: self.num_registers,
+            parameters,
+            ..program
+        }
+    }
+
+
+    /// Loads a literal, or an expression of parameters, into the register.
+    fn compile_constant(&mut self, expression: &Expression, target: usize) {
+        match expression {
+            Expression::Literal(value) => self.emit(Instruction::Value { value: value.clone(), target }),
+            expression => self.emit(Instruction::Eval { expression: expression.clone(), row: 0, target }),
+        };
+    }
+
+
+    /// Inserts the values as a record through cursor 0. An omitted rowid is NULL, and is
+    /// replaced by the next free one.
+    fn compile_insert(&mut self, statement: &Statement, catalog: &Catalog, program: &mut Program) {
+        let Some(schema) = catalog.find(&statement.table_name).map(|entry| &entry.schema) else {
+            return;
+        };
+        program.num_cursors = 1;
+        program.constraints = vec![String::new()];
+        self.emit(Instruction::OpenWrite { cursor: 0, table: statement.table_name.clone() });
+
+        let values = &statement.values_to_insert;
+        let start = self.allocate(values.len());
+        for (i, value) in values.iter().enumerate() {
+            self.compile_constant(value, start + i);
+            // Parameter values are converted once they are bound
+            if !matches!(value, Expression::Literal(_)) {
+                self.emit(Instruction::Cast { register: start + i, column_type: schema.columns[i].column_type });
+            }
+        }
+        if let Some(rowid_column) = schema.rowid_column() {
+            let given = self.emit(Instruction::NotNull { register: start + rowid_column, target: 0 });
+            self.emit(Instruction::NewRowid { cursor: 0, target: start + rowid_column });
+            self.patch_here(&[given]);
+        }
+
+        let record = self.allocate(1);
+        self.emit(Instruction::MakeRecord { start, count: values.len(), target: record });
+        self.emit(Instruction::Insert { cursor: 0, record });
+    }
+
+
+    /// Updates or deletes the rows of the table matching the WHERE clause, read through
+    /// cursor 0 like the FROM table of a select. The loop only adds the key of every
+    /// matching row to the row set, with its new record for an update, since changing
+    /// the table reshapes the leaves under the cursor. The rows are then deleted, or
+    /// for an update rewritten once every new record is checked.
+    fn compile_change(&mut self, statement: &Statement, catalog: &Catalog, program: &mut Program) {
+        let Some(schema) = catalog.find(&statement.table_name).map(|entry| &entry.schema) else {
+            return;
+        };
+        let update = matches!(statement.statement_type, StatementType::StatementUpdate);
+        let width = schema.num_columns();
+        let column_types: Vec<_> = schema.columns.iter().map(|column| column.column_type).collect();
+        let tables = [(statement.table_name.as_str(), width)];
+        self.allocate(width);
+
+        let index_name = statement.index_scan.as_ref().and_then(|scan| scan.index_name.as_ref());
+        program.num_cursors = 1 + index_name.is_some() as usize;
+        program.constraints = vec![String::new(); program.num_cursors];
+        if let Some(index_scan) = &statement.index_scan {
+            program.constraints[0] = index_scan.constraint.clone();
+        }
+
+        self.emit(Instruction::OpenWrite { cursor: 0, table: statement.table_name.clone() });
+        if let Some(index_name) = index_name {
+            self.emit(Instruction::OpenIndex { cursor: 1, index: index_name.clone() });
+        }
+
+        // The new row is built from the old one held in the first registers
+        let new_row = if update { self.allocate(width) } else { 0 };
+        let record = self.allocate(1);
+        self.compile_loop(statement, &tables, 0, 0, &mut |compiler| {
+            let skips = compiler.compile_where(statement);
+            if !update {
+                compiler.emit(Instruction::RowSetAdd { cursor: 0, record: None });
+                return skips;
+            }
+
+            for (column, &column_type) in column_types.iter().enumerate() {
+                let target = new_row + column;
+                match statement.assignments.iter().find(|(assigned, _)| *assigned == column) {
+                    Some((_, expression)) => {
+                        compiler.emit(Instruction::Eval { expression: expression.clone(), row: 0, target });
+                        compiler.emit(Instruction::Cast { register: target, column_type });
+                    }
+                    None => {
+                        compiler.emit(Instruction::Copy { source: column, target });
+                    }
+                }
+            }
+            compiler.emit(Instruction::MakeRecord { start: new_row, count: width, target: record });
+            compiler.emit(Instruction::RowSetAdd { cursor: 0, record: Some(record) });
+            skips
+        });
+
+        if !update {
+            let empty = self.emit(Instruction::RowSetRewind { if_empty: 0 });
+            self.exits.push(empty);
+            let top = self.emit(Instruction::RowSetSeek { cursor: 0 });
+            self.emit(Instruction::Delete { cursor: 0 });
+            self.emit(Instruction::RowSetNext { if_more: top });
+            return;
+        }
+
+        // Rows keeping their key are written over, since the file cannot reuse the
+        // pages of deleted rows. The others are all deleted before any is inserted.
+        self.emit(Instruction::RowSetCheck { cursor: 0 });
+        let empty = self.emit(Instruction::RowSetRewind { if_empty: 0 });
+        self.exits.push(empty);
+        let top = self.emit(Instruction::RowSetSeek { cursor: 0 });
+        self.emit(Instruction::RowSetRecord { target: record });
+        let in_place = self.emit(Instruction::RowSetStays { target: 0 });
+        self.emit(Instruction::Delete { cursor: 0 });
+        let deleted = self.emit(Instruction::Goto { target: 0 });
+        self.patch_here(&[in_place]);
+        self.emit(Instruction::UpdateRow { cursor: 0, record });
+        self.patch_here(&[deleted]);
+        self.emit(Instruction::RowSetNext { if_more: top });
+
+        let empty = self.emit(Instruction::RowSetRewind { if_empty: 0 });
+        self.exits.push(empty);
+        let top = self.emit(Instruction::RowSetStays { target: 0 });
+        self.emit(Instruction::RowSetRecord { target: record });
+        self.emit(Instruction::Insert { cursor: 0, record });
+        self.patch_here(&[top]);
+        self.emit(Instruction::RowSetNext { if_more: top });
+    }
+
+
+    /// The first registers hold the row being read: the columns of the FROM table
+    /// followed by those of every joined table. Each table gets a cursor, numbered in
+    /// FROM clause order, and a loop nested inside the loop of the table before it.
+    /// An index the FROM table is read through gets the cursor after them.
+    fn compile_select(&mut self, statement: &Statement, catalog: &Catalog, program: &mut Program) {
+        if statement.limit == Some(Expression::Literal(Value::Integer(0))) {
+            return;
+        }
+
+        let mut tables = vec![(
+            statement.table_name.as_str(),
+            catalog.find(&statement.table_name).map_or(0, |entry| entry.schema.num_columns()),
+        )];
+        tables.extend(statement.joins.iter().map(|join| (join.table_name.as_str(), join.num_columns)));
+
+        let row_width = tables.iter().map(|(_, width)| width).sum();
+        self.allocate(row_width);
+
+        let index_name = statement.index_scan.as_ref().and_then(|scan| scan.index_name.as_ref());
+        program.num_cursors = tables.len() + index_name.is_some() as usize;
+        program.constraints = vec![String::new(); program.num_cursors];
+        if let Some(index_scan) = &statement.index_scan {
+            program.constraints[0] = index_scan.constraint.clone();
+        }
+        for (i, join) in statement.joins.iter().enumerate() {
+            let key_column = catalog.find(&join.table_name)
+                .filter(|_| join.key_lookup.is_some())
+                .map(|entry| &entry.schema.columns[entry.schema.primary_key[0]].name);
+            if let Some(key_column) = key_column {
+                program.constraints[i + 1] = format!("{}=?", key_column);
+            }
+        }
+        program.aggregation = statement.aggregation.clone();
+        program.sort_order = statement.order_by.iter().map(|(_, descending)| *descending).collect();
+        program.limit = statement.limit.clone();
+        program.offset = statement.offset.clone();
+
+        for (cursor, (table, _)) in tables.iter().enumerate() {
+            self.emit(Instruction::OpenRead { cursor, table: table.to_string() });
+        }
+        if let Some(index_name) = index_name {
+            self.emit(Instruction::OpenIndex { cursor: tables.len(), index: index_name.clone() });
+        }
+
+        match &statement.aggregation {
+            None => {
+                self.compile_loop(statement, &tables, 0, 0, &mut |compiler| {
+                    let mut skips = compiler.compile_where(statement);
+                    skips.extend(compiler.compile_output(statement, 0));
+                    skips
+                });
+            }
+            Some(aggregation)
+                if statement.joins.is_empty() && statement.predicate.is_none() && aggregation.counts_all_rows() =>
+            {
+                // Only the number of rows is needed, which the leaves know without reading a row
+                let group = self.allocate(aggregation.aggregates.len());
+                self.emit(Instruction::Count { cursor: 0, target: group });
+                for i in 1..aggregation.aggregates.len() {
+                    self.emit(Instruction::Copy { source: group, target: group + i });
+                }
+
+                let skips = self.compile_output(statement, group);
+                self.patch_here(&skips);
+            }
+            Some(aggregation) => {
+                self.compile_loop(statement, &tables, 0, 0, &mut |compiler| {
+                    let skips = compiler.compile_where(statement);
+                    compiler.emit(Instruction::AggStep { start: 0, count: row_width });
+                    skips
+                });
+
+                let group = self.allocate(aggregation.group_by.len() + aggregation.aggregates.len());
+                let empty = self.emit(Instruction::AggFinal { if_empty: 0 });
+                self.exits.push(empty);
+
+                let top = self.emit(Instruction::GroupRow { target: group });
+                let mut skips = match &aggregation.having {
+                    Some(having) => self.compile_condition(having, group),
+                    None => Vec::new(),
+                };
+                skips.extend(self.compile_output(statement, group));
+                self.patch_here(&skips);
+                self.emit(Instruction::GroupNext { if_more: top });
+            }
+        }
+
+        if !statement.order_by.is_empty() {
+            let count = statement.projection.len();
+            let output = self.allocate(count);
+
+            let empty = self.emit(Instruction::SorterSort { if_empty: 0 });
+            self.exits.push(empty);
+
+            let top = self.emit(Instruction::SorterRow { target: output });
+            let skips = self.compile_result_row(statement, output, count);
+            self.patch_here(&skips);
+            self.emit(Instruction::SorterNext { if_more: top });
+        }
+    }
+
+
+    /// Loops over the rows of the table at `level` and of the tables after it, running
+    /// the body for every joined row. The table's columns go into the registers from
+    /// `offset` on. A join with a key lookup seeks its single matching row instead of
+    /// looping, and a LEFT JOIN without a match runs the rest once with NULL columns.
+    /// The FROM table may instead loop over a range of its primary key, or of an index
+    /// while seeking the row of every entry. The body returns the jumps that skip to the
+    /// next row.
+    fn compile_loop(
+        &mut self,
+        statement: &Statement,
+        tables: &[(&str, usize)],
+        level: usize,
+        offset: usize,
+        body: &mut dyn FnMut(&mut Self) -> Vec<usize>,
+    ) {
+        let join = level.checked_sub(1).map(|i| &statement.joins[i]);
+        let width = tables[level].1;
+
+        let matched = match join {
+            Some(join) if join.kind == JoinKind::Left => {
+                let matched = self.allocate(1);
+                self.emit(Instruction::Value { value: Value::Boolean(false), target: matched });
+                Some(matched)
+            }
+            _ => None,
+        };
+
+        let key_lookup = join.and_then(|join| join.key_lookup.as_ref());
+        let index_scan = statement.index_scan.as_ref().filter(|_| level == 0);
+        // A range of the primary key is read with the table's own cursor
+        let range_cursor = match index_scan {
+            Some(IndexScan { index_name: Some(_), .. }) => tables.len(),
+            _ => level,
+        };
+        let bounds = index_scan.map(|_| self.allocate(3));
+
+        let start = match (key_lookup, index_scan.zip(bounds)) {
+            (Some(key_lookup), _) => {
+                let key = self.allocate(1);
+                self.emit(Instruction::Eval { expression: key_lookup.clone(), row: 0, target: key });
+                self.emit(Instruction::SeekKey { cursor: level, key, if_not_found: 0 })
+            }
+            (None, Some((index_scan, lower))) => {
+                self.compile_constant(&index_scan.lower.0, lower);
+                if let Some((upper, _)) = &index_scan.upper {
+                    self.compile_constant(upper, lower + 1);
+                }
+                self.emit(Instruction::SeekGe { cursor: range_cursor, key: lower, if_empty: 0 })
+            }
+            (None, None) => self.emit(Instruction::Rewind { cursor: level, if_empty: 0 }),
+        };
+
+        let top = self.here();
+        let mut skips = Vec::new();
+        let mut ends = vec![start];
+        if let Some((index_scan, lower)) = index_scan.zip(bounds) {
+            // The entries are in order, so the first one above the range ends the loop
+            let (upper, value) = (lower + 1, lower + 2);
+            let column = if range_cursor == level { index_scan.column } else { 0 };
+            self.emit(Instruction::Column { cursor: range_cursor, column, target: value });
+            if !index_scan.lower.1 {
+                skips.push(self.emit(Instruction::Compare {
+                    operator: BinaryOperator::Greater,
+                    left: value,
+                    right: lower,
+                    if_false: 0,
+                }));
+            }
+            if let Some((_, inclusive)) = index_scan.upper {
+                let operator = if inclusive { BinaryOperator::LessEqual } else { BinaryOperator::Less };
+                ends.push(self.emit(Instruction::Compare { operator, left: value, right: upper, if_false: 0 }));
+            }
+            if range_cursor != level {
+                self.emit(Instruction::SeekRow { cursor: level, index_cursor: range_cursor });
+            }
+        }
+
+        for column in 0..width {
+            self.emit(Instruction::Column { cursor: level, column, target: offset + column });
+        }
+
+        if let Some(condition) = join.and_then(|join| join.condition.as_ref()) {
+            skips.extend(self.compile_condition(condition, 0));
+        }
+        if let Some(matched) = matched {
+            self.emit(Instruction::Value { value: Value::Boolean(true), target: matched });
+        }
+
+        let joined = self.here();
+        if level + 1 < tables.len() {
+            self.compile_loop(statement, tables, level + 1, offset + width, body);
+        } else {
+            skips.extend(body(self));
+        }
+
+        self.patch_here(&skips);
+        if key_lookup.is_none() {
+            self.emit(Instruction::Next { cursor: range_cursor, if_more: top });
+        }
+        self.patch_here(&ends);
+
+        if let Some(matched) = matched {
+            let done = self.emit(Instruction::If { register: matched, target: 0 });
+            self.emit(Instruction::Null { target: offset, count: width });
+            self.emit(Instruction::Value { value: Value::Boolean(true), target: matched });
+            self.emit(Instruction::Goto { target: joined });
+            self.patch_here(&[done]);
+        }
+    }
+
+
+    fn compile_where(&mut self, statement: &Statement) -> Vec<usize> {
+        match &statement.predicate {
+            Some(predicate) => self.compile_condition(predicate, 0),
+            None => Vec::new(),
+        }
+    }
+
+
+    /// Tests the condition against the row held in the registers from `row` on, and
+    /// returns the jumps taken when it is not true. Terms ANDed together are tested one
+    /// by one, and comparisons compare their evaluated operands.
+    fn compile_condition(&mut self, condition: &Expression, row: usize) -> Vec<usize> {
+        match condition {
+            Expression::Binary { operator: BinaryOperator::And, left, right } => {
+                let mut jumps = self.compile_condition(left, row);
+                jumps.extend(self.compile_condition(right, row));
+                jumps
+            }
+            Expression::Binary { operator, left, right } if Self::is_comparison(*operator) => {
+                let operands = self.allocate(2);
+                self.emit(Instruction::Eval { expression: left.as_ref().clone(), row, target: operands });
+                self.emit(Instruction::Eval { expression: right.as_ref().clone(), row, target: operands + 1 });
+                vec![self.emit(Instruction::Compare {
+                    operator: *operator,
+                    left: operands,
+                    right: operands + 1,
+                    if_false: 0,
+                })]
+            }
+            condition => {
+                let register = self.allocate(1);
+                self.emit(Instruction::Eval { expression: condition.clone(), row, target: register });
+                vec![self.emit(Instruction::IfNot { register, target: 0 })]
+            }
+        }
+    }
+
+
+    fn is_comparison(operator: BinaryOperator) -> bool {
+        matches!(
+            operator,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual
+        )
+    }
+
+
+    /// Projects the row held in the registers from `row` on. The result goes into the
+    /// sort when there is an ORDER BY, and is emitted otherwise. Returns the jumps
+    /// taken for rows skipped by OFFSET.
+    fn compile_output(&mut self, statement: &Statement, row: usize) -> Vec<usize> {
+        let count = statement.projection.len();
+
+        if statement.order_by.is_empty() {
+            // Rows skipped by OFFSET are not projected
+            let skips = self.compile_offset(statement);
+            let output = self.allocate(count);
+            self.compile_projection(statement, row, output);
+            self.compile_emit(statement, output, count);
+            return skips;
+        }
+
+        // Sort keys are evaluated against the row, not the projection
+        let keys = self.allocate(statement.order_by.len());
+        for (i, (expression, _)) in statement.order_by.iter().enumerate() {
+            self.emit(Instruction::Eval { expression: expression.clone(), row, target: keys + i });
+        }
+
+        let output = self.allocate(count);
+        self.compile_projection(statement, row, output);
+        self.emit(Instruction::SorterInsert { keys, num_keys: statement.order_by.len(), row: output, count });
+        Vec::new()
+    }
+
+
+    fn compile_projection(&mut self, statement: &Statement, row: usize, output: usize) {
+        for (i, expression) in statement.projection.iter().enumerate() {
+            self.emit(Instruction::Eval { expression: expression.clone(), row, target: output + i });
+        }
+    }
+
+
+    /// Emits a row already in the output registers, applying OFFSET and LIMIT. Returns
+    /// the jumps taken for rows skipped by OFFSET.
+    fn compile_result_row(&mut self, statement: &Statement, output: usize, count: usize) -> Vec<usize> {
+        let skips = self.compile_offset(statement);
+        self.compile_emit(statement, output, count);
+        skips
+    }
+
+
+    fn compile_offset(&mut self, statement: &Statement) -> Vec<usize> {
+        if matches!(statement.offset, None | Some(Expression::Literal(Value::Integer(0)))) {
+            return Vec::new();
+        }
+        vec![self.emit(Instruction::Offset { target: 0 })]
+    }
+
+
+    /// Emits the row, and ends the program once LIMIT rows have been emitted.
+    fn compile_emit(&mut self, statement: &Statement, output: usize, count: usize) {
+        self.emit(Instruction::ResultRow { start: output, count });
+        if statement.limit.is_some() {
+            let limit = self.emit(Instruction::Limit { target: 0 });
+            self.exits.push(limit);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, ErrorKind, Write};
+use crate::cursor::Cursor;
+use crate::database::Database;
+use crate::import::{self, ImportReport, Importer};
+use crate::schema::Schema;
+use crate::table::Row;
+use crate::value::{ColumnType, Value};
+
+
+/// Reads the records of a CSV file as described by RFC 4180. Fields are separated by
+/// commas and may be quoted, in which case they can hold commas, line breaks and
+/// quotes written twice. An empty field that is not quoted reads as `None`.
+pub struct CsvReader<R> {
+    input: R,
+    line: usize,
+    record_line: usize,
+}
+
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(input: R) -> Self {
+        CsvReader {
+            input,
+            line: 0,
+            record_line: 0,
+        }
+    }
+
+
+    /// Line the last record read started on, counting from 1.
+    pub fn record_line(&self) -> usize {
+        self.record_line
+    }
+
+
+    /// Reads the fields of the next record, skipping blank lines. Returns `None` at
+    /// the end of the input, and an error if it ends inside a quoted field.
+    pub fn read_record(&mut self) -> io::Result<Option<Vec<Option<String>>>> {
+        let mut text = String::new();
+        self.record_line = self.line + 1;
+
+        loop {
+            if self.input.read_line(&mut text)? == 0 {
+                return match text.is_empty() {
+                    true => Ok(None),
+                    false => Err(io::Error::new(ErrorKind::InvalidData, "Unterminated quoted field")),
+                };
+            }
+            self.line += 1;
+
+            // Inside a quoted field the quotes read so far are odd in number
+            if text.matches('"').count() % 2 == 1 {
+                continue;
+            }
+            let record = text.trim_end_matches(['\r', '\n']);
+            if record.is_empty() {
+                text.clear();
+                self.record_line = self.line + 1;
+                continue;
+            }
+            return Ok(Some(Self::parse_fields(record)));
+        }
+    }
+
+
+    fn parse_fields(record: &str) -> Vec<Option<String>> {
+        let mut fields = Vec::new();
+        let mut chars = record.chars().peekable();
+
+        loop {
+            let field = if chars.next_if_eq(&'"').is_some() {
+                let mut field = String::new();
+                while let Some(c) = chars.next() {
+                    if c == '"' && chars.next_if_eq(&'"').is_none() {
+                        break;
+                    }
+                    field.push(c);
+                }
+                // Text between the closing quote and the comma is kept rather than rejected
+                field.extend(std::iter::from_fn(|| chars.next_if(|&c| c != ',')));
+                Some(field)
+            } else {
+                let field: String = std::iter::from_fn(|| chars.next_if(|&c| c != ',')).collect();
+                (!field.is_empty()).then_some(field)
+            };
+            fields.push(field);
+
+            if chars.next().is_none() {
+                return fields;
+            }
+        }
+    }
+}
+
+
+/// Writes a record ended by CRLF, quoting the fields that hold a comma, a quote or a
+/// line break. `None` is written as an empty field and an empty string as `""`, so
+/// the two read back differently.
+pub fn write_record(out: &mut impl Write, fields: &[Option<String>]) -> io::Result<()> {
+    let fields: Vec<Cow<str>> = fields.iter()
+        .map(|field| match field {
+            None => Cow::Borrowed(""),
+            Some(field) if field.is_empty() || field.contains([',', '"', '\r', '\n']) =>
+                Cow::Owned(format!("\"{}\"", field.replace('"', "\"\""))),
+            Some(field) => Cow::Borrowed(field.as_str()),
+        })
+        .collect();
+
+    write!(out, "{}\r\n", fields.join(","))
+}
+
+
+impl Database {
+    /// Inserts the records of a CSV file into the table. The first record names the
+    /// column of every field, and columns it leaves out are NULL. Fields are parsed
+    /// as the type of their column, with empty unquoted fields read as NULL. Rows
+    /// that cannot be parsed or inserted are reported and skipped.
+    pub fn import_csv(&mut self, table_name: &str, input: impl BufRead) -> io::Result<ImportReport> {
+        let mut table = self.table(table_name).ok_or_else(|| import::unknown_table(table_name))?;
+        let mut reader = CsvReader::new(input);
+
+        let Some(header) = reader.read_record()? else {
+            return Ok(ImportReport::default());
+        };
+        let mut columns = Vec::with_capacity(header.len());
+        for name in header {
+            let name = name.unwrap_or_default();
+            let invalid = |message| io::Error::new(ErrorKind::InvalidData, message);
+            match table.entry.schema.column_index(&name) {
+                Some(column) if columns.contains(&column) =>
+                    return Err(invalid(format!("Column '{}' is given more than once", name))),
+                Some(column) => columns.push(column),
+                None => return Err(invalid(format!("No column '{}' in table '{}'", name, table_name))),
+            }
+        }
+
+        let mut importer = Importer::new(&mut table);
+        loop {
+            let fields = match reader.read_record() {
+                Ok(Some(fields)) => fields,
+                Ok(None) => break,
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    importer.error(reader.record_line(), e.to_string());
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            let row = Self::csv_row(importer.schema(), &columns, fields);
+            importer.add(reader.record_line(), row)?;
+        }
+
+        importer.finish()
+    }
+
+
+    /// Writes the rows of the table in primary key order as CSV, after a header
+    /// naming the columns. NULL is written as an empty field. Returns the number
+    /// of rows written.
+    pub fn export_csv(&mut self, table_name: &str, out: &mut impl Write) -> io::Result<usize> {
+        let mut table = self.table(table_name).ok_or_else(|| import::unknown_table(table_name))?;
+        let header: Vec<Option<String>> = table.entry.schema.columns.iter().map(|c| Some(c.name.clone())).collect();
+        write_record(out, &header)?;
+
+        let mut count = 0;
+        let mut cursor = Cursor::table_start(&mut table);
+        while !cursor.end_of_table {
+            let row = Row::deserialize_row(cursor.cursor_value())?;
+            let fields: Vec<Option<String>> = row.values.iter()
+                .map(|value| (!value.is_null()).then(|| value.to_string()))
+                .collect();
+            write_record(out, &fields)?;
+            count += 1;
+            cursor.cursor_advance();
+        }
+
+        Ok(count)
+    }
+
+
+    /// Builds a table row from the fields of a record, given the column of each field.
+    fn csv_row(schema: &Schema, columns: &[usize], fields: Vec<Option<String>>) -> Result<Row, String> {
+        if fields.len() != columns.len() {
+            return Err(format!("Expected {} fields, found {}", columns.len(), fields.len()));
+        }
+
+        let mut values = vec![Value::Null; schema.num_columns()];
+        for (&i, field) in columns.iter().zip(fields) {
+            let Some(field) = field else {
+                continue;
+            };
+            let column = &schema.columns[i];
+            values[i] = match column.column_type {
+                // Text is taken as it is, so "null" stays a string
+                ColumnType::Text => Value::Text(field),
+                column_type => Value::parse_literal(&field, column_type)
+                    .ok_or_else(|| format!("Invalid {} value '{}' for column '{}'", column_type, field, column.name))?,
+            };
+        }
+
+        Ok(Row::new(values))
+    }
+}
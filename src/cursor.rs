@@ -1,37 +1,56 @@
-use std::process::exit;
+use std::cmp::Ordering;
+use crate::internal_node::InternalNode;
+use crate::key::Key;
 use crate::leaf_node::{NodeType, LeafNode};
 use crate::table::Table;
+use crate::value::Value;
 
-pub struct Cursor<'a> {
-    pub table: &'a mut Table,
+pub struct Cursor<'a, 'b> {
+    pub table: &'a mut Table<'b>,
     pub page_num: usize,
     pub cell_num: usize,
     pub end_of_table: bool
 }
 
 
-impl<'a> Cursor<'a> {
-    pub fn table_start(table: &'a mut Table) -> Self {
-        let page_num = table.root_page_num;
-        let cell_num = 0;
+impl<'a, 'b> Cursor<'a, 'b> {
+    pub fn table_start(table: &'a mut Table<'b>) -> Self {
+        let mut page_num = table.entry.root_page_num;
 
-        let root_node = table.pager.get_page(page_num).expect("Failed to retrieve page");
-        let num_cells = *LeafNode::leaf_node_num_cells(root_node);
+        loop {
+            let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+            match LeafNode::get_node_type(node) {
+                NodeType::NodeLeaf => break,
+                NodeType::NodeInternal => page_num = InternalNode::internal_node_child(node, 0) as usize,
+            }
+        }
+
+        let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+        let num_cells = LeafNode::leaf_node_num_cells(node);
         let end_of_table = num_cells == 0;
 
         Cursor {
             table,
             page_num,
-            cell_num,
+            cell_num: 0,
             end_of_table
         }
     }
 
 
-    pub fn table_end(table: &'a mut Table) -> Self {
-        let page_num = table.root_page_num;
-        let root_node = table.pager.get_page(page_num).expect("Failed to retrieve page");
-        let num_cells = *LeafNode::leaf_node_num_cells(root_node) as usize;
+    pub fn table_end(table: &'a mut Table<'b>) -> Self {
+        let mut page_num = table.entry.root_page_num;
+
+        loop {
+            let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+            match LeafNode::get_node_type(node) {
+                NodeType::NodeLeaf => break,
+                NodeType::NodeInternal => page_num = InternalNode::internal_node_right_child(node) as usize,
+            }
+        }
+
+        let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+        let num_cells = LeafNode::leaf_node_num_cells(node) as usize;
 
         Cursor {
             table,
@@ -42,83 +61,99 @@ impl<'a> Cursor<'a> {
     }
 
 
-    pub fn table_find(table: &'a mut Table, key: u32) -> Self {
-        let root_page_num = table.root_page_num;
-        let root_node = table.pager.get_page(root_page_num).expect("Failed to retrieve page");
+    /// Returns a cursor at the given key, or at the position where it would be inserted.
+    pub fn table_find(table: &'a mut Table<'b>, key: &[u8]) -> Self {
+        let (page_num, cell_num) = Self::table_find_position(table, key);
+        let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+        let num_cells = LeafNode::leaf_node_num_cells(node) as usize;
+        let next_leaf = LeafNode::leaf_node_next_leaf(node);
 
-        if LeafNode::get_node_type(root_node) == NodeType::NodeLeaf {
-            return Self::leaf_node_find(table, root_page_num, key)
-        } else {
-            println!("Need to implement searching an internal node");
-            exit(1);
+        let mut cursor = Cursor {
+            table,
+            page_num,
+            cell_num,
+            end_of_table: false
+        };
+
+        // Past the last cell of this leaf: the next greater key starts the next leaf
+        if cell_num >= num_cells {
+            if next_leaf == 0 {
+                cursor.end_of_table = true;
+            } else {
+                cursor.page_num = next_leaf as usize;
+                cursor.cell_num = 0;
+            }
         }
+
+        cursor
     }
 
 
-    pub fn leaf_node_find(table: &'a mut Table, page_num: usize, key: u32) -> Self {
-        let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
-        let num_cells = LeafNode::leaf_node_num_cells(node);
-
-        let mut min_index = 0;
-        let mut max_index = *num_cells as usize;
+    /// Returns a cursor at the first row whose first key column is at least the value.
+    /// A value that is NULL or not of the column type starts at the first row instead.
+    /// Only meaningful for trees ordered bytewise, the only ones ranges are read from.
+    pub fn table_seek(table: &'a mut Table<'b>, value: &Value) -> Self {
+        let schema = &table.entry.schema;
+        let key_type = schema.columns[schema.primary_key[0]].column_type;
 
-        while min_index != max_index {
-            let index = min_index + (max_index - min_index) / 2;
-            let key_at_index = u32::from_le_bytes(LeafNode::leaf_node_key(node, index).try_into().unwrap());
-
-            if key == key_at_index {
-                return Cursor {
-                    table,
-                    page_num,
-                    cell_num: index,
-                    end_of_table: false
-                }
-            } else if key < key_at_index {
-                max_index = index;
-            } else {
-                min_index = index + 1;
-            }
+        match value.clone().coerce(key_type).filter(|value| !value.is_null()) {
+            Some(value) => Self::table_find(table, &Key::encode(&[value])),
+            None => Self::table_start(table),
         }
+    }
+
+
+    pub fn leaf_node_find(table: &'a mut Table<'b>, page_num: usize, key: &[u8]) -> Self {
+        let (page_num, cell_num) = Self::leaf_node_find_position(table, page_num, key);
+        let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+        let end_of_table = cell_num >= LeafNode::leaf_node_num_cells(node) as usize;
 
         Cursor {
             table,
             page_num,
-            cell_num: min_index,
-            end_of_table: true,
+            cell_num,
+            end_of_table,
         }
     }
 
 
-    pub fn table_find_position(table: &'a mut Table, key: u32) -> (usize, usize) {
-        let root_page_num = table.root_page_num;
-        let root_node = table.pager.get_page(root_page_num).expect("Failed to retrieve page");
+    /// Returns the leaf page and cell holding the key, or where it would be inserted.
+    pub fn table_find_position(table: &mut Table, key: &[u8]) -> (usize, usize) {
+        let mut page_num = table.entry.root_page_num;
 
-        if LeafNode::get_node_type(root_node) == NodeType::NodeLeaf {
-            return Self::leaf_node_find_position(table, root_page_num, key);
-        } else {
-            println!("Need to implement searching an internal node");
-            exit(1);
+        loop {
+            let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+            match LeafNode::get_node_type(node) {
+                NodeType::NodeLeaf => return Self::leaf_node_find_position(table, page_num, key),
+                NodeType::NodeInternal => page_num = Self::internal_node_find(table, page_num, key),
+            }
         }
     }
 
 
-    pub fn leaf_node_find_position(table: &'a mut Table, page_num: usize, key: u32) -> (usize, usize) {
+    /// Returns the child page of an internal node that should contain the key.
+    pub fn internal_node_find(table: &mut Table, page_num: usize, key: &[u8]) -> usize {
+        let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
+        let child_index = InternalNode::internal_node_find_child(node, key, table.key_comparator);
+        InternalNode::internal_node_child(node, child_index) as usize
+    }
+
+
+    pub fn leaf_node_find_position(table: &mut Table, page_num: usize, key: &[u8]) -> (usize, usize) {
         let node = table.pager.get_page(page_num).expect("Failed to retrieve page");
         let num_cells = LeafNode::leaf_node_num_cells(node);
 
         let mut min_index = 0;
-        let mut max_index = *num_cells as usize;
+        let mut max_index = num_cells as usize;
 
         while min_index != max_index {
             let index = min_index + (max_index - min_index) / 2;
-            let key_at_index = u32::from_le_bytes(LeafNode::leaf_node_key(node, index).try_into().unwrap());
+            let key_at_index = LeafNode::leaf_node_key(node, index);
 
-            if key == key_at_index {
-                return (page_num, index);
-            } else if key < key_at_index {
-                max_index = index;
-            } else {
-                min_index = index + 1;
+            match table.key_comparator.compare(key, key_at_index) {
+                Ordering::Equal => return (page_num, index),
+                Ordering::Less => max_index = index,
+                Ordering::Greater => min_index = index + 1,
             }
         }
 
@@ -126,6 +161,12 @@ impl<'a> Cursor<'a> {
     }
 
 
+    pub fn cursor_key(&mut self) -> &[u8] {
+        let page = self.table.pager.get_page(self.page_num).unwrap();
+        LeafNode::leaf_node_key(page, self.cell_num)
+    }
+
+
     pub fn cursor_value(&mut self) -> &mut [u8] {
         let page = self.table.pager.get_page(self.page_num).unwrap();
         LeafNode::leaf_node_value(page, self.cell_num)
@@ -136,8 +177,16 @@ impl<'a> Cursor<'a> {
         let node = self.table.pager.get_page(self.page_num).unwrap();
         self.cell_num += 1;
 
-        if self.cell_num >= *LeafNode::leaf_node_num_cells(node) as usize {
-            self.end_of_table = true;
+        if self.cell_num >= LeafNode::leaf_node_num_cells(node) as usize {
+            // Advance to next leaf node
+            let next_page_num = LeafNode::leaf_node_next_leaf(node);
+            if next_page_num == 0 {
+                // This was rightmost leaf
+                self.end_of_table = true;
+            } else {
+                self.page_num = next_page_num as usize;
+                self.cell_num = 0;
+            }
         }
     }
-}
\ No newline at end of file
+}
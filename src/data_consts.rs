@@ -1,12 +1,14 @@
-
-pub const ID_SIZE: usize = 4;
-pub const USERNAME_SIZE: usize = 32;
-pub const EMAIL_SIZE: usize = 255;
-pub const ID_OFFSET: usize = 0;
-pub const USERNAME_OFFSET: usize = ID_OFFSET + ID_SIZE;
-
-pub const EMAIL_OFFSET: usize = USERNAME_OFFSET + USERNAME_SIZE;
-
 pub const PAGE_SIZE: usize = 4096;
-pub const TABLE_MAX_PAGES: usize = 100;
-pub const ROW_SIZE: usize = ID_SIZE + USERNAME_SIZE + EMAIL_SIZE;
\ No newline at end of file
+/// Largest number of pages in a database file.
+pub const TABLE_MAX_PAGES: usize = 1 << 20;
+/// Most pages the pager keeps in memory. Past it, a page is written back to the file
+/// and dropped to make room for the next one read.
+pub const PAGER_CACHE_PAGES: usize = 1 << 12;
+/// Largest serialized row (record header plus encoded values) that fits in a leaf cell.
+pub const ROW_SIZE: usize = 400;
+/// Largest encoded primary key that fits in a leaf or internal node cell.
+pub const KEY_SIZE: usize = 126;
+/// Bytes of rows an ORDER BY sorts in memory before spilling them to a temporary file.
+pub const SORT_MEMORY_LIMIT: usize = 1 << 20;
+/// Share of the cells of a node that bulk loading fills, leaving room for later inserts.
+pub const BULK_LOAD_FILL_FACTOR: f64 = 0.9;
@@ -0,0 +1,257 @@
+use std::io::{self, ErrorKind};
+use crate::catalog::{Catalog, IndexEntry, TableEntry};
+use crate::data_consts::TABLE_MAX_PAGES;
+use crate::key::{BytewiseComparator, KeyComparator};
+use crate::leaf_node::LeafNode;
+use crate::pager::Pager;
+use crate::schema::Schema;
+use crate::cursor::Cursor;
+use crate::table::{ExecuteResult, Row, Table};
+
+
+/// A database file: the pager shared by all tables and the catalog that lists them.
+pub struct Database {
+    pub pager: Pager,
+    pub catalog: Catalog,
+    pub key_comparator: Box<dyn KeyComparator>,
+}
+
+
+impl Database {
+    pub const DEFAULT_TABLE_NAME: &'static str = "users";
+
+
+    /// Opens the database stored in the file, creating a `users` table if the file is new.
+    pub fn db_open(filename: &str) -> io::Result<Self> {
+        Self::open(filename, Self::DEFAULT_TABLE_NAME, Schema::default())
+    }
+
+
+    /// Opens the database, creating the table with the given schema if the file is new.
+    /// Fails if the file does not hold the table with this schema.
+    pub fn db_open_with_schema(filename: &str, name: &str, schema: Schema) -> io::Result<Self> {
+        let database = Self::open(filename, name, schema.clone())?;
+
+        match database.catalog.find(name) {
+            Some(entry) if entry.schema == schema => Ok(database),
+            Some(_) => Err(io::Error::new(ErrorKind::InvalidData, "Schema does not match the stored table.")),
+            None => Err(io::Error::new(ErrorKind::NotFound, format!("No table named '{}'.", name))),
+        }
+    }
+
+
+    fn open(filename: &str, name: &str, schema: Schema) -> io::Result<Self> {
+        let mut pager = Pager::pager_open(filename)?;
+        let is_new = pager.num_pages == 0;
+
+        let catalog = if is_new {
+            Catalog::default()
+        } else {
+            Catalog::deserialize(pager.get_page(Catalog::CATALOG_PAGE_NUM)?)?
+        };
+
+        let mut database = Database {
+            pager,
+            catalog,
+            key_comparator: Box::new(BytewiseComparator),
+        };
+
+        if is_new {
+            match database.create_table(name, schema, &[]) {
+                ExecuteResult::ExecuteSuccess => (),
+                _ => return Err(io::Error::other("Failed to create the initial table.")),
+            }
+        }
+
+        Ok(database)
+    }
+
+
+    /// Replaces the ordering of primary keys. Must be set before any rows are inserted
+    /// and every time the database is opened, since the trees are built in this order.
+    pub fn set_key_comparator(&mut self, key_comparator: Box<dyn KeyComparator>) {
+        self.key_comparator = key_comparator;
+    }
+
+
+    pub fn table(&mut self, name: &str) -> Option<Table<'_>> {
+        let entry = self.catalog.find_mut(name)?;
+
+        Some(Table {
+            pager: &mut self.pager,
+            entry,
+            key_comparator: self.key_comparator.as_ref(),
+        })
+    }
+
+
+    /// The tree of an index, whose keys are ordered bytewise.
+    pub fn index(&mut self, name: &str) -> Option<Table<'_>> {
+        let index = self.catalog.find_index_mut(name)?;
+
+        Some(Table {
+            pager: &mut self.pager,
+            entry: &mut index.tree,
+            key_comparator: &BytewiseComparator,
+        })
+    }
+
+
+    /// Adds an empty table, whose root leaf goes on a new page, with a unique index on
+    /// each of the `unique` column lists. Either the table and all of its indexes are
+    /// added, or none of them.
+    pub fn create_table(&mut self, name: &str, schema: Schema, unique: &[Vec<usize>]) -> ExecuteResult {
+        if self.catalog.find(name).is_some() || self.catalog.find_index(name).is_some() {
+            return ExecuteResult::ExecuteTableExists(name.to_string());
+        }
+
+        let index_names: Vec<String> = (1..=unique.len()).map(|i| format!("autoindex_{}_{}", name, i)).collect();
+        let taken = |index_name: &&String| self.catalog.find(index_name).is_some() || self.catalog.find_index(index_name).is_some();
+        if let Some(index_name) = index_names.iter().find(taken) {
+            return ExecuteResult::ExecuteIndexExists(index_name.clone());
+        }
+
+        let root_page_num = match self.allocate_root() {
+            Ok(root_page_num) => root_page_num,
+            Err(result) => return result,
+        };
+
+        self.catalog.tables.push(TableEntry {
+            name: name.to_string(),
+            root_page_num,
+            schema,
+            sequence: 0,
+            indexes: Vec::new(),
+        });
+        if let Err(e) = self.save_catalog() {
+            self.catalog.tables.pop();
+            eprintln!("Failed to create table: {}", e);
+            return ExecuteResult::ExecuteFailed;
+        }
+        self.initialize_root(root_page_num);
+
+        for (index_name, columns) in index_names.iter().zip(unique) {
+            let result = self.create_index(index_name, name, columns.clone(), true);
+            if matches!(result, ExecuteResult::ExecuteSuccess) {
+                continue;
+            }
+
+            // The indexes already added go with the table, which is the last one
+            self.catalog.tables.pop();
+            if let Err(e) = self.save_catalog() {
+                eprintln!("Failed to drop table: {}", e);
+                return ExecuteResult::ExecuteFailed;
+            }
+            return result;
+        }
+
+        ExecuteResult::ExecuteSuccess
+    }
+
+
+    /// Adds an index on the columns of a table and fills it with the rows already stored.
+    /// A unique index is not added if two of the rows have the same indexed values.
+    pub fn create_index(&mut self, name: &str, table_name: &str, columns: Vec<usize>, unique: bool) -> ExecuteResult {
+        if self.catalog.find(name).is_some() || self.catalog.find_index(name).is_some() {
+            return ExecuteResult::ExecuteIndexExists(name.to_string());
+        }
+
+        let Some(mut table) = self.table(table_name) else {
+            return ExecuteResult::ExecuteFailed;
+        };
+        let mut rows = Vec::new();
+        let mut cursor = Cursor::table_start(&mut table);
+        while !cursor.end_of_table {
+            match Row::deserialize_row(cursor.cursor_value()) {
+                Ok(row) => rows.push(row),
+                Err(e) => {
+                    eprintln!("Failed to create index: {}", e);
+                    return ExecuteResult::ExecuteFailed;
+                }
+            }
+            cursor.cursor_advance();
+        }
+
+        // The root is picked once the rows are known to fit the index
+        let entry = self.catalog.find(table_name).expect("Table was found above");
+        let mut index = IndexEntry::new(name, 0, &entry.schema, columns, unique);
+        if unique {
+            let mut taken: Vec<Vec<u8>> = rows.iter().filter_map(|row| index.unique_prefix(&row.values)).collect();
+            taken.sort();
+            if taken.windows(2).any(|pair| pair[0] == pair[1]) {
+                return ExecuteResult::ExecuteUniqueViolation(index.column_names(&entry.schema));
+            }
+        }
+
+        index.tree.root_page_num = match self.allocate_root() {
+            Ok(root_page_num) => root_page_num,
+            Err(result) => return result,
+        };
+        self.initialize_root(index.tree.root_page_num);
+        let entry = self.catalog.find_mut(table_name).expect("Table was found above");
+        let index_rows: Vec<Row> = rows.iter().map(|row| index.index_row(&entry.schema, &row.values)).collect();
+        entry.indexes.push(index);
+
+        let mut result = ExecuteResult::ExecuteSuccess;
+        let mut tree = self.index(name).expect("Index was just added");
+        for index_row in &index_rows {
+            result = tree.insert_row(index_row);
+            if !matches!(result, ExecuteResult::ExecuteSuccess) {
+                break;
+            }
+        }
+        if matches!(result, ExecuteResult::ExecuteSuccess) {
+            if let Err(e) = self.save_catalog() {
+                eprintln!("Failed to create index: {}", e);
+                result = ExecuteResult::ExecuteFailed;
+            }
+        }
+
+        // The pages of a failed index are left unused, since the file has no free list
+        if !matches!(result, ExecuteResult::ExecuteSuccess) {
+            self.catalog.find_mut(table_name).expect("Table was found above").indexes.pop();
+        }
+        result
+    }
+
+
+    /// Picks the page for the root of a new tree.
+    fn allocate_root(&mut self) -> Result<usize, ExecuteResult> {
+        // Page 0 holds the catalog, so a new file allocates it before the first root
+        if self.pager.get_page(Catalog::CATALOG_PAGE_NUM).is_err() {
+            return Err(ExecuteResult::ExecuteFailed);
+        }
+        let root_page_num = self.pager.get_unused_page_num();
+        if root_page_num >= TABLE_MAX_PAGES {
+            return Err(ExecuteResult::ExecuteTableFull);
+        }
+
+        Ok(root_page_num)
+    }
+
+
+    fn initialize_root(&mut self, root_page_num: usize) {
+        let root_node = self.pager.get_page(root_page_num).expect("Failed to retrieve page");
+        LeafNode::initialize_leaf_node(root_node);
+        LeafNode::set_node_root(root_node, true);
+    }
+
+
+    fn save_catalog(&mut self) -> io::Result<()> {
+        self.catalog.serialize(self.pager.get_page(Catalog::CATALOG_PAGE_NUM)?)
+    }
+
+
+    pub fn db_close(&mut self) -> io::Result<()> {
+        self.save_catalog()?;
+
+        let pager = &mut self.pager;
+        for i in 0..pager.pages.len() {
+            if pager.pages[i].is_some() {
+                pager.pager_flush(i)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use std::borrow::Cow;
+use std::io::{self, ErrorKind, Write};
+use crate::catalog::TableEntry;
+use crate::cursor::Cursor;
+use crate::database::Database;
+use crate::import;
+use crate::lexer::Keyword;
+use crate::table::Row;
+use crate::value::Value;
+
+
+impl Database {
+    /// Writes SQL that rebuilds the table, or every table without a name: the CREATE
+    /// TABLE statement, an INSERT for every row in primary key order, then the CREATE
+    /// INDEX statements. Each statement takes one line, so dumps can be diffed. Tables
+    /// are created with IF NOT EXISTS, so a dump can be read into a new database, which
+    /// already has an empty `users` table. An AUTOINCREMENT table ends with a
+    /// `.sequence` line that `.read` applies, keeping rowids of deleted rows unused.
+    pub fn dump(&mut self, table_name: Option<&str>, out: &mut impl Write) -> io::Result<()> {
+        let names: Vec<String> = match table_name {
+            Some(name) => {
+                let entry = self.catalog.find(name).ok_or_else(|| import::unknown_table(name))?;
+                vec![entry.name.clone()]
+            }
+            None => self.catalog.tables.iter().map(|t| t.name.clone()).collect(),
+        };
+
+        for name in names {
+            let mut table = self.table(&name).expect("Table is in the catalog");
+            let (create_table, create_indexes) = Self::create_statements(table.entry);
+            let insert = format!("INSERT INTO {} VALUES", Self::identifier(&name));
+
+            writeln!(out, "{}", create_table)?;
+            let mut cursor = Cursor::table_start(&mut table);
+            while !cursor.end_of_table {
+                let row = Row::deserialize_row(cursor.cursor_value())?;
+                let values: Vec<String> = row.values.iter().map(Self::literal).collect();
+                writeln!(out, "{} ({});", insert, values.join(", "))?;
+                cursor.cursor_advance();
+            }
+            for create_index in create_indexes {
+                writeln!(out, "{}", create_index)?;
+            }
+            if table.entry.schema.autoincrement && table.entry.sequence != 0 {
+                writeln!(out, ".sequence {} {}", name, table.entry.sequence)?;
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /// Raises the AUTOINCREMENT high-water mark of the table to `sequence`. The mark is
+    /// never lowered, so a rowid handed out before is not handed out again.
+    pub fn set_sequence(&mut self, name: &str, sequence: i64) -> io::Result<()> {
+        let table = self.table(name).ok_or_else(|| import::unknown_table(name))?;
+        if !table.entry.schema.autoincrement {
+            let message = format!("Table '{}' has no AUTOINCREMENT column", table.entry.name);
+            return Err(io::Error::new(ErrorKind::InvalidInput, message));
+        }
+
+        table.entry.sequence = table.entry.sequence.max(sequence);
+        Ok(())
+    }
+
+
+    /// The CREATE TABLE statement of the table and the CREATE INDEX statements of its
+    /// indexes. Indexes made for UNIQUE constraints become table constraints again, in
+    /// the same order, so they get back the same names.
+    fn create_statements(entry: &TableEntry) -> (String, Vec<String>) {
+        let schema = &entry.schema;
+        let column_names = |columns: &[usize]| -> String {
+            let names: Vec<Cow<str>> = columns.iter().map(|&i| Self::identifier(&schema.columns[i].name)).collect();
+            names.join(", ")
+        };
+
+        let mut definitions = Vec::new();
+        for (i, column) in schema.columns.iter().enumerate() {
+            let mut definition = format!("{} {}", Self::identifier(&column.name), column.column_type);
+            if schema.primary_key == [i] {
+                definition.push_str(" PRIMARY KEY");
+                if schema.autoincrement {
+                    definition.push_str(" AUTOINCREMENT");
+                }
+            } else if column.not_null && !schema.primary_key.contains(&i) {
+                definition.push_str(" NOT NULL");
+            }
+            definitions.push(definition);
+        }
+        if schema.primary_key.len() > 1 {
+            definitions.push(format!("PRIMARY KEY ({})", column_names(&schema.primary_key)));
+        }
+
+        let mut create_indexes = Vec::new();
+        let autoindex_prefix = format!("autoindex_{}_", entry.name);
+        for index in &entry.indexes {
+            if index.unique && index.name().starts_with(&autoindex_prefix) {
+                definitions.push(format!("UNIQUE ({})", column_names(&index.columns)));
+            } else {
+                create_indexes.push(format!(
+                    "CREATE {}INDEX {} ON {} ({});",
+                    if index.unique { "UNIQUE " } else { "" },
+                    Self::identifier(index.name()),
+                    Self::identifier(&entry.name),
+                    column_names(&index.columns),
+                ));
+            }
+        }
+
+        let create_table = format!("CREATE TABLE IF NOT EXISTS {} ({});", Self::identifier(&entry.name), definitions.join(", "));
+        (create_table, create_indexes)
+    }
+
+
+    /// The name as it can be written in SQL, quoted if it is a keyword or holds
+    /// characters other than letters, digits and underscores.
+    fn identifier(name: &str) -> Cow<'_, str> {
+        let plain = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && Keyword::from_word(name).is_none();
+
+        match plain {
+            true => Cow::Borrowed(name),
+            false => Cow::Owned(format!("\"{}\"", name.replace('"', "\"\""))),
+        }
+    }
+
+
+    /// The value as a SQL literal that reads back as the same value. Line breaks in
+    /// text are escaped to keep the statement on one line.
+    fn literal(value: &Value) -> String {
+        match value {
+            Value::Null => String::from("NULL"),
+            Value::Integer(i) => i.to_string(),
+            Value::Real(r) if r.is_nan() => String::from("(1e999 - 1e999)"),
+            Value::Real(r) if r.is_infinite() => String::from(if *r > 0.0 { "1e999" } else { "-1e999" }),
+            Value::Real(r) => format!("{:?}", r),
+            Value::Text(text) => {
+                let mut literal = String::from("'");
+                for c in text.chars() {
+                    match c {
+                        '\'' => literal.push_str("''"),
+                        '\\' => literal.push_str("\\\\"),
+                        '\n' => literal.push_str("\\n"),
+                        '\r' => literal.push_str("\\r"),
+                        '\t' => literal.push_str("\\t"),
+                        '\0' => literal.push_str("\\0"),
+                        c => literal.push(c),
+                    }
+                }
+                literal.push('\'');
+                literal
+            }
+            Value::Blob(_) => value.to_string(),
+            Value::Boolean(b) => String::from(if *b { "TRUE" } else { "FALSE" }),
+        }
+    }
+}
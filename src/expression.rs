@@ -0,0 +1,496 @@
+use std::cmp::Ordering;
+use std::fmt;
+use crate::aggregate::Aggregate;
+use crate::ast::{self, BinaryOperator, UnaryOperator};
+use crate::schema::{Column, Schema};
+use crate::table::Row;
+use crate::value::Value;
+
+
+/// An expression whose column references are resolved to column indexes, ready to be
+/// evaluated against decoded rows.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    Literal(Value),
+    Column(usize),
+    /// A parameter of a prepared statement, numbered from 1, whose value is bound when
+    /// the statement runs.
+    Parameter(usize),
+    Unary { operator: UnaryOperator, operand: Box<Expression> },
+    Binary { operator: BinaryOperator, left: Box<Expression>, right: Box<Expression> },
+    IsNull { operand: Box<Expression>, negated: bool },
+    Like { operand: Box<Expression>, pattern: Box<Expression>, negated: bool },
+    InList { operand: Box<Expression>, list: Vec<Expression>, negated: bool },
+}
+
+
+/// Why an expression could not be bound.
+#[derive(Debug, PartialEq, Clone)]
+pub enum BindError {
+    UnknownColumn(String),
+    /// The expression is valid, but not where it is used, such as an aggregate in WHERE.
+    Misuse(String),
+}
+
+
+/// The tables an expression can refer to, in FROM clause order. Rows hold the columns
+/// of every table one after the other.
+pub struct Scope<'a> {
+    tables: Vec<(&'a str, &'a Schema)>,
+}
+
+
+impl<'a> Scope<'a> {
+    /// A scope without columns, for expressions that must be constant.
+    pub fn empty() -> Self {
+        Scope { tables: Vec::new() }
+    }
+
+
+    /// A scope over a single table, whose columns may be qualified with `name`.
+    pub fn new(name: &'a str, schema: &'a Schema) -> Self {
+        Scope { tables: vec![(name, schema)] }
+    }
+
+
+    /// Adds the columns of a joined table after the existing ones.
+    pub fn join(&mut self, name: &'a str, schema: &'a Schema) {
+        self.tables.push((name, schema));
+    }
+
+
+    pub fn num_columns(&self) -> usize {
+        self.tables.iter().map(|(_, schema)| schema.num_columns()).sum()
+    }
+
+
+    /// Every column in row order, with the name of its table.
+    pub fn columns(&self) -> impl Iterator<Item = (&'a str, &'a Column)> + '_ {
+        self.tables
+            .iter()
+            .flat_map(|&(name, schema)| schema.columns.iter().map(move |column| (name, column)))
+    }
+
+
+    /// The row index of a column. An unqualified name must be unique across the tables.
+    pub fn resolve(&self, table: Option<&str>, name: &str) -> Result<usize, BindError> {
+        let mut found = None;
+        for (i, (table_name, column)) in self.columns().enumerate() {
+            let table_matches = table.is_none_or(|table| table.eq_ignore_ascii_case(table_name));
+            if table_matches && column.name.eq_ignore_ascii_case(name) {
+                if found.is_some() {
+                    return Err(BindError::Misuse(format!("column '{}' is ambiguous", name)));
+                }
+                found = Some(i);
+            }
+        }
+
+        found.ok_or_else(|| match table {
+            Some(table) => BindError::UnknownColumn(format!("{}.{}", table, name)),
+            None => BindError::UnknownColumn(name.to_string()),
+        })
+    }
+}
+
+
+impl Expression {
+    /// Resolves the column names of the parsed expression against the tables in scope.
+    /// Aggregate functions are rejected, they need `bind_grouped`.
+    pub fn bind(expr: &ast::Expr, scope: &Scope) -> Result<Self, BindError> {
+        match expr {
+            ast::Expr::Column { table, name } => scope.resolve(table.as_deref(), name).map(Expression::Column),
+            ast::Expr::Aggregate { function, .. } => {
+                Err(BindError::Misuse(format!("{}() is not allowed here", function)))
+            }
+            expr => Self::bind_operands(expr, &mut |operand| Self::bind(operand, scope)),
+        }
+    }
+
+
+    /// Binds an expression that is evaluated once per group, against rows holding the
+    /// values of the bound GROUP BY expressions followed by the aggregate results. Aggregates that are not yet in
+    /// `aggregates` are added to it. Columns must be grouped or inside an aggregate.
+    pub fn bind_grouped(
+        expr: &ast::Expr,
+        scope: &Scope,
+        group_by: &[Expression],
+        aggregates: &mut Vec<Aggregate>,
+    ) -> Result<Self, BindError> {
+        if let Some(i) = Self::bind(expr, scope).ok().and_then(|bound| group_by.iter().position(|g| *g == bound)) {
+            return Ok(Expression::Column(i));
+        }
+
+        match expr {
+            ast::Expr::Column { table, name } => {
+                scope.resolve(table.as_deref(), name)?;
+                Err(BindError::Misuse(format!(
+                    "column '{}' must appear in GROUP BY or be used in an aggregate function", expr,
+                )))
+            }
+            ast::Expr::Aggregate { function, argument } => {
+                let argument = match argument {
+                    Some(argument) => Some(Self::bind(argument, scope)?),
+                    None => None,
+                };
+                let aggregate = Aggregate { function: *function, argument };
+
+                let i = match aggregates.iter().position(|a| *a == aggregate) {
+                    Some(i) => i,
+                    None => {
+                        aggregates.push(aggregate);
+                        aggregates.len() - 1
+                    }
+                };
+                Ok(Expression::Column(group_by.len() + i))
+            }
+            expr => Self::bind_operands(expr, &mut |operand| {
+                Self::bind_grouped(operand, scope, group_by, aggregates)
+            }),
+        }
+    }
+
+
+    /// Binds a literal or operator, binding its operands with `bind`.
+    fn bind_operands(
+        expr: &ast::Expr,
+        bind: &mut dyn FnMut(&ast::Expr) -> Result<Self, BindError>,
+    ) -> Result<Self, BindError> {
+        let expression = match expr {
+            ast::Expr::Literal(value) => Expression::Literal(value.clone()),
+            ast::Expr::Unary { operator, operand } => Expression::Unary {
+                operator: *operator,
+                operand: Box::new(bind(operand)?),
+            },
+            ast::Expr::Binary { operator, left, right } => Expression::Binary {
+                operator: *operator,
+                left: Box::new(bind(left)?),
+                right: Box::new(bind(right)?),
+            },
+            ast::Expr::IsNull { operand, negated } => Expression::IsNull {
+                operand: Box::new(bind(operand)?),
+                negated: *negated,
+            },
+            ast::Expr::Like { operand, pattern, negated } => Expression::Like {
+                operand: Box::new(bind(operand)?),
+                pattern: Box::new(bind(pattern)?),
+                negated: *negated,
+            },
+            ast::Expr::InList { operand, list, negated } => Expression::InList {
+                operand: Box::new(bind(operand)?),
+                list: list.iter().map(&mut *bind).collect::<Result<_, _>>()?,
+                negated: *negated,
+            },
+            ast::Expr::Parameter { index, .. } => Expression::Parameter(*index),
+            ast::Expr::Column { .. } | ast::Expr::Aggregate { .. } => unreachable!("bound by the caller"),
+        };
+
+        Ok(expression)
+    }
+
+
+    /// Evaluates the expression against a row. Comparisons and logic follow SQL's
+    /// three-valued logic, so most operators yield NULL when an operand is NULL.
+    pub fn evaluate(&self, row: &Row) -> Result<Value, String> {
+        self.evaluate_values(&row.values)
+    }
+
+
+    /// Evaluates the expression against the values of a row, column `i` being `values[i]`.
+    pub fn evaluate_values(&self, values: &[Value]) -> Result<Value, String> {
+        self.evaluate_with(values, &[])
+    }
+
+
+    /// Evaluates the expression against the values of a row, with parameter `i` being
+    /// `parameters[i - 1]`.
+    pub fn evaluate_with(&self, values: &[Value], parameters: &[Value]) -> Result<Value, String> {
+        match self {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Column(column) => Ok(values.get(*column).cloned().unwrap_or(Value::Null)),
+            Expression::Parameter(index) => {
+                parameters.get(*index - 1).cloned().ok_or_else(|| format!("no value is bound to parameter {}", index))
+            }
+            Expression::Unary { operator, operand } => {
+                let value = operand.evaluate_with(values, parameters)?;
+                match operator {
+                    UnaryOperator::Not => Ok(Self::truth(&value)?.map_or(Value::Null, |b| Value::Boolean(!b))),
+                    UnaryOperator::Negate => match value {
+                        Value::Null => Ok(Value::Null),
+                        Value::Integer(i) => i.checked_neg().map(Value::Integer).ok_or_else(|| "integer overflow".to_string()),
+                        Value::Real(r) => Ok(Value::Real(-r)),
+                        value => Err(format!("cannot negate {}", Self::type_name(&value))),
+                    },
+                }
+            }
+            Expression::Binary { operator: BinaryOperator::And, left, right } => {
+                // FALSE wins over NULL, NULL wins over TRUE
+                let left = Self::truth(&left.evaluate_with(values, parameters)?)?;
+                if left == Some(false) {
+                    return Ok(Value::Boolean(false));
+                }
+                match (left, Self::truth(&right.evaluate_with(values, parameters)?)?) {
+                    (_, Some(false)) => Ok(Value::Boolean(false)),
+                    (Some(true), Some(true)) => Ok(Value::Boolean(true)),
+                    _ => Ok(Value::Null),
+                }
+            }
+            Expression::Binary { operator: BinaryOperator::Or, left, right } => {
+                // TRUE wins over NULL, NULL wins over FALSE
+                let left = Self::truth(&left.evaluate_with(values, parameters)?)?;
+                if left == Some(true) {
+                    return Ok(Value::Boolean(true));
+                }
+                match (left, Self::truth(&right.evaluate_with(values, parameters)?)?) {
+                    (_, Some(true)) => Ok(Value::Boolean(true)),
+                    (Some(false), Some(false)) => Ok(Value::Boolean(false)),
+                    _ => Ok(Value::Null),
+                }
+            }
+            Expression::Binary { operator, left, right } => {
+                Self::binary(*operator, left.evaluate_with(values, parameters)?, right.evaluate_with(values, parameters)?)
+            }
+            Expression::IsNull { operand, negated } => {
+                Ok(Value::Boolean(operand.evaluate_with(values, parameters)?.is_null() != *negated))
+            }
+            Expression::Like { operand, pattern, negated } => {
+                match (operand.evaluate_with(values, parameters)?, pattern.evaluate_with(values, parameters)?) {
+                    (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+                    (Value::Text(text), Value::Text(pattern)) => {
+                        Ok(Value::Boolean(Self::like(&text, &pattern) != *negated))
+                    }
+                    (text, pattern) => Err(format!(
+                        "cannot apply LIKE to {} and {}", Self::type_name(&text), Self::type_name(&pattern),
+                    )),
+                }
+            }
+            Expression::InList { operand, list, negated } => {
+                let value = operand.evaluate_with(values, parameters)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+
+                // Not found and compared against a NULL: unknown rather than false
+                let mut saw_null = false;
+                for expression in list {
+                    let candidate = expression.evaluate_with(values, parameters)?;
+                    if candidate.is_null() {
+                        saw_null = true;
+                    } else if Self::compare(&value, &candidate)? == Ordering::Equal {
+                        return Ok(Value::Boolean(!*negated));
+                    }
+                }
+
+                Ok(if saw_null { Value::Null } else { Value::Boolean(*negated) })
+            }
+        }
+    }
+
+
+    /// The highest column index the expression refers to, `None` if it refers to none.
+    pub fn max_column(&self) -> Option<usize> {
+        match self {
+            Expression::Literal(_) | Expression::Parameter(_) => None,
+            Expression::Column(column) => Some(*column),
+            Expression::Unary { operand, .. } | Expression::IsNull { operand, .. } => operand.max_column(),
+            Expression::Binary { left, right, .. } => left.max_column().max(right.max_column()),
+            Expression::Like { operand, pattern, .. } => operand.max_column().max(pattern.max_column()),
+            Expression::InList { operand, list, .. } => {
+                list.iter().map(Expression::max_column).fold(operand.max_column(), Option::max)
+            }
+        }
+    }
+
+
+    /// Whether the expression refers to a parameter.
+    pub fn has_parameters(&self) -> bool {
+        match self {
+            Expression::Literal(_) | Expression::Column(_) => false,
+            Expression::Parameter(_) => true,
+            Expression::Unary { operand, .. } | Expression::IsNull { operand, .. } => operand.has_parameters(),
+            Expression::Binary { left, right, .. } => left.has_parameters() || right.has_parameters(),
+            Expression::Like { operand, pattern, .. } => operand.has_parameters() || pattern.has_parameters(),
+            Expression::InList { operand, list, .. } => {
+                operand.has_parameters() || list.iter().any(Expression::has_parameters)
+            }
+        }
+    }
+
+
+    /// Evaluates the expression as a filter. Rows where it is NULL do not match.
+    pub fn matches(&self, row: &Row, parameters: &[Value]) -> Result<bool, String> {
+        Ok(Self::truth(&self.evaluate_with(&row.values, parameters)?)?.unwrap_or(false))
+    }
+
+
+    /// The truth value of a value, `None` for NULL. Numbers are true when non-zero.
+    pub(crate) fn truth(value: &Value) -> Result<Option<bool>, String> {
+        match value {
+            Value::Null => Ok(None),
+            Value::Boolean(b) => Ok(Some(*b)),
+            Value::Integer(i) => Ok(Some(*i != 0)),
+            Value::Real(r) => Ok(Some(*r != 0.0)),
+            value => Err(format!("expected a boolean, found {}", Self::type_name(value))),
+        }
+    }
+
+
+    pub(crate) fn binary(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+        if left.is_null() || right.is_null() {
+            return Ok(Value::Null);
+        }
+
+        let ordering = |matches: fn(Ordering) -> bool| -> Result<Value, String> {
+            Ok(Value::Boolean(matches(Self::compare(&left, &right)?)))
+        };
+
+        match operator {
+            BinaryOperator::Equal => ordering(Ordering::is_eq),
+            BinaryOperator::NotEqual => ordering(Ordering::is_ne),
+            BinaryOperator::Less => ordering(Ordering::is_lt),
+            BinaryOperator::LessEqual => ordering(Ordering::is_le),
+            BinaryOperator::Greater => ordering(Ordering::is_gt),
+            BinaryOperator::GreaterEqual => ordering(Ordering::is_ge),
+            BinaryOperator::And | BinaryOperator::Or => unreachable!("logical operators are evaluated lazily"),
+            _ => Self::arithmetic(operator, &left, &right),
+        }
+    }
+
+
+    /// Integer arithmetic stays integer and fails on overflow. As soon as one side is
+    /// a real the result is a real. Division or modulo by zero yields NULL.
+    fn arithmetic(operator: BinaryOperator, left: &Value, right: &Value) -> Result<Value, String> {
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                let (a, b) = (*a, *b);
+                if b == 0 && matches!(operator, BinaryOperator::Divide | BinaryOperator::Modulo) {
+                    return Ok(Value::Null);
+                }
+
+                let result = match operator {
+                    BinaryOperator::Add => a.checked_add(b),
+                    BinaryOperator::Subtract => a.checked_sub(b),
+                    BinaryOperator::Multiply => a.checked_mul(b),
+                    BinaryOperator::Divide => a.checked_div(b),
+                    BinaryOperator::Modulo => a.checked_rem(b),
+                    _ => unreachable!(),
+                };
+
+                result.map(Value::Integer).ok_or_else(|| "integer overflow".to_string())
+            }
+            (Value::Integer(_) | Value::Real(_), Value::Integer(_) | Value::Real(_)) => {
+                let (a, b) = (Self::as_real(left), Self::as_real(right));
+                if b == 0.0 && matches!(operator, BinaryOperator::Divide | BinaryOperator::Modulo) {
+                    return Ok(Value::Null);
+                }
+
+                Ok(Value::Real(match operator {
+                    BinaryOperator::Add => a + b,
+                    BinaryOperator::Subtract => a - b,
+                    BinaryOperator::Multiply => a * b,
+                    BinaryOperator::Divide => a / b,
+                    BinaryOperator::Modulo => a % b,
+                    _ => unreachable!(),
+                }))
+            }
+            _ => Err(format!(
+                "cannot apply '{}' to {} and {}", operator, Self::type_name(left), Self::type_name(right),
+            )),
+        }
+    }
+
+
+    fn as_real(value: &Value) -> f64 {
+        match value {
+            Value::Integer(i) => *i as f64,
+            Value::Real(r) => *r,
+            _ => unreachable!(),
+        }
+    }
+
+
+    /// Orders two non-NULL values. Integers, reals and booleans compare as numbers,
+    /// text and blobs compare bytewise. Values of other types cannot be compared.
+    pub fn compare(left: &Value, right: &Value) -> Result<Ordering, String> {
+        left.partial_compare(right).ok_or_else(|| {
+            format!("cannot compare {} with {}", Self::type_name(left), Self::type_name(right))
+        })
+    }
+
+
+    pub(crate) fn type_name(value: &Value) -> &'static str {
+        value.column_type().map_or("NULL", |t| t.name())
+    }
+
+
+    /// Matches `text` against a LIKE pattern, where `%` matches any run of characters
+    /// and `_` any single character. ASCII letters match regardless of case.
+    pub fn like(text: &str, pattern: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let pattern: Vec<char> = pattern.chars().collect();
+
+        // Position in the pattern and text to resume at when the last `%` should
+        // swallow one more character
+        let mut backtrack: Option<(usize, usize)> = None;
+        let (mut t, mut p) = (0, 0);
+
+        while t < text.len() {
+            if p < pattern.len() && pattern[p] == '%' {
+                p += 1;
+                backtrack = Some((p, t));
+            } else if p < pattern.len() && (pattern[p] == '_' || pattern[p].eq_ignore_ascii_case(&text[t])) {
+                p += 1;
+                t += 1;
+            } else if let Some((pattern_resume, text_resume)) = backtrack {
+                p = pattern_resume;
+                t = text_resume + 1;
+                backtrack = Some((pattern_resume, text_resume + 1));
+            } else {
+                return false;
+            }
+        }
+
+        pattern[p..].iter().all(|&c| c == '%')
+    }
+}
+
+
+/// Writes the expression as SQL, with columns written as their index, like `#2`.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let negation = |negated: &bool| if *negated { "NOT " } else { "" };
+
+        match self {
+            Expression::Literal(Value::Text(text)) => write!(f, "'{}'", text.replace('\'', "''")),
+            Expression::Literal(value) => write!(f, "{}", value),
+            Expression::Column(column) => write!(f, "#{}", column),
+            Expression::Parameter(index) => write!(f, "?{}", index),
+            Expression::Unary { operator: UnaryOperator::Negate, operand } => write!(f, "-{}", Operand(operand)),
+            Expression::Unary { operator: UnaryOperator::Not, operand } => write!(f, "NOT {}", Operand(operand)),
+            Expression::Binary { operator, left, right } => {
+                write!(f, "{} {} {}", Operand(left), operator, Operand(right))
+            }
+            Expression::IsNull { operand, negated } => write!(f, "{} IS {}NULL", Operand(operand), negation(negated)),
+            Expression::Like { operand, pattern, negated } => {
+                write!(f, "{} {}LIKE {}", Operand(operand), negation(negated), Operand(pattern))
+            }
+            Expression::InList { operand, list, negated } => {
+                let list: Vec<String> = list.iter().map(Expression::to_string).collect();
+                write!(f, "{} {}IN ({})", Operand(operand), negation(negated), list.join(", "))
+            }
+        }
+    }
+}
+
+
+/// An operand of an operator, parenthesized unless it is a literal or a column.
+struct Operand<'a>(&'a Expression);
+
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Expression::Literal(_) | Expression::Column(_) | Expression::Parameter(_) => write!(f, "{}", self.0),
+            expression => write!(f, "({})", expression),
+        }
+    }
+}
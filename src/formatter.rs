@@ -0,0 +1,216 @@
+use std::io::{self, Write};
+use crate::csv;
+use crate::json::JsonValue;
+use crate::table::Row;
+use crate::value::Value;
+
+
+/// How the rows of a result are written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutputMode {
+    /// `(1, alice, NULL)`, one row per line.
+    Tuple,
+    /// Aligned columns inside a box drawn with `+`, `-` and `|`.
+    Table,
+    /// RFC 4180 records, with NULL as an empty field.
+    Csv,
+    /// An array of objects keyed by column name, one object per line.
+    Json,
+    /// `column = value`, one value per line, with a blank line between rows.
+    Line,
+    /// A GitHub flavored markdown table, which always has a header.
+    Markdown,
+}
+
+
+impl OutputMode {
+    pub const ALL: [OutputMode; 6] = [
+        OutputMode::Tuple,
+        OutputMode::Table,
+        OutputMode::Csv,
+        OutputMode::Json,
+        OutputMode::Line,
+        OutputMode::Markdown,
+    ];
+
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputMode::Tuple => "tuple",
+            OutputMode::Table => "table",
+            OutputMode::Csv => "csv",
+            OutputMode::Json => "json",
+            OutputMode::Line => "line",
+            OutputMode::Markdown => "markdown",
+        }
+    }
+}
+
+
+/// Writes the rows of a result in an output mode, with a header naming the columns if
+/// `headers` is set. JSON, line and markdown output always name the columns. Table and
+/// markdown output need the width of every column, so their rows are held until
+/// [`Formatter::finish`].
+pub struct Formatter<W: Write> {
+    out: W,
+    mode: OutputMode,
+    headers: bool,
+    column_names: Vec<String>,
+    held_rows: Vec<Row>,
+    num_rows: usize,
+}
+
+
+impl<W: Write> Formatter<W> {
+    pub fn new(out: W, mode: OutputMode, headers: bool, column_names: Vec<String>) -> Self {
+        Formatter {
+            out,
+            mode,
+            headers,
+            column_names,
+            held_rows: Vec::new(),
+            num_rows: 0,
+        }
+    }
+
+
+    pub fn write_row(&mut self, row: &Row) -> io::Result<()> {
+        if self.num_rows == 0 {
+            self.write_header()?;
+        }
+
+        match self.mode {
+            OutputMode::Tuple => {
+                let values: Vec<String> = row.values.iter().map(Value::to_string).collect();
+                writeln!(self.out, "({})", values.join(", "))?;
+            }
+            OutputMode::Csv => {
+                let fields: Vec<Option<String>> = row.values.iter()
+                    .map(|value| (!value.is_null()).then(|| value.to_string()))
+                    .collect();
+                csv::write_record(&mut self.out, &fields)?;
+            }
+            OutputMode::Json => {
+                if self.num_rows > 0 {
+                    writeln!(self.out, ",")?;
+                }
+                let members = self.column_names.iter().zip(&row.values)
+                    .map(|(name, value)| (name.clone(), JsonValue::from_value(value)))
+                    .collect();
+                write!(self.out, "{}", JsonValue::Object(members))?;
+            }
+            OutputMode::Line => {
+                if self.num_rows > 0 {
+                    writeln!(self.out)?;
+                }
+                let width = self.column_names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+                for (name, value) in self.column_names.iter().zip(&row.values) {
+                    writeln!(self.out, "{:>width$} = {}", name, value, width = width)?;
+                }
+            }
+            OutputMode::Table | OutputMode::Markdown => self.held_rows.push(row.clone()),
+        }
+
+        self.num_rows += 1;
+        Ok(())
+    }
+
+
+    /// Writes what is left after the last row, such as the rows held for a table.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.num_rows == 0 {
+            self.write_header()?;
+        }
+
+        match self.mode {
+            OutputMode::Json => writeln!(self.out, "]")?,
+            OutputMode::Table | OutputMode::Markdown => self.write_table()?,
+            OutputMode::Tuple | OutputMode::Csv | OutputMode::Line => (),
+        }
+        self.out.flush()
+    }
+
+
+    /// Writes what comes before the first row. Tables write their header at the end.
+    fn write_header(&mut self) -> io::Result<()> {
+        match self.mode {
+            OutputMode::Tuple if self.headers => writeln!(self.out, "({})", self.column_names.join(", ")),
+            OutputMode::Csv if self.headers => {
+                let fields: Vec<Option<String>> = self.column_names.iter().cloned().map(Some).collect();
+                csv::write_record(&mut self.out, &fields)
+            }
+            OutputMode::Json => write!(self.out, "["),
+            _ => Ok(()),
+        }
+    }
+
+
+    /// Writes the held rows with every column as wide as its widest value. Numbers are
+    /// aligned to the right.
+    fn write_table(&mut self) -> io::Result<()> {
+        let markdown = self.mode == OutputMode::Markdown;
+        let header = markdown || self.headers;
+        if self.held_rows.is_empty() && !header {
+            return Ok(());
+        }
+
+        let text = |value: &Value| match markdown {
+            true => value.to_string().replace('|', "\\|"),
+            false => value.to_string(),
+        };
+        let cells: Vec<Vec<(String, bool)>> = self.held_rows.iter()
+            .map(|row| row.values.iter()
+                .map(|value| (text(value), matches!(value, Value::Integer(_) | Value::Real(_))))
+                .collect())
+            .collect();
+
+        let mut widths: Vec<usize> = match header {
+            true => self.column_names.iter().map(|name| name.chars().count()).collect(),
+            false => vec![0; self.column_names.len()],
+        };
+        for row in &cells {
+            for (width, (cell, _)) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        if markdown {
+            // The separator line needs at least three dashes
+            widths.iter_mut().for_each(|width| *width = (*width).max(3));
+        }
+
+        let dashes: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+        let border = format!("+{}+", dashes.join("+"));
+        let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+        if !markdown {
+            writeln!(self.out, "{}", border)?;
+        }
+        if header {
+            let names = self.column_names.iter().zip(&widths).map(|(name, &width)| format!("{:<width$}", name)).collect();
+            writeln!(self.out, "{}", line(names))?;
+            match markdown {
+                true => writeln!(self.out, "|{}|", dashes.join("|"))?,
+                false => writeln!(self.out, "{}", border)?,
+            }
+        }
+        for row in cells {
+            let padded = row.into_iter().zip(&widths)
+                .map(|((cell, numeric), &width)| match numeric {
+                    true => format!("{:>width$}", cell),
+                    false => format!("{:<width$}", cell),
+                })
+                .collect();
+            writeln!(self.out, "{}", line(padded))?;
+        }
+        if !markdown && !self.held_rows.is_empty() {
+            writeln!(self.out, "{}", border)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+use std::io::{self, ErrorKind};
+use std::iter;
+use crate::cursor::Cursor;
+use crate::data_consts::SORT_MEMORY_LIMIT;
+use crate::key::Key;
+use crate::schema::Schema;
+use crate::sorter::Sorter;
+use crate::table::{ExecuteResult, Row, Table};
+use crate::value::Value;
+
+
+/// The outcome of importing rows from a file: how many were inserted, and the line
+/// and reason of every one that was not. A failed row does not stop the import.
+#[derive(Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub errors: Vec<(usize, String)>,
+}
+
+
+/// Inserts the rows read from a file into a table. Rows for a table that starts out
+/// empty and has no unique index are sorted by key, spilling to temporary files, and
+/// loaded at the end with [`Table::bulk_load`]. Otherwise, and from the first row
+/// without its rowid on, rows are inserted one at a time.
+pub(crate) struct Importer<'t, 'd> {
+    table: &'t mut Table<'d>,
+    report: ImportReport,
+    /// Rows to bulk load, keyed by their encoded primary key, each preceded by its line.
+    sorter: Option<Sorter>,
+}
+
+
+impl<'t, 'd> Importer<'t, 'd> {
+    pub(crate) fn new(table: &'t mut Table<'d>) -> Self {
+        let bulk = Cursor::table_start(table).end_of_table && !table.entry.indexes.iter().any(|index| index.unique);
+        let sorter = bulk.then(|| Sorter::new(vec![false], SORT_MEMORY_LIMIT));
+
+        Importer { table, report: ImportReport::default(), sorter }
+    }
+
+
+    pub(crate) fn schema(&self) -> &Schema {
+        &self.table.entry.schema
+    }
+
+
+    /// Adds a row read from the given line, or records why it could not be read.
+    pub(crate) fn add(&mut self, line: usize, row: Result<Row, String>) -> io::Result<()> {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                self.error(line, e);
+                return Ok(());
+            }
+        };
+
+        // The rowid given to a row without one depends on the rows inserted before it
+        let rowid_column = self.table.entry.schema.rowid_column();
+        if rowid_column.is_some_and(|column| row.values.get(column).is_some_and(Value::is_null)) {
+            self.load()?;
+        }
+
+        if self.sorter.is_none() {
+            self.insert(line, &row);
+            return Ok(());
+        }
+        match self.check_row(&row) {
+            Ok(key) => {
+                let values = iter::once(Value::Integer(line as i64)).chain(row.values).collect();
+                self.sorter.as_mut().expect("Checked above").add(vec![Value::Blob(key)], Row::new(values))
+            }
+            Err(result) => {
+                self.error(line, result.to_string());
+                Ok(())
+            }
+        }
+    }
+
+
+    pub(crate) fn error(&mut self, line: usize, message: String) {
+        self.report.errors.push((line, message));
+    }
+
+
+    /// Loads the rows kept for bulk loading, and returns the report of the import.
+    pub(crate) fn finish(mut self) -> io::Result<ImportReport> {
+        self.load()?;
+        self.report.errors.sort_by_key(|(line, _)| *line);
+        Ok(self.report)
+    }
+
+
+    /// Checks a row as [`Table::insert_row`] would before it looks at the other rows,
+    /// and returns its key.
+    fn check_row(&mut self, row: &Row) -> Result<Vec<u8>, ExecuteResult> {
+        let key = self.table.check_row(row)?;
+        for (i, index_row) in self.table.index_rows(row).iter().enumerate() {
+            self.table.index_tree(i).check_row(index_row)?;
+        }
+
+        Ok(key)
+    }
+
+
+    /// Bulk loads the rows sorted so far, then inserts one at a time any that the
+    /// table's key order puts before the row ahead of them. Of rows with equal keys,
+    /// the first in the file is kept.
+    fn load(&mut self) -> io::Result<()> {
+        let Some(sorter) = self.sorter.take() else {
+            return Ok(());
+        };
+        let mut rows = sorter.finish()?.map(|row| {
+            row.map(|mut row| {
+                let Value::Integer(line) = row.values.remove(0) else { unreachable!("Rows start with their line") };
+                (line as usize, row)
+            })
+        });
+
+        let schema = self.table.entry.schema.clone();
+        let comparator = self.table.key_comparator;
+        let mut last_key: Option<Vec<u8>> = None;
+        let mut loaded = Vec::new();
+        let mut duplicates = Vec::new();
+        let mut unsorted = None;
+        let mut error = None;
+
+        let result = self.table.bulk_load(iter::from_fn(|| loop {
+            let (line, row) = match rows.next()? {
+                Ok(row) => row,
+                Err(e) => {
+                    error = Some(e);
+                    return None;
+                }
+            };
+            let key = Key::encode(&schema.key_values(&row.values));
+            match last_key.as_ref().map(|last_key| comparator.compare(last_key, &key)) {
+                Some(Ordering::Equal) => duplicates.push(line),
+                Some(Ordering::Greater) => {
+                    unsorted = Some((line, row));
+                    return None;
+                }
+                _ => {
+                    last_key = Some(key);
+                    loaded.push(line);
+                    return Some(row);
+                }
+            }
+        }));
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        match result {
+            ExecuteResult::ExecuteSuccess => self.report.imported += loaded.len(),
+            result => self.report.errors.extend(loaded.into_iter().map(|line| (line, result.to_string()))),
+        }
+        let duplicate = ExecuteResult::ExecuteDuplicateKey.to_string();
+        self.report.errors.extend(duplicates.into_iter().map(|line| (line, duplicate.clone())));
+
+        for row in unsorted.into_iter().map(Ok).chain(rows) {
+            let (line, row) = row?;
+            self.insert(line, &row);
+        }
+        Ok(())
+    }
+
+
+    /// Inserts a row read from the given line, or records why it could not be inserted.
+    fn insert(&mut self, line: usize, row: &Row) {
+        match self.table.insert_row(row) {
+            ExecuteResult::ExecuteSuccess => self.report.imported += 1,
+            result => self.report.errors.push((line, result.to_string())),
+        }
+    }
+}
+
+
+pub(crate) fn unknown_table(name: &str) -> io::Error {
+    io::Error::new(ErrorKind::NotFound, format!("No table named '{}'", name))
+}
@@ -1,33 +1,129 @@
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::process::exit;
+use crate::lexer::Lexer;
+use crate::line_editor::LineEditor;
 
 
 pub struct InputBuffer {
     pub buffer: String,
+    /// Editor the lines are read through when stdin is a terminal.
+    editor: Option<LineEditor>,
+}
+
+
+pub enum ReadResult {
+    ReadSuccess,
+    /// Ctrl-C was pressed, which discards the line.
+    ReadInterrupted,
+    ReadEndOfInput,
 }
 
 
 impl InputBuffer {
+    pub const PROMPT: &'static str = "db > ";
+    /// Prompt for the next line of a statement that is not complete yet.
+    pub const CONTINUATION_PROMPT: &'static str = "   ...> ";
+
+
     pub fn new() -> InputBuffer {
         InputBuffer {
             buffer: String::new(),
+            editor: None,
+        }
+    }
+
+
+    /// Reads lines through a line editor if stdin is a terminal that supports one, with
+    /// the history kept in `~/.crablite_history`. Says so on stderr if the terminal has
+    /// no raw mode, which the editor needs.
+    pub fn with_line_editor() -> InputBuffer {
+        let editor = match LineEditor::open(LineEditor::default_history_path()) {
+            Ok(editor) => editor,
+            Err(e) => {
+                if io::stdin().is_terminal() {
+                    eprintln!("Line editing is not available: {}. Lines are read without it.", e);
+                }
+                None
+            }
+        };
+
+        InputBuffer {
+            buffer: String::new(),
+            editor,
+        }
+    }
+
+
+    /// Adds a complete statement or command to the line editor's history, if there is one.
+    pub fn add_history(&mut self, entry: &str) {
+        if let Some(editor) = &mut self.editor {
+            editor.add_history(entry);
+        }
+    }
+
+
+    /// The prompt for the next line, which continues `statement` unless it holds
+    /// nothing but whitespace and comments.
+    pub fn prompt(statement: &str) -> &'static str {
+        match Lexer::is_blank(statement) {
+            true => Self::PROMPT,
+            false => Self::CONTINUATION_PROMPT,
         }
     }
 
 
     pub fn print_prompt() {
-        print!("db > ");
+        print!("{}", Self::PROMPT);
         io::stdout().flush().expect("Failed to flush stdout")
     }
 
 
-    pub fn read_input(&mut self) {
+    /// Reads a line into the buffer. Returns `false` at the end of the input.
+    pub fn read_input(&mut self) -> bool {
         self.buffer.clear();
-        if io::stdin().read_line(&mut self.buffer).is_err() {
-            println!("Error reading input");
-            exit(1);
+        match io::stdin().read_line(&mut self.buffer) {
+            Ok(0) => return false,
+            Ok(_) => (),
+            Err(_) => {
+                println!("Error reading input");
+                exit(1);
+            }
+        }
+
+        self.buffer = self.buffer.trim_end().to_string();
+        true
+    }
+
+
+    /// Shows the prompt and reads a line into the buffer, through the line editor if
+    /// there is one. `complete` gives the words that can complete the word at the cursor,
+    /// from the text before that word and the word itself.
+    pub fn read_line(&mut self, prompt: &str, complete: &dyn Fn(&str, &str) -> Vec<String>) -> ReadResult {
+        let Some(editor) = &mut self.editor else {
+            print!("{}", prompt);
+            io::stdout().flush().expect("Failed to flush stdout");
+            return match self.read_input() {
+                true => ReadResult::ReadSuccess,
+                false => ReadResult::ReadEndOfInput,
+            };
+        };
+
+        match editor.read_line(prompt, &mut self.buffer, complete) {
+            Ok(result) => {
+                self.buffer = self.buffer.trim_end().to_string();
+                result
+            }
+            Err(_) => {
+                println!("Error reading input");
+                exit(1);
+            }
         }
+    }
+}
+
 
-        self.buffer = self.buffer.trim_end().to_string()
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,436 @@
+use std::cmp::Ordering;
+use std::io;
+use crate::data_consts::PAGE_SIZE;
+use crate::key::{Key, KeyComparator};
+use crate::leaf_node::{LeafNode, NodeType};
+use crate::table::Table;
+
+
+pub struct InternalNode;
+
+
+impl InternalNode {
+    /// Internal Node Header Layout
+    const INTERNAL_NODE_NUM_KEYS_SIZE: usize = std::mem::size_of::<u32>();
+    const INTERNAL_NODE_NUM_KEYS_OFFSET: usize = LeafNode::COMMON_NODE_HEADER_SIZE;
+    const INTERNAL_NODE_RIGHT_CHILD_SIZE: usize = std::mem::size_of::<u32>();
+    const INTERNAL_NODE_RIGHT_CHILD_OFFSET: usize = Self::INTERNAL_NODE_NUM_KEYS_OFFSET + Self::INTERNAL_NODE_NUM_KEYS_SIZE;
+    const INTERNAL_NODE_HEADER_SIZE: usize = LeafNode::COMMON_NODE_HEADER_SIZE + Self::INTERNAL_NODE_NUM_KEYS_SIZE + Self::INTERNAL_NODE_RIGHT_CHILD_SIZE;
+
+    /// Internal Node Body Layout
+    const INTERNAL_NODE_CHILD_SIZE: usize = std::mem::size_of::<u32>();
+    const INTERNAL_NODE_KEY_SIZE: usize = Key::SLOT_SIZE;
+    const INTERNAL_NODE_CELL_SIZE: usize = Self::INTERNAL_NODE_CHILD_SIZE + Self::INTERNAL_NODE_KEY_SIZE;
+    pub const INTERNAL_NODE_MAX_KEYS: usize = (PAGE_SIZE - Self::INTERNAL_NODE_HEADER_SIZE) / Self::INTERNAL_NODE_CELL_SIZE;
+    /// A non-root internal node left with fewer keys by a removal is merged with a
+    /// sibling when they fit in one node.
+    const INTERNAL_NODE_MIN_KEYS: usize = Self::INTERNAL_NODE_MAX_KEYS / 2;
+
+    /// Right child of an internal node that has no children yet
+    pub const INVALID_PAGE_NUM: u32 = u32::MAX;
+
+
+    pub fn internal_node_num_keys(node: &[u8]) -> u32 {
+        let num_keys_bytes = &node[Self::INTERNAL_NODE_NUM_KEYS_OFFSET..Self::INTERNAL_NODE_NUM_KEYS_OFFSET + Self::INTERNAL_NODE_NUM_KEYS_SIZE];
+        u32::from_le_bytes(num_keys_bytes.try_into().unwrap())
+    }
+
+
+    pub fn set_internal_node_num_keys(node: &mut [u8], num_keys: u32) {
+        node[Self::INTERNAL_NODE_NUM_KEYS_OFFSET..Self::INTERNAL_NODE_NUM_KEYS_OFFSET + Self::INTERNAL_NODE_NUM_KEYS_SIZE]
+            .copy_from_slice(&num_keys.to_le_bytes());
+    }
+
+
+    pub fn internal_node_right_child(node: &[u8]) -> u32 {
+        let right_child_bytes = &node[Self::INTERNAL_NODE_RIGHT_CHILD_OFFSET..Self::INTERNAL_NODE_RIGHT_CHILD_OFFSET + Self::INTERNAL_NODE_RIGHT_CHILD_SIZE];
+        u32::from_le_bytes(right_child_bytes.try_into().unwrap())
+    }
+
+
+    pub fn set_internal_node_right_child(node: &mut [u8], right_child: u32) {
+        node[Self::INTERNAL_NODE_RIGHT_CHILD_OFFSET..Self::INTERNAL_NODE_RIGHT_CHILD_OFFSET + Self::INTERNAL_NODE_RIGHT_CHILD_SIZE]
+            .copy_from_slice(&right_child.to_le_bytes());
+    }
+
+
+    fn internal_node_cell_offset(cell_num: usize) -> usize {
+        Self::INTERNAL_NODE_HEADER_SIZE + cell_num * Self::INTERNAL_NODE_CELL_SIZE
+    }
+
+
+    /// Page number of the child at `child_num`. `num_keys` refers to the right child.
+    pub fn internal_node_child(node: &[u8], child_num: usize) -> u32 {
+        let num_keys = Self::internal_node_num_keys(node) as usize;
+        assert!(child_num <= num_keys, "Tried to access child_num {} > num_keys {}", child_num, num_keys);
+
+        if child_num == num_keys {
+            let right_child = Self::internal_node_right_child(node);
+            assert_ne!(right_child, Self::INVALID_PAGE_NUM, "Tried to access right child of node, but was invalid page");
+            return right_child;
+        }
+
+        let offset = Self::internal_node_cell_offset(child_num);
+        u32::from_le_bytes(node[offset..offset + Self::INTERNAL_NODE_CHILD_SIZE].try_into().unwrap())
+    }
+
+
+    pub fn set_internal_node_child(node: &mut [u8], child_num: usize, child: u32) {
+        let offset = Self::internal_node_cell_offset(child_num);
+        node[offset..offset + Self::INTERNAL_NODE_CHILD_SIZE].copy_from_slice(&child.to_le_bytes());
+    }
+
+
+    pub fn internal_node_key(node: &[u8], key_num: usize) -> &[u8] {
+        let offset = Self::internal_node_cell_offset(key_num) + Self::INTERNAL_NODE_CHILD_SIZE;
+        Key::read_slot(&node[offset..offset + Self::INTERNAL_NODE_KEY_SIZE])
+    }
+
+
+    pub fn set_internal_node_key(node: &mut [u8], key_num: usize, key: &[u8]) {
+        let offset = Self::internal_node_cell_offset(key_num) + Self::INTERNAL_NODE_CHILD_SIZE;
+        Key::write_slot(&mut node[offset..offset + Self::INTERNAL_NODE_KEY_SIZE], key);
+    }
+
+
+    pub fn initialize_internal_node(node: &mut [u8]) {
+        LeafNode::set_node_type(node, NodeType::NodeInternal);
+        LeafNode::set_node_root(node, false);
+        Self::set_internal_node_num_keys(node, 0);
+        Self::set_internal_node_right_child(node, Self::INVALID_PAGE_NUM);
+    }
+
+
+    /// Returns the index of the child which should contain the given key.
+    pub fn internal_node_find_child(node: &[u8], key: &[u8], comparator: &dyn KeyComparator) -> usize {
+        let num_keys = Self::internal_node_num_keys(node) as usize;
+
+        // Binary search for the first key that is >= the search key
+        let mut min_index = 0;
+        let mut max_index = num_keys;
+
+        while min_index != max_index {
+            let index = min_index + (max_index - min_index) / 2;
+            let key_to_right = Self::internal_node_key(node, index);
+
+            if comparator.compare(key_to_right, key) != Ordering::Less {
+                max_index = index;
+            } else {
+                min_index = index + 1;
+            }
+        }
+
+        min_index
+    }
+
+
+    pub fn update_internal_node_key(node: &mut [u8], old_key: &[u8], new_key: &[u8], comparator: &dyn KeyComparator) {
+        let old_child_index = Self::internal_node_find_child(node, old_key, comparator);
+
+        // The right child's max key is not stored in this node
+        if old_child_index < Self::internal_node_num_keys(node) as usize {
+            Self::set_internal_node_key(node, old_child_index, new_key);
+        }
+    }
+
+
+    /// Index of the child pointer that refers to the given page, `num_keys` for the right child.
+    pub fn internal_node_child_index(node: &[u8], child_page_num: usize) -> usize {
+        let num_keys = Self::internal_node_num_keys(node) as usize;
+
+        (0..=num_keys)
+            .find(|&i| Self::internal_node_child(node, i) as usize == child_page_num)
+            .expect("Page is not a child of its parent")
+    }
+
+
+    /// Handles splitting the root. The old root is copied to a new page and becomes
+    /// the left child. `right_child_page_num` becomes the right child. The root page
+    /// is reinitialized as an internal node pointing to both.
+    pub fn create_new_root(table: &mut Table, right_child_page_num: usize) -> io::Result<()> {
+        let root_page_num = table.entry.root_page_num;
+        let root_copy = table.pager.get_page(root_page_num)?.to_vec();
+
+        let right_child = table.pager.get_page(right_child_page_num)?;
+        if LeafNode::get_node_type(&root_copy) == NodeType::NodeInternal {
+            Self::initialize_internal_node(right_child);
+        }
+
+        // Fetched after the right child, so both get distinct new pages
+        let left_child_page_num = table.pager.get_unused_page_num();
+
+        // Left child has data copied from old root
+        let left_child = table.pager.get_page(left_child_page_num)?;
+        left_child.copy_from_slice(&root_copy);
+        LeafNode::set_node_root(left_child, false);
+
+        if LeafNode::get_node_type(left_child) == NodeType::NodeInternal {
+            let num_keys = Self::internal_node_num_keys(left_child) as usize;
+            let children: Vec<u32> = (0..=num_keys)
+                .map(|i| Self::internal_node_child(left_child, i))
+                .collect();
+            for child_page_num in children {
+                let child = table.pager.get_page(child_page_num as usize)?;
+                LeafNode::set_node_parent(child, left_child_page_num as u32);
+            }
+        }
+
+        // Root node is a new internal node with one key and two children
+        let left_child_max_key = table.get_node_max_key(left_child_page_num)?;
+        let root = table.pager.get_page(root_page_num)?;
+        Self::initialize_internal_node(root);
+        LeafNode::set_node_root(root, true);
+        Self::set_internal_node_num_keys(root, 1);
+        Self::set_internal_node_child(root, 0, left_child_page_num as u32);
+        Self::set_internal_node_key(root, 0, &left_child_max_key);
+        Self::set_internal_node_right_child(root, right_child_page_num as u32);
+
+        LeafNode::set_node_parent(table.pager.get_page(left_child_page_num)?, root_page_num as u32);
+        LeafNode::set_node_parent(table.pager.get_page(right_child_page_num)?, root_page_num as u32);
+
+        Ok(())
+    }
+
+
+    /// Adds a new child/key pair to the parent that corresponds to the child.
+    pub fn internal_node_insert(table: &mut Table, parent_page_num: usize, child_page_num: usize) -> io::Result<()> {
+        let child_max_key = table.get_node_max_key(child_page_num)?;
+        let parent = table.pager.get_page(parent_page_num)?;
+        let original_num_keys = Self::internal_node_num_keys(parent) as usize;
+
+        if original_num_keys >= Self::INTERNAL_NODE_MAX_KEYS {
+            return Self::internal_node_split_and_insert(table, parent_page_num, child_page_num);
+        }
+
+        let right_child_page_num = Self::internal_node_right_child(parent);
+        if right_child_page_num == Self::INVALID_PAGE_NUM {
+            // An empty internal node, the child becomes its right child
+            Self::set_internal_node_right_child(parent, child_page_num as u32);
+            return Ok(());
+        }
+
+        let right_max_key = table.get_node_max_key(right_child_page_num as usize)?;
+        let comparator = table.key_comparator;
+        let parent = table.pager.get_page(parent_page_num)?;
+        let index = Self::internal_node_find_child(parent, &child_max_key, comparator);
+
+        Self::set_internal_node_num_keys(parent, original_num_keys as u32 + 1);
+
+        if comparator.compare(&child_max_key, &right_max_key) == Ordering::Greater {
+            // Replace right child
+            Self::set_internal_node_child(parent, original_num_keys, right_child_page_num);
+            Self::set_internal_node_key(parent, original_num_keys, &right_max_key);
+            Self::set_internal_node_right_child(parent, child_page_num as u32);
+        } else {
+            // Make room for the new cell
+            let offset_src = Self::internal_node_cell_offset(index);
+            let offset_end = Self::internal_node_cell_offset(original_num_keys);
+            parent.copy_within(offset_src..offset_end, offset_src + Self::INTERNAL_NODE_CELL_SIZE);
+            Self::set_internal_node_child(parent, index, child_page_num as u32);
+            Self::set_internal_node_key(parent, index, &child_max_key);
+        }
+
+        Ok(())
+    }
+
+
+    /// Moves the upper half of a full internal node into a new node, then inserts the
+    /// child into whichever of the two it belongs to and links the new node into the
+    /// parent, splitting upwards as needed.
+    fn internal_node_split_and_insert(table: &mut Table, parent_page_num: usize, child_page_num: usize) -> io::Result<()> {
+        let mut old_page_num = parent_page_num;
+        let old_max = table.get_node_max_key(old_page_num)?;
+        let child_max = table.get_node_max_key(child_page_num)?;
+        let new_page_num = table.pager.get_unused_page_num();
+
+        let splitting_root = LeafNode::is_node_root(table.pager.get_page(old_page_num)?);
+
+        let parent_of_old = if splitting_root {
+            // The old root now lives in the new root's left child
+            Self::create_new_root(table, new_page_num)?;
+            let root = table.pager.get_page(table.entry.root_page_num)?;
+            old_page_num = Self::internal_node_child(root, 0) as usize;
+            table.entry.root_page_num
+        } else {
+            let new_node = table.pager.get_page(new_page_num)?;
+            Self::initialize_internal_node(new_node);
+            LeafNode::node_parent(table.pager.get_page(old_page_num)?) as usize
+        };
+
+        // Move the right child and the upper half of the cells into the new node
+        let old_node = table.pager.get_page(old_page_num)?;
+        let moved_right_child = Self::internal_node_right_child(old_node);
+        Self::set_internal_node_right_child(old_node, Self::INVALID_PAGE_NUM);
+        LeafNode::set_node_parent(table.pager.get_page(moved_right_child as usize)?, new_page_num as u32);
+        Self::internal_node_insert(table, new_page_num, moved_right_child as usize)?;
+
+        for i in (Self::INTERNAL_NODE_MAX_KEYS / 2 + 1..Self::INTERNAL_NODE_MAX_KEYS).rev() {
+            let old_node = table.pager.get_page(old_page_num)?;
+            let moved_child = Self::internal_node_child(old_node, i);
+            Self::set_internal_node_num_keys(old_node, i as u32);
+
+            LeafNode::set_node_parent(table.pager.get_page(moved_child as usize)?, new_page_num as u32);
+            Self::internal_node_insert(table, new_page_num, moved_child as usize)?;
+        }
+
+        // The highest remaining cell becomes the old node's right child
+        let old_node = table.pager.get_page(old_page_num)?;
+        let num_keys = Self::internal_node_num_keys(old_node) as usize;
+        let new_right_child = Self::internal_node_child(old_node, num_keys - 1);
+        Self::set_internal_node_right_child(old_node, new_right_child);
+        Self::set_internal_node_num_keys(old_node, num_keys as u32 - 1);
+
+        // Insert the child into whichever node covers its keys
+        let max_after_split = table.get_node_max_key(old_page_num)?;
+        let destination_page_num = if table.key_comparator.compare(&child_max, &max_after_split) == Ordering::Less {
+            old_page_num
+        } else {
+            new_page_num
+        };
+
+        LeafNode::set_node_parent(table.pager.get_page(child_page_num)?, destination_page_num as u32);
+        Self::internal_node_insert(table, destination_page_num, child_page_num)?;
+
+        let old_new_max = table.get_node_max_key(old_page_num)?;
+        let comparator = table.key_comparator;
+        let parent = table.pager.get_page(parent_of_old)?;
+        Self::update_internal_node_key(parent, &old_max, &old_new_max, comparator);
+
+        if !splitting_root {
+            // Set before inserting, since a split of the parent may move the new node again
+            LeafNode::set_node_parent(table.pager.get_page(new_page_num)?, parent_of_old as u32);
+            Self::internal_node_insert(table, parent_of_old, new_page_num)?;
+        }
+
+        Ok(())
+    }
+
+
+    /// Removes a child that was emptied or merged into a sibling from the node. A node
+    /// left without children is removed from its own parent in turn, one left less than
+    /// half full is merged with a sibling, and a root left with a single child absorbs
+    /// it, so the tree shrinks as rows are deleted. Removed pages are not reused.
+    pub fn internal_node_remove(table: &mut Table, page_num: usize, child_page_num: usize) -> io::Result<()> {
+        let node = table.pager.get_page(page_num)?;
+        let num_keys = Self::internal_node_num_keys(node) as usize;
+        let index = Self::internal_node_child_index(node, child_page_num);
+
+        if num_keys == 0 {
+            // The removed child was the only one
+            if LeafNode::is_node_root(node) {
+                LeafNode::initialize_leaf_node(node);
+                LeafNode::set_node_root(node, true);
+                return Ok(());
+            }
+
+            let parent_page_num = LeafNode::node_parent(node) as usize;
+            return Self::internal_node_remove(table, parent_page_num, page_num);
+        }
+
+        if index == num_keys {
+            // The last cell's child becomes the right child
+            let new_right_child = Self::internal_node_child(node, num_keys - 1);
+            Self::set_internal_node_right_child(node, new_right_child);
+        } else {
+            let offset_src = Self::internal_node_cell_offset(index + 1);
+            let offset_end = Self::internal_node_cell_offset(num_keys);
+            node.copy_within(offset_src..offset_end, Self::internal_node_cell_offset(index));
+        }
+        Self::set_internal_node_num_keys(node, num_keys as u32 - 1);
+
+        if LeafNode::is_node_root(node) {
+            return match num_keys {
+                1 => Self::collapse_root(table),
+                _ => Ok(()),
+            };
+        }
+        if num_keys <= Self::INTERNAL_NODE_MIN_KEYS {
+            return Self::internal_node_merge(table, page_num);
+        }
+
+        Ok(())
+    }
+
+
+    /// Merges the node with the sibling to its right under the same parent, or to its
+    /// left for the parent's right child, if their keys and the separator between them
+    /// fit in one node. The separator comes down as the key of the left node's right
+    /// child, and the children of the right node move to the left one.
+    fn internal_node_merge(table: &mut Table, page_num: usize) -> io::Result<()> {
+        let parent_page_num = LeafNode::node_parent(table.pager.get_page(page_num)?) as usize;
+        let parent = table.pager.get_page(parent_page_num)?;
+        let parent_num_keys = Self::internal_node_num_keys(parent) as usize;
+        if parent_num_keys == 0 {
+            return Ok(());
+        }
+
+        let left_index = Self::internal_node_child_index(parent, page_num).min(parent_num_keys - 1);
+        let left_page_num = Self::internal_node_child(parent, left_index) as usize;
+        let right_page_num = Self::internal_node_child(parent, left_index + 1) as usize;
+        let separator = Self::internal_node_key(parent, left_index).to_vec();
+
+        let right = table.pager.get_page(right_page_num)?;
+        let right_num_keys = Self::internal_node_num_keys(right) as usize;
+        let right_cells: Vec<(u32, Vec<u8>)> = (0..right_num_keys)
+            .map(|i| (Self::internal_node_child(right, i), Self::internal_node_key(right, i).to_vec()))
+            .collect();
+        let right_right_child = Self::internal_node_right_child(right);
+
+        let left = table.pager.get_page(left_page_num)?;
+        let left_num_keys = Self::internal_node_num_keys(left) as usize;
+        if left_num_keys + right_num_keys + 1 > Self::INTERNAL_NODE_MAX_KEYS {
+            return Ok(());
+        }
+
+        let left_right_child = Self::internal_node_right_child(left);
+        Self::set_internal_node_child(left, left_num_keys, left_right_child);
+        Self::set_internal_node_key(left, left_num_keys, &separator);
+        for (i, (child, key)) in right_cells.iter().enumerate() {
+            Self::set_internal_node_child(left, left_num_keys + 1 + i, *child);
+            Self::set_internal_node_key(left, left_num_keys + 1 + i, key);
+        }
+        Self::set_internal_node_right_child(left, right_right_child);
+        Self::set_internal_node_num_keys(left, (left_num_keys + right_num_keys + 1) as u32);
+
+        let moved_children = right_cells.iter().map(|(child, _)| *child).chain([right_right_child]);
+        for child in moved_children {
+            LeafNode::set_node_parent(table.pager.get_page(child as usize)?, left_page_num as u32);
+        }
+
+        let parent = table.pager.get_page(parent_page_num)?;
+        if left_index + 1 < parent_num_keys {
+            let right_separator = Self::internal_node_key(parent, left_index + 1).to_vec();
+            Self::set_internal_node_key(parent, left_index, &right_separator);
+        }
+        Self::internal_node_remove(table, parent_page_num, right_page_num)
+    }
+
+
+    /// Replaces a root that has a single child with a copy of that child, until the root
+    /// has several children or is a leaf. Internal nodes left with a single child by
+    /// earlier deletes would otherwise stay on the path as extra levels.
+    fn collapse_root(table: &mut Table) -> io::Result<()> {
+        let root_page_num = table.entry.root_page_num;
+
+        loop {
+            let root = table.pager.get_page(root_page_num)?;
+            if LeafNode::get_node_type(root) != NodeType::NodeInternal || Self::internal_node_num_keys(root) > 0 {
+                return Ok(());
+            }
+            let child_page_num = Self::internal_node_right_child(root) as usize;
+            let child_copy = table.pager.get_page(child_page_num)?.to_vec();
+
+            let root = table.pager.get_page(root_page_num)?;
+            root.copy_from_slice(&child_copy);
+            LeafNode::set_node_root(root, true);
+
+            if LeafNode::get_node_type(root) == NodeType::NodeInternal {
+                let num_keys = Self::internal_node_num_keys(root) as usize;
+                let children: Vec<u32> = (0..=num_keys).map(|i| Self::internal_node_child(root, i)).collect();
+                for grandchild_page_num in children {
+                    LeafNode::set_node_parent(table.pager.get_page(grandchild_page_num as usize)?, root_page_num as u32);
+                }
+            }
+        }
+    }
+}
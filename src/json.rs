@@ -0,0 +1,410 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::iter::Peekable;
+use std::str::CharIndices;
+use crate::cursor::Cursor;
+use crate::database::Database;
+use crate::import::{self, ImportReport, Importer};
+use crate::schema::Schema;
+use crate::table::Row;
+use crate::value::{ColumnType, Value};
+
+
+/// A JSON value. Numbers without a fraction or exponent that fit an `i64` are integers.
+/// Object members keep their order.
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonValue {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+
+impl JsonValue {
+    /// Parses a JSON text holding a single value, with whitespace allowed around it.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = JsonParser {
+            text,
+            chars: text.char_indices().peekable(),
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        match parser.chars.peek() {
+            None => Ok(value),
+            Some(&(position, _)) => Err(format!("Unexpected character at column {}", position + 1)),
+        }
+    }
+
+
+    /// The object of the row's values, keyed by column name in column order.
+    pub fn from_row(schema: &Schema, row: &Row) -> Self {
+        let members = schema.columns.iter().zip(&row.values)
+            .map(|(column, value)| (column.name.clone(), Self::from_value(value)))
+            .collect();
+
+        JsonValue::Object(members)
+    }
+
+
+    /// The JSON form of a value. Blobs are written as their `x'..'` literal, and reals
+    /// that are not finite as null.
+    pub fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonValue::Null,
+            Value::Integer(i) => JsonValue::Integer(*i),
+            Value::Real(r) if r.is_finite() => JsonValue::Real(*r),
+            Value::Real(_) => JsonValue::Null,
+            Value::Text(text) => JsonValue::String(text.clone()),
+            Value::Blob(_) => JsonValue::String(value.to_string()),
+            Value::Boolean(b) => JsonValue::Boolean(*b),
+        }
+    }
+
+
+    /// Builds a row from an object, matching member names with column names. Columns
+    /// without a member are NULL. Values are converted to the column type where no
+    /// information is lost, and strings for blob columns are read as `x'..'` literals.
+    pub fn to_row(&self, schema: &Schema) -> Result<Row, String> {
+        let JsonValue::Object(members) = self else {
+            return Err(String::from("Expected a JSON object"));
+        };
+
+        let mut values = vec![Value::Null; schema.num_columns()];
+        let mut given = vec![false; schema.num_columns()];
+        for (name, member) in members {
+            let Some(i) = schema.column_index(name) else {
+                return Err(format!("No column '{}'", name));
+            };
+            if given[i] {
+                return Err(format!("Column '{}' is given more than once", name));
+            }
+            given[i] = true;
+
+            let column = &schema.columns[i];
+            let value = match (member, column.column_type) {
+                (JsonValue::String(text), ColumnType::Blob) => Value::parse_literal(text, ColumnType::Blob),
+                (member, column_type) => member.to_value().and_then(|value| value.coerce(column_type)),
+            };
+            values[i] = value
+                .ok_or_else(|| format!("Invalid {} value '{}' for column '{}'", column.column_type, member, column.name))?;
+        }
+
+        Ok(Row::new(values))
+    }
+
+
+    /// The value of a scalar, without converting it to any column type.
+    fn to_value(&self) -> Option<Value> {
+        match self {
+            JsonValue::Null => Some(Value::Null),
+            JsonValue::Boolean(b) => Some(Value::Boolean(*b)),
+            JsonValue::Integer(i) => Some(Value::Integer(*i)),
+            JsonValue::Real(r) => Some(Value::Real(*r)),
+            JsonValue::String(text) => Some(Value::Text(text.clone())),
+            JsonValue::Array(_) | JsonValue::Object(_) => None,
+        }
+    }
+
+
+    fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in text.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+
+/// Writes the value as compact JSON, on a single line.
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Boolean(b) => write!(f, "{}", b),
+            JsonValue::Integer(i) => write!(f, "{}", i),
+            JsonValue::Real(r) => write!(f, "{:?}", r),
+            JsonValue::String(text) => Self::write_string(f, text),
+            JsonValue::Array(elements) => {
+                f.write_str("[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                f.write_str("]")
+            }
+            JsonValue::Object(members) => {
+                f.write_str("{")?;
+                for (i, (name, member)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    Self::write_string(f, name)?;
+                    write!(f, ":{}", member)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+
+struct JsonParser<'t> {
+    text: &'t str,
+    chars: Peekable<CharIndices<'t>>,
+}
+
+
+impl JsonParser<'_> {
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        let Some(&(position, c)) = self.chars.peek() else {
+            return Err(String::from("Unexpected end of JSON"));
+        };
+
+        match c {
+            '{' => self.parse_object(),
+            '[' => self.parse_array(),
+            '"' => self.parse_string().map(JsonValue::String),
+            '-' | '0'..='9' => self.parse_number(),
+            _ => {
+                let word: String = std::iter::from_fn(|| self.chars.next_if(|(_, c)| c.is_ascii_alphabetic()))
+                    .map(|(_, c)| c)
+                    .collect();
+                match word.as_str() {
+                    "null" => Ok(JsonValue::Null),
+                    "true" => Ok(JsonValue::Boolean(true)),
+                    "false" => Ok(JsonValue::Boolean(false)),
+                    _ => Err(format!("Unexpected character at column {}", position + 1)),
+                }
+            }
+        }
+    }
+
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.chars.next_if(|&(_, c)| c == '}').is_some() {
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            members.push((name, self.parse_value()?));
+
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(JsonValue::Object(members)),
+                Some((position, _)) => return Err(format!("Expected ',' or '}}' at column {}", position + 1)),
+                None => return Err(String::from("Unexpected end of JSON")),
+            }
+        }
+    }
+
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if self.chars.next_if(|&(_, c)| c == ']').is_some() {
+            return Ok(JsonValue::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(JsonValue::Array(elements)),
+                Some((position, _)) => return Err(format!("Expected ',' or ']' at column {}", position + 1)),
+                None => return Err(String::from("Unexpected end of JSON")),
+            }
+        }
+    }
+
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(text),
+                Some((_, '\\')) => {
+                    let escaped = match self.chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '/')) => '/',
+                        Some((_, 'b')) => '\u{8}',
+                        Some((_, 'f')) => '\u{c}',
+                        Some((_, 'n')) => '\n',
+                        Some((_, 'r')) => '\r',
+                        Some((_, 't')) => '\t',
+                        Some((position, 'u')) => self.parse_unicode_escape(position)?,
+                        Some((position, _)) => return Err(format!("Invalid escape at column {}", position + 1)),
+                        None => return Err(String::from("Unterminated string")),
+                    };
+                    text.push(escaped);
+                }
+                Some((position, c)) if c.is_control() => {
+                    return Err(format!("Unescaped control character at column {}", position + 1));
+                }
+                Some((_, c)) => text.push(c),
+                None => return Err(String::from("Unterminated string")),
+            }
+        }
+    }
+
+
+    /// Reads the four hex digits after `\u`, and a second escape after a high surrogate.
+    fn parse_unicode_escape(&mut self, position: usize) -> Result<char, String> {
+        let invalid = || format!("Invalid unicode escape at column {}", position + 1);
+
+        let first = self.parse_hex_digits().ok_or_else(invalid)?;
+        let code = if (0xD800..0xDC00).contains(&first) {
+            let second = match (self.chars.next(), self.chars.next()) {
+                (Some((_, '\\')), Some((_, 'u'))) => self.parse_hex_digits().ok_or_else(invalid)?,
+                _ => return Err(invalid()),
+            };
+            if !(0xDC00..0xE000).contains(&second) {
+                return Err(invalid());
+            }
+            0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+        } else {
+            first
+        };
+
+        char::from_u32(code).ok_or_else(invalid)
+    }
+
+
+    fn parse_hex_digits(&mut self) -> Option<u32> {
+        let digits: String = (0..4).filter_map(|_| self.chars.next_if(|(_, c)| c.is_ascii_hexdigit())).map(|(_, c)| c).collect();
+        u32::from_str_radix(&digits, 16).ok().filter(|_| digits.len() == 4)
+    }
+
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.chars.peek().map_or(self.text.len(), |&(position, _)| position);
+        while self.chars.next_if(|&(_, c)| matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9')).is_some() {}
+        let end = self.chars.peek().map_or(self.text.len(), |&(position, _)| position);
+        let number = &self.text[start..end];
+
+        if !Self::is_number(number) {
+            return Err(format!("Invalid number '{}' at column {}", number, start + 1));
+        }
+        match number.parse::<i64>() {
+            Ok(i) => Ok(JsonValue::Integer(i)),
+            Err(_) => number.parse::<f64>()
+                .map(JsonValue::Real)
+                .map_err(|_| format!("Invalid number '{}' at column {}", number, start + 1)),
+        }
+    }
+
+
+    /// Whether the text follows the JSON number grammar, which is stricter than Rust's:
+    /// `-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?`.
+    fn is_number(number: &str) -> bool {
+        let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+        let rest = number.strip_prefix('-').unwrap_or(number);
+        let integer = digits(rest);
+        if integer == 0 || (integer > 1 && rest.starts_with('0')) {
+            return false;
+        }
+        let mut rest = &rest[integer..];
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let count = digits(fraction);
+            if count == 0 {
+                return false;
+            }
+            rest = &fraction[count..];
+        }
+        if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+            let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            let count = digits(exponent);
+            if count == 0 {
+                return false;
+            }
+            rest = &exponent[count..];
+        }
+
+        rest.is_empty()
+    }
+
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((position, _)) => Err(format!("Expected '{}' at column {}", expected, position + 1)),
+            None => Err(String::from("Unexpected end of JSON")),
+        }
+    }
+
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|&(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r')).is_some() {}
+    }
+}
+
+
+impl Database {
+    /// Inserts the objects of an NDJSON file, one per line, into the table. Members
+    /// are matched with columns by name, as in [`JsonValue::to_row`]. Lines that cannot
+    /// be parsed or inserted are reported and skipped, and blank lines are ignored.
+    pub fn import_json(&mut self, table_name: &str, input: impl BufRead) -> io::Result<ImportReport> {
+        let mut table = self.table(table_name).ok_or_else(|| import::unknown_table(table_name))?;
+        let mut importer = Importer::new(&mut table);
+
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = JsonValue::parse(&line).and_then(|object| object.to_row(importer.schema()));
+            importer.add(i + 1, row)?;
+        }
+
+        importer.finish()
+    }
+
+
+    /// Writes the rows of the table in primary key order as NDJSON, one object per
+    /// line. Returns the number of rows written.
+    pub fn export_json(&mut self, table_name: &str, out: &mut impl Write) -> io::Result<usize> {
+        let mut table = self.table(table_name).ok_or_else(|| import::unknown_table(table_name))?;
+        let schema = table.entry.schema.clone();
+
+        let mut count = 0;
+        let mut cursor = Cursor::table_start(&mut table);
+        while !cursor.end_of_table {
+            let row = Row::deserialize_row(cursor.cursor_value())?;
+            writeln!(out, "{}", JsonValue::from_row(&schema, &row))?;
+            count += 1;
+            cursor.cursor_advance();
+        }
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,191 @@
+use std::cmp::Ordering;
+use crate::data_consts::KEY_SIZE;
+use crate::value::Value;
+
+
+/// Orders the encoded keys stored in leaf and internal nodes.
+pub trait KeyComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+
+    /// Whether keys are ordered byte by byte, which is the order of the values they
+    /// encode. Reading a range of the primary key and taking the largest rowid from the
+    /// rightmost leaf rely on it, and are replaced by full scans otherwise.
+    fn is_bytewise(&self) -> bool {
+        false
+    }
+}
+
+
+/// Compares encoded keys byte by byte. Correct for every key produced by `Key::encode`,
+/// since that encoding preserves the order of the values.
+pub struct BytewiseComparator;
+
+
+impl KeyComparator for BytewiseComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.cmp(b)
+    }
+
+
+    fn is_bytewise(&self) -> bool {
+        true
+    }
+}
+
+
+pub struct Key;
+
+
+impl Key {
+    /// Key encoding: every value is a one byte type tag followed by a payload whose
+    /// byte order matches the order of the values. Integers are big endian with the
+    /// sign bit flipped, reals are big endian with the sign bit flipped for positive
+    /// numbers and all bits flipped for negative ones. Text and blobs escape 0x00 as
+    /// 0x00 0xFF and are terminated by 0x00 0x00, so that a prefix sorts first.
+    /// Composite keys are the concatenation of their encoded values.
+    const TAG_NULL: u8 = 0;
+    const TAG_BOOLEAN: u8 = 1;
+    const TAG_INTEGER: u8 = 2;
+    const TAG_REAL: u8 = 3;
+    const TAG_TEXT: u8 = 4;
+    const TAG_BLOB: u8 = 5;
+    const ESCAPE: u8 = 0x00;
+    const ESCAPED_ZERO: u8 = 0xFF;
+    const TERMINATOR: u8 = 0x00;
+
+    /// Key slot layout in node cells: a u16 length followed by up to KEY_SIZE key bytes.
+    const SLOT_LENGTH_SIZE: usize = std::mem::size_of::<u16>();
+    pub const SLOT_SIZE: usize = Self::SLOT_LENGTH_SIZE + KEY_SIZE;
+
+
+    pub fn encode(values: &[Value]) -> Vec<u8> {
+        let mut key = Vec::new();
+
+        for value in values {
+            match value {
+                Value::Null => key.push(Self::TAG_NULL),
+                Value::Boolean(b) => {
+                    key.push(Self::TAG_BOOLEAN);
+                    key.push(*b as u8);
+                }
+                Value::Integer(i) => {
+                    key.push(Self::TAG_INTEGER);
+                    key.extend_from_slice(&((*i as u64) ^ (1 << 63)).to_be_bytes());
+                }
+                Value::Real(r) => {
+                    // -0.0 equals 0.0, so both must be the same key
+                    let bits = if *r == 0.0 { 0.0f64.to_bits() } else { r.to_bits() };
+                    let ordered = if bits >> 63 == 1 { !bits } else { bits ^ (1 << 63) };
+                    key.push(Self::TAG_REAL);
+                    key.extend_from_slice(&ordered.to_be_bytes());
+                }
+                Value::Text(text) => {
+                    key.push(Self::TAG_TEXT);
+                    Self::encode_bytes(text.as_bytes(), &mut key);
+                }
+                Value::Blob(bytes) => {
+                    key.push(Self::TAG_BLOB);
+                    Self::encode_bytes(bytes, &mut key);
+                }
+            }
+        }
+
+        key
+    }
+
+
+    fn encode_bytes(bytes: &[u8], key: &mut Vec<u8>) {
+        for &byte in bytes {
+            key.push(byte);
+            if byte == Self::ESCAPE {
+                key.push(Self::ESCAPED_ZERO);
+            }
+        }
+
+        key.push(Self::ESCAPE);
+        key.push(Self::TERMINATOR);
+    }
+
+
+    pub fn decode(key: &[u8]) -> Vec<Value> {
+        let mut values = Vec::new();
+        let mut offset = 0;
+
+        while offset < key.len() {
+            let tag = key[offset];
+            offset += 1;
+
+            let value = match tag {
+                Self::TAG_NULL => Value::Null,
+                Self::TAG_BOOLEAN => {
+                    offset += 1;
+                    Value::Boolean(key[offset - 1] != 0)
+                }
+                Self::TAG_INTEGER => {
+                    let bits = u64::from_be_bytes(key[offset..offset + 8].try_into().unwrap());
+                    offset += 8;
+                    Value::Integer((bits ^ (1 << 63)) as i64)
+                }
+                Self::TAG_REAL => {
+                    let ordered = u64::from_be_bytes(key[offset..offset + 8].try_into().unwrap());
+                    offset += 8;
+                    let bits = if ordered >> 63 == 1 { ordered ^ (1 << 63) } else { !ordered };
+                    Value::Real(f64::from_bits(bits))
+                }
+                Self::TAG_TEXT => {
+                    let bytes = Self::decode_bytes(key, &mut offset);
+                    Value::Text(String::from_utf8_lossy(&bytes).into_owned())
+                }
+                Self::TAG_BLOB => Value::Blob(Self::decode_bytes(key, &mut offset)),
+                tag => panic!("Corrupt key: unknown value tag {}", tag),
+            };
+
+            values.push(value);
+        }
+
+        values
+    }
+
+
+    fn decode_bytes(key: &[u8], offset: &mut usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        loop {
+            let byte = key[*offset];
+            *offset += 1;
+
+            if byte != Self::ESCAPE {
+                bytes.push(byte);
+                continue;
+            }
+
+            let next = key[*offset];
+            *offset += 1;
+            if next == Self::TERMINATOR {
+                return bytes;
+            }
+            bytes.push(Self::ESCAPE);
+        }
+    }
+
+
+    pub fn read_slot(slot: &[u8]) -> &[u8] {
+        let length = u16::from_le_bytes([slot[0], slot[1]]) as usize;
+        &slot[Self::SLOT_LENGTH_SIZE..Self::SLOT_LENGTH_SIZE + length]
+    }
+
+
+    pub fn write_slot(slot: &mut [u8], key: &[u8]) {
+        assert!(key.len() <= KEY_SIZE, "Key does not fit in slot");
+
+        slot[..Self::SLOT_LENGTH_SIZE].copy_from_slice(&(key.len() as u16).to_le_bytes());
+        slot[Self::SLOT_LENGTH_SIZE..Self::SLOT_LENGTH_SIZE + key.len()].copy_from_slice(key);
+        slot[Self::SLOT_LENGTH_SIZE + key.len()..Self::SLOT_SIZE].fill(0);
+    }
+
+
+    pub fn format(key: &[u8]) -> String {
+        let values: Vec<String> = Self::decode(key).iter().map(Value::to_string).collect();
+        values.join(", ")
+    }
+}
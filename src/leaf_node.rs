@@ -1,9 +1,11 @@
 use std::io;
-use std::io::ErrorKind;
 use crate::leaf_node::NodeType::NodeLeaf;
 use crate::cursor::Cursor;
 use crate::data_consts::{PAGE_SIZE, ROW_SIZE};
+use crate::internal_node::InternalNode;
+use crate::key::Key;
 use crate::Row;
+use crate::table::Table;
 
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -25,16 +27,18 @@ impl<'a> LeafNode<'a> {
     const IS_ROOT_OFFSET: usize = Self::NODE_TYPE_SIZE;
     const PARENT_POINTER_SIZE: usize = std::mem::size_of::<u32>();
     const PARENT_POINTER_OFFSET: usize = Self::IS_ROOT_OFFSET + Self::IS_ROOT_SIZE;
-    const COMMON_NODE_HEADER_SIZE: usize = Self::NODE_TYPE_SIZE + Self::IS_ROOT_SIZE + Self::PARENT_POINTER_SIZE;
+    pub(crate) const COMMON_NODE_HEADER_SIZE: usize = Self::NODE_TYPE_SIZE + Self::IS_ROOT_SIZE + Self::PARENT_POINTER_SIZE;
 
     /// Leaf Node Header Layout
     const LEAF_NODE_NUM_CELL_SIZE: usize = std::mem::size_of::<u32>();
     const LEAF_NODE_NUM_CELL_OFFSET: usize = Self::COMMON_NODE_HEADER_SIZE;
-    const LEAF_NODE_HEADER_SIZE: usize = Self::COMMON_NODE_HEADER_SIZE + Self::LEAF_NODE_NUM_CELL_SIZE;
+    const LEAF_NODE_NEXT_LEAF_SIZE: usize = std::mem::size_of::<u32>();
+    const LEAF_NODE_NEXT_LEAF_OFFSET: usize = Self::LEAF_NODE_NUM_CELL_OFFSET + Self::LEAF_NODE_NUM_CELL_SIZE;
+    const LEAF_NODE_HEADER_SIZE: usize = Self::COMMON_NODE_HEADER_SIZE + Self::LEAF_NODE_NUM_CELL_SIZE + Self::LEAF_NODE_NEXT_LEAF_SIZE;
 
 
     /// Leaf Node Body Layout
-    const LEAF_NODE_KEY_SIZE: usize = std::mem::size_of::<u32>();
+    const LEAF_NODE_KEY_SIZE: usize = Key::SLOT_SIZE;
     const LEAF_NODE_KEY_OFFSET: usize = 0;
     const LEAF_NODE_VALUE_SIZE: usize = ROW_SIZE;
     const LEAF_NODE_VALUE_OFFSET: usize = Self::LEAF_NODE_KEY_OFFSET + Self::LEAF_NODE_KEY_SIZE;
@@ -42,22 +46,43 @@ impl<'a> LeafNode<'a> {
     const LEAF_NODE_SPACE_FOR_CELLS: usize = PAGE_SIZE - Self::LEAF_NODE_HEADER_SIZE;
     pub const LEAF_NODE_MAX_CELLS: usize = Self::LEAF_NODE_SPACE_FOR_CELLS / Self::LEAF_NODE_CELL_SIZE;
 
+    /// Cells are split evenly between the old and new node, the extra one going left.
+    const LEAF_NODE_RIGHT_SPLIT_COUNT: usize = Self::LEAF_NODE_MAX_CELLS.div_ceil(2);
+    const LEAF_NODE_LEFT_SPLIT_COUNT: usize = (Self::LEAF_NODE_MAX_CELLS + 1) - Self::LEAF_NODE_RIGHT_SPLIT_COUNT;
+
+    /// A non-root leaf left with fewer cells by a delete is merged with a sibling or
+    /// takes cells from it.
+    const LEAF_NODE_MIN_CELLS: usize = Self::LEAF_NODE_MAX_CELLS / 2;
+
 
     pub fn new(node: &'a mut [u8]) -> Self {
         LeafNode { node }
     }
 
 
-    pub fn leaf_node_num_cells(node: &mut [u8]) -> &mut u32 {
-        unsafe {
-            &mut *(node.as_ptr().add(Self::LEAF_NODE_NUM_CELL_OFFSET) as *mut u32)
-        }
+    pub fn leaf_node_num_cells(node: &[u8]) -> u32 {
+        let num_cells_bytes = &node[Self::LEAF_NODE_NUM_CELL_OFFSET..Self::LEAF_NODE_NUM_CELL_OFFSET + Self::LEAF_NODE_NUM_CELL_SIZE];
+        u32::from_le_bytes(num_cells_bytes.try_into().unwrap())
     }
 
 
-    pub fn leaf_node_num_cells_safe(node: &mut [u8]) -> u32 {
-        let num_cells_bytes = &node[Self::LEAF_NODE_NUM_CELL_OFFSET..Self::LEAF_NODE_NUM_CELL_OFFSET + Self::LEAF_NODE_NUM_CELL_SIZE];
-        u32::from_le_bytes(num_cells_bytes.try_into().unwrap())
+    pub fn set_leaf_node_num_cells(node: &mut [u8], num_cells: u32) {
+        node[Self::LEAF_NODE_NUM_CELL_OFFSET..Self::LEAF_NODE_NUM_CELL_OFFSET + Self::LEAF_NODE_NUM_CELL_SIZE]
+            .copy_from_slice(&num_cells.to_le_bytes());
+    }
+
+
+    /// Page number of the next leaf to the right. 0 means this is the rightmost leaf,
+    /// since page 0 can never be a non-root leaf.
+    pub fn leaf_node_next_leaf(node: &[u8]) -> u32 {
+        let next_leaf_bytes = &node[Self::LEAF_NODE_NEXT_LEAF_OFFSET..Self::LEAF_NODE_NEXT_LEAF_OFFSET + Self::LEAF_NODE_NEXT_LEAF_SIZE];
+        u32::from_le_bytes(next_leaf_bytes.try_into().unwrap())
+    }
+
+
+    pub fn set_leaf_node_next_leaf(node: &mut [u8], next_leaf: u32) {
+        node[Self::LEAF_NODE_NEXT_LEAF_OFFSET..Self::LEAF_NODE_NEXT_LEAF_OFFSET + Self::LEAF_NODE_NEXT_LEAF_SIZE]
+            .copy_from_slice(&next_leaf.to_le_bytes());
     }
 
 
@@ -67,8 +92,14 @@ impl<'a> LeafNode<'a> {
     }
 
 
-    pub fn leaf_node_key(node: &mut [u8], cell_num: usize) -> &mut [u8] {
-        &mut Self::leaf_node_cell(node, cell_num)[..Self::LEAF_NODE_KEY_SIZE]
+    pub fn leaf_node_key(node: &[u8], cell_num: usize) -> &[u8] {
+        let offset = Self::LEAF_NODE_HEADER_SIZE + cell_num * Self::LEAF_NODE_CELL_SIZE + Self::LEAF_NODE_KEY_OFFSET;
+        Key::read_slot(&node[offset..offset + Self::LEAF_NODE_KEY_SIZE])
+    }
+
+
+    pub fn set_leaf_node_key(node: &mut [u8], cell_num: usize, key: &[u8]) {
+        Key::write_slot(&mut Self::leaf_node_cell(node, cell_num)[..Self::LEAF_NODE_KEY_SIZE], key);
     }
 
 
@@ -80,60 +111,240 @@ impl<'a> LeafNode<'a> {
 
     pub fn initialize_leaf_node(node: &mut [u8]) {
         Self::set_node_type(node, NodeLeaf);
-        *Self::leaf_node_num_cells(node) = 0
+        Self::set_node_root(node, false);
+        Self::set_leaf_node_num_cells(node, 0);
+        Self::set_leaf_node_next_leaf(node, 0);
     }
 
 
-    pub fn leaf_node_insert(cursor: &mut Cursor, key: u32, value: &Row) -> io::Result<()> {
+    pub fn leaf_node_insert(cursor: &mut Cursor, key: &[u8], value: &Row) -> io::Result<()> {
         let node = cursor.table.pager.get_page(cursor.page_num)?;
 
-        let num_cells = *Self::leaf_node_num_cells(node);
+        let num_cells = Self::leaf_node_num_cells(node);
         if num_cells as usize >= Self::LEAF_NODE_MAX_CELLS {
             // Node full
-            return Err(io::Error::new(ErrorKind::Other, "Need to implement splitting a leaf node."));
+            return Self::leaf_node_split_and_insert(cursor, key, value);
         }
 
         if cursor.cell_num < num_cells as usize {
             // Make room for new cell
-            for i in (cursor.cell_num..num_cells as usize).rev() {
-                let offset_src = Self::LEAF_NODE_HEADER_SIZE + i * Self::LEAF_NODE_CELL_SIZE;
-                let offset_dest = Self::LEAF_NODE_HEADER_SIZE + (i + 1) * Self::LEAF_NODE_CELL_SIZE;
-
-                unsafe {
-                    std::ptr::copy(
-                        node.as_ptr().add(offset_src),
-                        node.as_mut_ptr().add(offset_dest),
-                        Self::LEAF_NODE_CELL_SIZE
-                    );
-                }
+            let offset_src = Self::LEAF_NODE_HEADER_SIZE + cursor.cell_num * Self::LEAF_NODE_CELL_SIZE;
+            let offset_end = Self::LEAF_NODE_HEADER_SIZE + num_cells as usize * Self::LEAF_NODE_CELL_SIZE;
+            node.copy_within(offset_src..offset_end, offset_src + Self::LEAF_NODE_CELL_SIZE);
+        }
+
+        Self::set_leaf_node_num_cells(node, num_cells + 1);
+        Self::set_leaf_node_key(node, cursor.cell_num, key);
+        value.serialize_row(Self::leaf_node_value(node, cursor.cell_num));
+
+        Ok(())
+    }
+
+
+    /// Removes the cell under the cursor. A non-root leaf left less than half full is
+    /// rebalanced with a sibling.
+    pub fn leaf_node_delete(cursor: &mut Cursor) -> io::Result<()> {
+        let node = cursor.table.pager.get_page(cursor.page_num)?;
+        let num_cells = Self::leaf_node_num_cells(node) as usize;
+
+        let offset_dst = Self::LEAF_NODE_HEADER_SIZE + cursor.cell_num * Self::LEAF_NODE_CELL_SIZE;
+        let offset_end = Self::LEAF_NODE_HEADER_SIZE + num_cells * Self::LEAF_NODE_CELL_SIZE;
+        node.copy_within(offset_dst + Self::LEAF_NODE_CELL_SIZE..offset_end, offset_dst);
+        Self::set_leaf_node_num_cells(node, num_cells as u32 - 1);
+
+        if num_cells > Self::LEAF_NODE_MIN_CELLS || Self::is_node_root(node) {
+            return Ok(());
+        }
+
+        Self::leaf_node_rebalance(cursor.table, cursor.page_num)
+    }
+
+
+    /// Merges the leaf with the sibling to its right under the same parent, or to its
+    /// left for the parent's right child, when their cells fit in one leaf. Otherwise
+    /// the cells are shared evenly between the two. The separator key of the left leaf
+    /// becomes its largest key, or the separator of the right leaf it absorbs. A leaf
+    /// without a sibling is only removed once it is empty.
+    fn leaf_node_rebalance(table: &mut Table, page_num: usize) -> io::Result<()> {
+        let node = table.pager.get_page(page_num)?;
+        let num_cells = Self::leaf_node_num_cells(node);
+        let next_leaf = Self::leaf_node_next_leaf(node);
+        let parent_page_num = Self::node_parent(node) as usize;
+        let parent = table.pager.get_page(parent_page_num)?;
+        let num_keys = InternalNode::internal_node_num_keys(parent) as usize;
+        let index = InternalNode::internal_node_child_index(parent, page_num);
+
+        if num_keys == 0 {
+            if num_cells > 0 {
+                return Ok(());
+            }
+            if let Some(previous_page_num) = Self::previous_leaf(table, page_num)? {
+                Self::set_leaf_node_next_leaf(table.pager.get_page(previous_page_num)?, next_leaf);
+            }
+            return InternalNode::internal_node_remove(table, parent_page_num, page_num);
+        }
+
+        let left_index = index.min(num_keys - 1);
+        let left_page_num = InternalNode::internal_node_child(parent, left_index) as usize;
+        let right_page_num = InternalNode::internal_node_child(parent, left_index + 1) as usize;
+
+        let right = table.pager.get_page(right_page_num)?;
+        let right_next_leaf = Self::leaf_node_next_leaf(right);
+        let mut cells: Vec<Vec<u8>> = (0..Self::leaf_node_num_cells(right) as usize)
+            .map(|i| Self::leaf_node_cell(right, i).to_vec())
+            .collect();
+        let left = table.pager.get_page(left_page_num)?;
+        let left_cells = Self::leaf_node_num_cells(left) as usize;
+        let left_cells: Vec<Vec<u8>> = (0..left_cells).map(|i| Self::leaf_node_cell(left, i).to_vec()).collect();
+        cells.splice(0..0, left_cells);
+
+        if cells.len() <= Self::LEAF_NODE_MAX_CELLS {
+            for (i, cell) in cells.iter().enumerate() {
+                Self::leaf_node_cell(left, i).copy_from_slice(cell);
+            }
+            Self::set_leaf_node_num_cells(left, cells.len() as u32);
+            Self::set_leaf_node_next_leaf(left, right_next_leaf);
+
+            // A right child absorbed by its left sibling is replaced by it in the parent
+            let parent = table.pager.get_page(parent_page_num)?;
+            if left_index + 1 < num_keys {
+                let right_separator = InternalNode::internal_node_key(parent, left_index + 1).to_vec();
+                InternalNode::set_internal_node_key(parent, left_index, &right_separator);
             }
+            return InternalNode::internal_node_remove(table, parent_page_num, right_page_num);
         }
 
-        *Self::leaf_node_num_cells(node) += 1;
-        Self::leaf_node_key(node, cursor.cell_num).copy_from_slice(&key.to_le_bytes());
-        value.serialize_row_unsafe(Self::leaf_node_value(node, cursor.cell_num));
+        let left_count = cells.len().div_ceil(2);
+        for (i, cell) in cells[..left_count].iter().enumerate() {
+            Self::leaf_node_cell(left, i).copy_from_slice(cell);
+        }
+        Self::set_leaf_node_num_cells(left, left_count as u32);
+        let separator = Self::leaf_node_key(left, left_count - 1).to_vec();
 
+        let right = table.pager.get_page(right_page_num)?;
+        for (i, cell) in cells[left_count..].iter().enumerate() {
+            Self::leaf_node_cell(right, i).copy_from_slice(cell);
+        }
+        Self::set_leaf_node_num_cells(right, (cells.len() - left_count) as u32);
+
+        let parent = table.pager.get_page(parent_page_num)?;
+        InternalNode::set_internal_node_key(parent, left_index, &separator);
         Ok(())
     }
 
 
-    pub fn print_leaf_node(node: &mut [u8]) {
-        let num_cells = *Self::leaf_node_num_cells(node);
-        println!("leaf (size {})", num_cells);
-        for i in 0..num_cells as usize {
-            let key = Self::leaf_node_key(node, i);
-            let key_value = u32::from_le_bytes(key.try_into().expect("Incorrect key length"));
-            println!("  - {} : {}", i, key_value);
+    /// The leaf to the left of the given leaf, found by climbing to the first ancestor
+    /// where the path does not go through the leftmost child.
+    fn previous_leaf(table: &mut Table, page_num: usize) -> io::Result<Option<usize>> {
+        let mut page_num = page_num;
+
+        loop {
+            let node = table.pager.get_page(page_num)?;
+            if Self::is_node_root(node) {
+                return Ok(None);
+            }
+
+            let parent_page_num = Self::node_parent(node) as usize;
+            let parent = table.pager.get_page(parent_page_num)?;
+            let index = InternalNode::internal_node_child_index(parent, page_num);
+
+            if index > 0 {
+                let mut page_num = InternalNode::internal_node_child(parent, index - 1) as usize;
+                loop {
+                    let node = table.pager.get_page(page_num)?;
+                    match Self::get_node_type(node) {
+                        NodeType::NodeLeaf => return Ok(Some(page_num)),
+                        NodeType::NodeInternal => page_num = InternalNode::internal_node_right_child(node) as usize,
+                    }
+                }
+            }
+
+            page_num = parent_page_num;
+        }
+    }
+
+
+    /// Creates a new node and moves half the cells over. Inserts the new value in one
+    /// of the two nodes, then updates the parent or creates a new root.
+    fn leaf_node_split_and_insert(cursor: &mut Cursor, key: &[u8], value: &Row) -> io::Result<()> {
+        let table = &mut *cursor.table;
+        let old_page_num = cursor.page_num;
+        let old_max = table.get_node_max_key(old_page_num)?;
+        let new_page_num = table.pager.get_unused_page_num();
+
+        // Collect all existing cells plus the new one in key order
+        let old_node = table.pager.get_page(old_page_num)?;
+        let num_cells = Self::leaf_node_num_cells(old_node) as usize;
+        let mut cells: Vec<Vec<u8>> = (0..num_cells)
+            .map(|i| Self::leaf_node_cell(old_node, i).to_vec())
+            .collect();
+
+        let mut new_cell = vec![0u8; Self::LEAF_NODE_CELL_SIZE];
+        Key::write_slot(&mut new_cell[..Self::LEAF_NODE_KEY_SIZE], key);
+        value.serialize_row(&mut new_cell[Self::LEAF_NODE_VALUE_OFFSET..]);
+        cells.insert(cursor.cell_num, new_cell);
+
+        let parent_page_num = Self::node_parent(old_node);
+        let old_next_leaf = Self::leaf_node_next_leaf(old_node);
+        let is_root = Self::is_node_root(old_node);
+
+        for (i, cell) in cells[..Self::LEAF_NODE_LEFT_SPLIT_COUNT].iter().enumerate() {
+            Self::leaf_node_cell(old_node, i).copy_from_slice(cell);
+        }
+        Self::set_leaf_node_num_cells(old_node, Self::LEAF_NODE_LEFT_SPLIT_COUNT as u32);
+        Self::set_leaf_node_next_leaf(old_node, new_page_num as u32);
+
+        let new_node = table.pager.get_page(new_page_num)?;
+        Self::initialize_leaf_node(new_node);
+        Self::set_node_parent(new_node, parent_page_num);
+        Self::set_leaf_node_next_leaf(new_node, old_next_leaf);
+        for (i, cell) in cells[Self::LEAF_NODE_LEFT_SPLIT_COUNT..].iter().enumerate() {
+            Self::leaf_node_cell(new_node, i).copy_from_slice(cell);
+        }
+        Self::set_leaf_node_num_cells(new_node, Self::LEAF_NODE_RIGHT_SPLIT_COUNT as u32);
+
+        if is_root {
+            return InternalNode::create_new_root(table, new_page_num);
         }
+
+        let new_max = table.get_node_max_key(old_page_num)?;
+        let parent = table.pager.get_page(parent_page_num as usize)?;
+        InternalNode::update_internal_node_key(parent, &old_max, &new_max, table.key_comparator);
+        InternalNode::internal_node_insert(table, parent_page_num as usize, new_page_num)
     }
 
 
-    pub fn get_node_type(node: &mut [u8]) -> NodeType {
-        unsafe { std::mem::transmute(node[Self::NODE_TYPE_OFFSET])}
+    pub fn get_node_type(node: &[u8]) -> NodeType {
+        match node[Self::NODE_TYPE_OFFSET] {
+            0 => NodeType::NodeInternal,
+            _ => NodeType::NodeLeaf,
+        }
     }
 
 
     pub fn set_node_type(node: &mut [u8], node_type: NodeType) {
         node[Self::NODE_TYPE_OFFSET] = node_type as u8;
     }
-}
\ No newline at end of file
+
+
+    pub fn is_node_root(node: &[u8]) -> bool {
+        node[Self::IS_ROOT_OFFSET] != 0
+    }
+
+
+    pub fn set_node_root(node: &mut [u8], is_root: bool) {
+        node[Self::IS_ROOT_OFFSET] = is_root as u8;
+    }
+
+
+    pub fn node_parent(node: &[u8]) -> u32 {
+        let parent_bytes = &node[Self::PARENT_POINTER_OFFSET..Self::PARENT_POINTER_OFFSET + Self::PARENT_POINTER_SIZE];
+        u32::from_le_bytes(parent_bytes.try_into().unwrap())
+    }
+
+
+    pub fn set_node_parent(node: &mut [u8], parent_page_num: u32) {
+        node[Self::PARENT_POINTER_OFFSET..Self::PARENT_POINTER_OFFSET + Self::PARENT_POINTER_SIZE]
+            .copy_from_slice(&parent_page_num.to_le_bytes());
+    }
+}
@@ -0,0 +1,498 @@
+use std::fmt;
+use crate::value::Value;
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Keyword {
+    And,
+    As,
+    Asc,
+    Autoincrement,
+    By,
+    Create,
+    Delete,
+    Desc,
+    Exists,
+    Explain,
+    False,
+    From,
+    Group,
+    Having,
+    If,
+    In,
+    Index,
+    Inner,
+    Insert,
+    Into,
+    Is,
+    Join,
+    Key,
+    Left,
+    Like,
+    Limit,
+    Not,
+    Null,
+    Offset,
+    On,
+    Or,
+    Order,
+    Outer,
+    Primary,
+    Select,
+    Set,
+    Table,
+    True,
+    Unique,
+    Update,
+    Values,
+    Where,
+}
+
+
+impl Keyword {
+    /// Every keyword, in alphabetical order.
+    pub const ALL: [Keyword; 42] = [
+        Keyword::And, Keyword::As, Keyword::Asc, Keyword::Autoincrement, Keyword::By,
+        Keyword::Create, Keyword::Delete, Keyword::Desc, Keyword::Exists, Keyword::Explain,
+        Keyword::False, Keyword::From, Keyword::Group, Keyword::Having, Keyword::If, Keyword::In,
+        Keyword::Index, Keyword::Inner, Keyword::Insert, Keyword::Into, Keyword::Is, Keyword::Join,
+        Keyword::Key, Keyword::Left, Keyword::Like, Keyword::Limit, Keyword::Not, Keyword::Null,
+        Keyword::Offset, Keyword::On, Keyword::Or, Keyword::Order, Keyword::Outer, Keyword::Primary,
+        Keyword::Select, Keyword::Set, Keyword::Table, Keyword::True, Keyword::Unique,
+        Keyword::Update, Keyword::Values, Keyword::Where,
+    ];
+
+
+    pub fn from_word(word: &str) -> Option<Self> {
+        let keyword = match word.to_ascii_uppercase().as_str() {
+            "AND" => Keyword::And,
+            "AS" => Keyword::As,
+            "ASC" => Keyword::Asc,
+            "AUTOINCREMENT" => Keyword::Autoincrement,
+            "BY" => Keyword::By,
+            "CREATE" => Keyword::Create,
+            "DELETE" => Keyword::Delete,
+            "DESC" => Keyword::Desc,
+            "EXISTS" => Keyword::Exists,
+            "EXPLAIN" => Keyword::Explain,
+            "FALSE" => Keyword::False,
+            "FROM" => Keyword::From,
+            "GROUP" => Keyword::Group,
+            "HAVING" => Keyword::Having,
+            "IF" => Keyword::If,
+            "IN" => Keyword::In,
+            "INDEX" => Keyword::Index,
+            "INNER" => Keyword::Inner,
+            "INSERT" => Keyword::Insert,
+            "INTO" => Keyword::Into,
+            "IS" => Keyword::Is,
+            "JOIN" => Keyword::Join,
+            "KEY" => Keyword::Key,
+            "LEFT" => Keyword::Left,
+            "LIKE" => Keyword::Like,
+            "LIMIT" => Keyword::Limit,
+            "NOT" => Keyword::Not,
+            "NULL" => Keyword::Null,
+            "OFFSET" => Keyword::Offset,
+            "ON" => Keyword::On,
+            "OR" => Keyword::Or,
+            "ORDER" => Keyword::Order,
+            "OUTER" => Keyword::Outer,
+            "PRIMARY" => Keyword::Primary,
+            "SELECT" => Keyword::Select,
+            "SET" => Keyword::Set,
+            "TABLE" => Keyword::Table,
+            "TRUE" => Keyword::True,
+            "UNIQUE" => Keyword::Unique,
+            "UPDATE" => Keyword::Update,
+            "VALUES" => Keyword::Values,
+            "WHERE" => Keyword::Where,
+            _ => return None,
+        };
+
+        Some(keyword)
+    }
+}
+
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_ascii_uppercase())
+    }
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TokenKind {
+    Keyword(Keyword),
+    Identifier(String),
+    String(String),
+    /// Digits without a fraction or exponent. The sign is a separate token, so values up
+    /// to `-i64::MIN` are kept for the parser to negate.
+    Integer(u64),
+    Real(f64),
+    Blob(Vec<u8>),
+    /// `?`, or `:name` for a named parameter.
+    Parameter(Option<String>),
+    LeftParen,
+    RightParen,
+    Comma,
+    Semicolon,
+    Dot,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    Eof,
+}
+
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Keyword(keyword) => write!(f, "{}", keyword),
+            TokenKind::Identifier(name) => write!(f, "identifier '{}'", name),
+            TokenKind::String(s) => write!(f, "string '{}'", s),
+            TokenKind::Integer(i) => write!(f, "{}", i),
+            TokenKind::Real(r) => write!(f, "{}", r),
+            TokenKind::Blob(_) => f.write_str("blob literal"),
+            TokenKind::Parameter(None) => f.write_str("'?'"),
+            TokenKind::Parameter(Some(name)) => write!(f, "parameter ':{}'", name),
+            TokenKind::LeftParen => f.write_str("'('"),
+            TokenKind::RightParen => f.write_str("')'"),
+            TokenKind::Comma => f.write_str("','"),
+            TokenKind::Semicolon => f.write_str("';'"),
+            TokenKind::Dot => f.write_str("'.'"),
+            TokenKind::Star => f.write_str("'*'"),
+            TokenKind::Plus => f.write_str("'+'"),
+            TokenKind::Minus => f.write_str("'-'"),
+            TokenKind::Slash => f.write_str("'/'"),
+            TokenKind::Percent => f.write_str("'%'"),
+            TokenKind::Equals => f.write_str("'='"),
+            TokenKind::NotEquals => f.write_str("'!='"),
+            TokenKind::Less => f.write_str("'<'"),
+            TokenKind::LessEquals => f.write_str("'<='"),
+            TokenKind::Greater => f.write_str("'>'"),
+            TokenKind::GreaterEquals => f.write_str("'>='"),
+            TokenKind::Eof => f.write_str("end of input"),
+        }
+    }
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+
+/// An error found while tokenizing or parsing, with the 1-based position it refers to.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+
+pub struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Lexer {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+
+    /// Splits the whole input into tokens. The last token is always `Eof`.
+    pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+        let mut lexer = Lexer::new(input);
+        let mut tokens = Vec::new();
+
+        loop {
+            let token = lexer.next_token()?;
+            let is_eof = token.kind == TokenKind::Eof;
+            tokens.push(token);
+            if is_eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+
+    /// Finds where the first statement of the input ends: just after the first `;`
+    /// that is not inside a string, a quoted identifier or a comment. Returns `None`
+    /// if the input has no such `;` yet.
+    pub fn statement_end(input: &str) -> Option<usize> {
+        let mut chars = input.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                ';' => return Some(i + 1),
+                '-' if chars.next_if(|&(_, c)| c == '-').is_some() => {
+                    while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                }
+                '\'' | '"' | '`' => loop {
+                    match chars.next()? {
+                        (_, '\\') if c == '\'' => {
+                            chars.next();
+                        }
+                        // A doubled quote stays inside the string
+                        (_, q) if q == c && chars.next_if(|&(_, q)| q == c).is_none() => break,
+                        _ => (),
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+
+    /// Whether the input holds nothing but whitespace and comments.
+    pub fn is_blank(input: &str) -> bool {
+        let mut lexer = Lexer::new(input);
+        lexer.skip_whitespace_and_comments();
+        lexer.chars.peek().is_none()
+    }
+
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+
+    fn error(&self, message: String, line: usize, column: usize) -> ParseError {
+        ParseError { message, line, column }
+    }
+
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.advance();
+            } else if c == '-' && self.chars.clone().nth(1) == Some('-') {
+                // Line comment
+                while self.chars.peek().is_some_and(|&c| c != '\n') {
+                    self.advance();
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+
+    pub fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_whitespace_and_comments();
+
+        let line = self.line;
+        let column = self.column;
+        let token = |kind| Ok(Token { kind, line, column });
+
+        let Some(c) = self.advance() else {
+            return token(TokenKind::Eof);
+        };
+
+        match c {
+            '(' => token(TokenKind::LeftParen),
+            ')' => token(TokenKind::RightParen),
+            ',' => token(TokenKind::Comma),
+            ';' => token(TokenKind::Semicolon),
+            '.' if !self.chars.peek().is_some_and(char::is_ascii_digit) => token(TokenKind::Dot),
+            '*' => token(TokenKind::Star),
+            '+' => token(TokenKind::Plus),
+            '-' => token(TokenKind::Minus),
+            '/' => token(TokenKind::Slash),
+            '%' => token(TokenKind::Percent),
+            '=' => {
+                if self.chars.peek() == Some(&'=') {
+                    self.advance();
+                }
+                token(TokenKind::Equals)
+            }
+            '!' if self.chars.peek() == Some(&'=') => {
+                self.advance();
+                token(TokenKind::NotEquals)
+            }
+            '<' => match self.chars.peek() {
+                Some('=') => {
+                    self.advance();
+                    token(TokenKind::LessEquals)
+                }
+                Some('>') => {
+                    self.advance();
+                    token(TokenKind::NotEquals)
+                }
+                _ => token(TokenKind::Less),
+            },
+            '>' => {
+                if self.chars.peek() == Some(&'=') {
+                    self.advance();
+                    token(TokenKind::GreaterEquals)
+                } else {
+                    token(TokenKind::Greater)
+                }
+            }
+            '\'' => {
+                let s = self.read_quoted('\'', line, column)?;
+                token(TokenKind::String(s))
+            }
+            '"' | '`' => {
+                let name = self.read_quoted(c, line, column)?;
+                token(TokenKind::Identifier(name))
+            }
+            'x' | 'X' if self.chars.peek() == Some(&'\'') => {
+                self.advance();
+                let hex = self.read_quoted('\'', line, column)?;
+                let bytes = Value::parse_hex(&hex)
+                    .ok_or_else(|| self.error(format!("invalid blob literal x'{}'", hex), line, column))?;
+                token(TokenKind::Blob(bytes))
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let kind = self.read_number(c, line, column)?;
+                token(kind)
+            }
+            '?' => token(TokenKind::Parameter(None)),
+            ':' if self.chars.peek().is_some_and(|&c| c.is_alphabetic() || c == '_') => {
+                let first = self.advance().unwrap();
+                token(TokenKind::Parameter(Some(self.read_word(first))))
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let word = self.read_word(c);
+                match Keyword::from_word(&word) {
+                    Some(keyword) => token(TokenKind::Keyword(keyword)),
+                    None => token(TokenKind::Identifier(word)),
+                }
+            }
+            c => Err(self.error(format!("unexpected character '{}'", c), line, column)),
+        }
+    }
+
+
+    /// Reads the rest of a keyword, identifier or parameter name.
+    fn read_word(&mut self, first: char) -> String {
+        let mut word = String::from(first);
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            word.push(c);
+            self.advance();
+        }
+
+        word
+    }
+
+
+    /// Reads up to the closing quote. A doubled quote stands for the quote itself, and
+    /// backslash escapes `\n`, `\t`, `\r`, `\0`, `\\` and `\'` are recognized.
+    fn read_quoted(&mut self, quote: char, line: usize, column: usize) -> Result<String, ParseError> {
+        let mut s = String::new();
+
+        loop {
+            match self.advance() {
+                None => return Err(self.error("unterminated quoted string".to_string(), line, column)),
+                Some(c) if c == quote => {
+                    if self.chars.peek() == Some(&quote) {
+                        self.advance();
+                        s.push(quote);
+                    } else {
+                        return Ok(s);
+                    }
+                }
+                Some('\\') if quote == '\'' => {
+                    let escape_line = self.line;
+                    let escape_column = self.column - 1;
+                    match self.advance() {
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        Some('r') => s.push('\r'),
+                        Some('0') => s.push('\0'),
+                        Some('\\') => s.push('\\'),
+                        Some('\'') => s.push('\''),
+                        Some(c) => {
+                            return Err(self.error(format!("unknown escape sequence '\\{}'", c), escape_line, escape_column));
+                        }
+                        None => return Err(self.error("unterminated quoted string".to_string(), line, column)),
+                    }
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+
+    fn read_number(&mut self, first: char, line: usize, column: usize) -> Result<TokenKind, ParseError> {
+        let mut text = String::from(first);
+        let mut is_real = first == '.';
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_digit() {
+                text.push(c);
+            } else if c == '.' && !is_real {
+                is_real = true;
+                text.push(c);
+            } else if (c == 'e' || c == 'E') && !text.contains(['e', 'E']) {
+                is_real = true;
+                text.push(c);
+                self.advance();
+                if let Some(&sign) = self.chars.peek().filter(|&&c| c == '+' || c == '-') {
+                    text.push(sign);
+                    self.advance();
+                }
+                continue;
+            } else {
+                break;
+            }
+            self.advance();
+        }
+
+        if let Some(&c) = self.chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+            return Err(self.error(format!("invalid number '{}{}'", text, c), line, column));
+        }
+
+        let invalid = || ParseError { message: format!("invalid number '{}'", text), line, column };
+        if is_real {
+            text.parse::<f64>().map(TokenKind::Real).map_err(|_| invalid())
+        } else {
+            // Integers too large for u64 fall back to reals
+            text.parse::<u64>()
+                .map(TokenKind::Integer)
+                .or_else(|_| text.parse::<f64>().map(TokenKind::Real))
+                .map_err(|_| invalid())
+        }
+    }
+}
@@ -1,10 +1,35 @@
 pub mod statement;
 pub mod input_buffer;
+pub mod line_editor;
+pub mod terminal;
 pub mod table;
 pub mod pager;
 pub mod cursor;
+pub mod bulk_load;
 pub mod data_consts;
 pub mod leaf_node;
+pub mod internal_node;
+pub mod key;
+pub mod value;
+pub mod schema;
+pub mod catalog;
+pub mod lexer;
+pub mod ast;
+pub mod parser;
+pub mod expression;
+pub mod sorter;
+pub mod aggregate;
+pub mod database;
+pub mod select;
+pub mod vm;
+pub mod compiler;
+pub mod prepare;
+pub mod prepared_statement;
+pub mod import;
+pub mod csv;
+pub mod json;
+pub mod dump;
+pub mod formatter;
 
 pub use input_buffer::InputBuffer;
-pub use table::Row;
\ No newline at end of file
+pub use table::Row;
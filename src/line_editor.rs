@@ -0,0 +1,453 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufReader, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use crate::input_buffer::ReadResult;
+use crate::terminal::{self, RawMode};
+
+
+/// Reads lines from a terminal with cursor movement, history and tab completion. The
+/// terminal stays in raw mode from when the editor is opened until it is dropped, so
+/// it is only available where [`RawMode`] is. History entries are whole statements,
+/// which may span several lines.
+pub struct LineEditor<R: Read = io::Stdin, W: Write = io::Stdout> {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    /// Key presses, read through a buffer of its own so that [`read_key`] can tell
+    /// whether the rest of an escape sequence has already been read.
+    input: BufReader<R>,
+    output: W,
+    /// `None` for an editor made with [`LineEditor::new`], whose terminal is not stdin.
+    _raw_mode: Option<RawMode>,
+}
+
+
+/// A key press, after decoding escape sequences and UTF-8.
+enum Key {
+    Char(char),
+    /// A control character, such as `\r` or 0x12 for Ctrl-R.
+    Control(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    /// Esc pressed on its own, not starting an escape sequence.
+    Escape,
+    Unknown,
+}
+
+
+/// How a reverse history search ended.
+enum SearchEnd {
+    /// The found line is edited further.
+    Edit(Vec<char>),
+    /// The found line is entered.
+    Accept(Vec<char>),
+    Cancel,
+    EndOfInput,
+}
+
+
+impl LineEditor {
+    /// Opens an editor if stdin and stdout are a terminal, with the history stored in the
+    /// file. Returns `None` if they are not, and fails if the terminal has no raw mode.
+    /// The file holds an entry per line, with newlines and backslashes escaped.
+    pub fn open(history_path: Option<PathBuf>) -> io::Result<Option<Self>> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Ok(None);
+        }
+        let raw_mode = RawMode::enter()?;
+
+        let mut editor = LineEditor::new(io::stdin(), io::stdout(), history_path);
+        editor._raw_mode = Some(raw_mode);
+        Ok(Some(editor))
+    }
+
+
+    /// `~/.crablite_history`, if the home directory is known.
+    pub fn default_history_path() -> Option<PathBuf> {
+        let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+        Some(PathBuf::from(home).join(".crablite_history"))
+    }
+}
+
+
+impl<R: Read, W: Write> LineEditor<R, W> {
+    /// Number of history entries kept. Older ones are dropped from the file when it is loaded.
+    pub const HISTORY_SIZE: usize = 1000;
+    /// How long to wait for the rest of an escape sequence after Esc.
+    const ESCAPE_TIMEOUT_MS: u32 = 50;
+    /// Size of the input buffer. Reads this large skip the buffer of `Stdin` itself, so
+    /// no bytes are left there.
+    const INPUT_BUFFER_SIZE: usize = 8 * 1024;
+
+
+    /// Makes an editor that reads key presses from `input` and draws on `output`, which
+    /// must already behave like a terminal in raw mode, with the history stored in the file.
+    pub fn new(input: R, output: W, history_path: Option<PathBuf>) -> Self {
+        let mut history: Vec<String> = history_path.as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().filter(|line| !line.is_empty()).map(unescape).collect())
+            .unwrap_or_default();
+        if history.len() > Self::HISTORY_SIZE {
+            history.drain(..history.len() - Self::HISTORY_SIZE);
+            if let Some(path) = &history_path {
+                let text: String = history.iter().map(|entry| escape(entry) + "\n").collect();
+                let _ = fs::write(path, text);
+            }
+        }
+
+        let input = BufReader::with_capacity(Self::INPUT_BUFFER_SIZE, input);
+        LineEditor { history, history_path, input, output, _raw_mode: None }
+    }
+
+
+    /// Shows the prompt and reads a line into `line`. `complete` is given the text before
+    /// the word at the cursor and the word itself, and returns the words that can replace it.
+    /// A line recalled from the history may hold several lines of a statement.
+    pub fn read_line(&mut self, prompt: &str, line: &mut String, complete: &dyn Fn(&str, &str) -> Vec<String>) -> io::Result<ReadResult> {
+        let (result, chars) = self.edit(prompt, complete)?;
+        line.clear();
+        line.extend(chars);
+        Ok(result)
+    }
+
+
+    fn edit(&mut self, prompt: &str, complete: &dyn Fn(&str, &str) -> Vec<String>) -> io::Result<(ReadResult, Vec<char>)> {
+        let input = &mut self.input;
+        let out = &mut self.output;
+        let mut chars: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        // Row of the line being edited that the terminal cursor is on
+        let mut row = 0;
+        // Line being browsed with up and down, where `history.len()` is the new line,
+        // and the new line as it was before browsing
+        let mut history_index = self.history.len();
+        let mut new_line = Vec::new();
+        let mut previous_tab = false;
+
+        Self::redraw(out, prompt, &chars, cursor, &mut row)?;
+        loop {
+            let Some(key) = read_key(input)? else {
+                return Ok((ReadResult::ReadEndOfInput, chars));
+            };
+            let tab = matches!(key, Key::Control(b'\t'));
+
+            match key {
+                Key::Char(c) => {
+                    chars.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Control(b'\r' | b'\n') => {
+                    Self::redraw(out, prompt, &chars, chars.len(), &mut row)?;
+                    out.write_all(b"\r\n")?;
+                    return Ok((ReadResult::ReadSuccess, chars));
+                }
+                Key::Control(0x03) => {
+                    Self::redraw(out, prompt, &chars, chars.len(), &mut row)?;
+                    out.write_all(b"^C\r\n")?;
+                    return Ok((ReadResult::ReadInterrupted, Vec::new()));
+                }
+                Key::Control(0x04) if chars.is_empty() => return Ok((ReadResult::ReadEndOfInput, chars)),
+                Key::Control(0x04) | Key::Delete if cursor < chars.len() => {
+                    chars.remove(cursor);
+                }
+                Key::Control(0x7f | 0x08) if cursor > 0 => {
+                    cursor -= 1;
+                    chars.remove(cursor);
+                }
+                Key::Control(0x01) | Key::Home => cursor = 0,
+                Key::Control(0x05) | Key::End => cursor = chars.len(),
+                Key::Control(0x02) | Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Control(0x06) | Key::Right => cursor = (cursor + 1).min(chars.len()),
+                Key::Control(0x0b) => chars.truncate(cursor),
+                Key::Control(0x15) => {
+                    chars.drain(..cursor);
+                    cursor = 0;
+                }
+                Key::Control(0x17) => {
+                    // The word before the cursor and the spaces after it
+                    let mut start = cursor;
+                    while start > 0 && chars[start - 1] == ' ' {
+                        start -= 1;
+                    }
+                    while start > 0 && chars[start - 1] != ' ' {
+                        start -= 1;
+                    }
+                    chars.drain(start..cursor);
+                    cursor = start;
+                }
+                Key::Control(0x0c) => {
+                    out.write_all(b"\x1b[H\x1b[2J")?;
+                    row = 0;
+                }
+                Key::Up | Key::Control(0x10) if history_index > 0 => {
+                    if history_index == self.history.len() {
+                        new_line = chars.clone();
+                    }
+                    history_index -= 1;
+                    chars = self.history[history_index].chars().collect();
+                    cursor = chars.len();
+                }
+                Key::Down | Key::Control(0x0e) if history_index < self.history.len() => {
+                    history_index += 1;
+                    chars = match self.history.get(history_index) {
+                        Some(line) => line.chars().collect(),
+                        None => new_line.clone(),
+                    };
+                    cursor = chars.len();
+                }
+                Key::Control(0x12) => match Self::search(&self.history, input, out)? {
+                    SearchEnd::Edit(found) => {
+                        chars = found;
+                        cursor = chars.len();
+                    }
+                    SearchEnd::Accept(found) => {
+                        Self::redraw(out, prompt, &found, found.len(), &mut row)?;
+                        out.write_all(b"\r\n")?;
+                        return Ok((ReadResult::ReadSuccess, found));
+                    }
+                    SearchEnd::Cancel => (),
+                    SearchEnd::EndOfInput => return Ok((ReadResult::ReadEndOfInput, chars)),
+                },
+                Key::Control(b'\t') => {
+                    cursor = Self::complete(out, prompt, &mut chars, cursor, &mut row, previous_tab, complete)?;
+                }
+                _ => (),
+            }
+
+            previous_tab = tab;
+            Self::redraw(out, prompt, &chars, cursor, &mut row)?;
+        }
+    }
+
+
+    /// Completes the word before the cursor with the words `complete` returns: fully if
+    /// there is one, up to their common prefix otherwise. A second tab in a row lists
+    /// them. Returns the new cursor position.
+    fn complete(
+        out: &mut impl Write,
+        prompt: &str,
+        chars: &mut Vec<char>,
+        cursor: usize,
+        row: &mut usize,
+        previous_tab: bool,
+        complete: &dyn Fn(&str, &str) -> Vec<String>,
+    ) -> io::Result<usize> {
+        let start = chars[..cursor].iter().rposition(|&c| !is_word_char(c)).map_or(0, |i| i + 1);
+        let before: String = chars[..start].iter().collect();
+        let word: String = chars[start..cursor].iter().collect();
+        let mut candidates = complete(&before, &word);
+        candidates.sort();
+        candidates.dedup();
+
+        let replacement: Vec<char> = match candidates.as_slice() {
+            [] => return out.write_all(b"\x07").map(|()| cursor),
+            [candidate] => candidate.chars().chain([' ']).collect(),
+            [first, rest @ ..] => {
+                let mut prefix: Vec<char> = first.chars().collect();
+                for candidate in rest {
+                    let common = prefix.iter().zip(candidate.chars()).take_while(|(a, b)| **a == *b).count();
+                    prefix.truncate(common);
+                }
+                if prefix.len() <= cursor - start {
+                    if previous_tab {
+                        Self::redraw(out, prompt, chars, chars.len(), row)?;
+                        write!(out, "\r\n{}\r\n", candidates.join("  "))?;
+                        *row = 0;
+                        Self::redraw(out, prompt, chars, cursor, row)?;
+                    } else {
+                        out.write_all(b"\x07")?;
+                    }
+                    return Ok(cursor);
+                }
+                prefix
+            }
+        };
+
+        let new_cursor = start + replacement.len();
+        chars.splice(start..cursor, replacement);
+        Ok(new_cursor)
+    }
+
+
+    /// Searches the history backwards for entries containing the text typed so far.
+    /// Ctrl-R moves on to an older match, Enter runs the match, Ctrl-G or Ctrl-C gives
+    /// up, and any other key edits it. Matches are shown on one line.
+    fn search(history: &[String], input: &mut BufReader<impl Read>, out: &mut impl Write) -> io::Result<SearchEnd> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+
+        loop {
+            let line = found.map_or("", |i| history[i].as_str());
+            write!(out, "\r(reverse-i-search)`{}': {}\x1b[K", query, line.replace('\n', " "))?;
+            out.flush()?;
+
+            let Some(key) = read_key(input)? else {
+                return Ok(SearchEnd::EndOfInput);
+            };
+            let search_before = match key {
+                Key::Char(c) => {
+                    query.push(c);
+                    found.map_or(history.len(), |i| i + 1)
+                }
+                Key::Control(0x7f | 0x08) => {
+                    query.pop();
+                    history.len()
+                }
+                Key::Control(0x12) => found.unwrap_or(history.len()),
+                Key::Control(0x07 | 0x03) => return Ok(SearchEnd::Cancel),
+                Key::Control(b'\r' | b'\n') => return Ok(SearchEnd::Accept(line.chars().collect())),
+                _ => return Ok(SearchEnd::Edit(line.chars().collect())),
+            };
+
+            if !query.is_empty() {
+                let older = (0..search_before).rev().find(|&i| history[i].contains(&query));
+                found = older.or(found.filter(|&i| history[i].contains(&query)));
+            }
+        }
+    }
+
+
+    /// Draws the prompt and the line, starting from its first row, and moves the terminal
+    /// cursor to `cursor`. Rows after the first are indented like the prompt. `row` is the
+    /// row the terminal cursor was left on, and is updated.
+    fn redraw(out: &mut impl Write, prompt: &str, chars: &[char], cursor: usize, row: &mut usize) -> io::Result<()> {
+        if *row > 0 {
+            write!(out, "\x1b[{}A", *row)?;
+        }
+        let indent = " ".repeat(prompt.chars().count());
+        let line: String = chars.iter().collect();
+        write!(out, "\r{}{}\x1b[J", prompt, line.replace('\n', &format!("\x1b[K\r\n{}", indent)))?;
+
+        let last_row = chars.iter().filter(|&&c| c == '\n').count();
+        let row_start = chars[..cursor].iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1);
+        *row = chars[..cursor].iter().filter(|&&c| c == '\n').count();
+        if last_row > *row {
+            write!(out, "\x1b[{}A", last_row - *row)?;
+        }
+        write!(out, "\r")?;
+        let column = indent.len() + cursor - row_start;
+        if column > 0 {
+            write!(out, "\x1b[{}C", column)?;
+        }
+        out.flush()
+    }
+
+
+    /// Adds an entry to the history, unless it is blank or the same as the last one.
+    pub fn add_history(&mut self, entry: &str) {
+        if entry.trim().is_empty() || self.history.last().is_some_and(|last| last == entry) {
+            return;
+        }
+        self.history.push(entry.to_string());
+        if self.history.len() > Self::HISTORY_SIZE {
+            self.history.remove(0);
+        }
+
+        // History is a convenience, so failing to save it is not an error
+        if let Some(path) = &self.history_path {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", escape(entry));
+            }
+        }
+    }
+}
+
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+
+/// A history entry as one line of the history file.
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            (c, _) => entry.push(c),
+        }
+    }
+    entry
+}
+
+
+/// Reads one key press. Returns `None` at the end of the input.
+fn read_key(input: &mut BufReader<impl Read>) -> io::Result<Option<Key>> {
+    let Some(byte) = read_byte(input)? else {
+        return Ok(None);
+    };
+
+    let key = match byte {
+        // The rest of an escape sequence arrives right after the Esc
+        0x1b if input.buffer().is_empty() && !terminal::input_ready(<LineEditor>::ESCAPE_TIMEOUT_MS) => Key::Escape,
+        0x1b => match read_byte(input)? {
+            Some(b'[' | b'O') => {
+                // Parameters, then a final byte from '@' to '~'
+                let mut sequence = Vec::new();
+                while let Some(byte) = read_byte(input)? {
+                    sequence.push(byte);
+                    if (0x40..=0x7e).contains(&byte) {
+                        break;
+                    }
+                }
+                match sequence.as_slice() {
+                    b"A" => Key::Up,
+                    b"B" => Key::Down,
+                    b"C" => Key::Right,
+                    b"D" => Key::Left,
+                    b"H" | b"1~" | b"7~" => Key::Home,
+                    b"F" | b"4~" | b"8~" => Key::End,
+                    b"3~" => Key::Delete,
+                    _ => Key::Unknown,
+                }
+            }
+            _ => Key::Unknown,
+        },
+        byte if byte < 0x20 || byte == 0x7f => Key::Control(byte),
+        byte => {
+            let length = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..length {
+                bytes.extend(read_byte(input)?);
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown,
+            }
+        }
+    };
+
+    Ok(Some(key))
+}
+
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
@@ -1,182 +1,478 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, IsTerminal, Read, Write};
 use std::process::exit;
 use crablite::InputBuffer;
+use crablite::input_buffer::ReadResult;
+use crablite::lexer::{Keyword, Lexer};
+use crablite::parser::Parser;
+use crablite::database::Database;
+use crablite::formatter::{Formatter, OutputMode};
+use crablite::prepare::PrepareResult;
 use crablite::statement::{Statement, StatementType};
-use crablite::table::{Row, Table, ExecuteResult};
-use crablite::data_consts::{USERNAME_SIZE, EMAIL_SIZE};
-use std::time::Instant;
-use crablite::leaf_node::LeafNode;
+use crablite::table::ExecuteResult;
+use crablite::vm::Program;
 
+#[allow(clippy::enum_variant_names)]
 enum MetaCommandResult {
     MetaCommandSuccess,
-    MetaCommandUnrecognizedCommand
+    MetaCommandUnrecognizedCommand,
+    /// The command ran into an error, which it has printed.
+    MetaCommandFailed,
 }
 
 
-enum PrepareResult {
-    PrepareSuccess,
-    PrepareNegativeId,
-    PrepareUnrecognizedStatement,
-    PrepareSyntaxError,
-    PrepareStringTooLong,
+/// How the results of selects are printed, set with `.mode` and `.headers`.
+struct Output {
+    mode: OutputMode,
+    headers: bool,
 }
 
 
-fn safe_implementation(statement: &Statement) {
-    let mut dest: [u8; 291] = [0u8; 291];
-    let start = Instant::now();
-    for _ in 0..1_000_000 {
-        Row::serialize_row(&statement.row_to_insert, &mut dest);
-    }
-    let duration = start.elapsed();
-    println!("safe impl: {:?}", duration);
-}
+const META_COMMANDS: [&str; 10] = [".btree", ".dump", ".exit", ".export", ".headers", ".import", ".mode", ".read", ".schema", ".sequence"];
 
 
-fn unsafe_implementation(statement: &Statement) {
-    let mut dest: [u8; 291] = [0u8; 291];
-    let start = Instant::now();
-    for _ in 0..1_000_000 {
-        Row::serialize_row_unsafe(&statement.row_to_insert, &mut dest);
-    }
-    let duration = start.elapsed();
-    println!("unsafe impl: {:?}", duration);
-}
+const USAGE: &str = "Usage: crablite [FILE] [-c COMMANDS]";
 
 
 fn main() {
-    let path = std::env::args().nth(1).unwrap_or(String::from("C:\\temp\\crablite.db"));
-    let mut table = match Table::db_open(&path) {
-        Ok(t) => t,
+    let mut arguments = std::env::args().skip(1);
+    let path = arguments.next().unwrap_or(String::from("C:\\temp\\crablite.db"));
+    let commands = match arguments.next().as_deref() {
+        None => None,
+        Some("-c") => match arguments.next() {
+            Some(commands) if arguments.next().is_none() => Some(commands),
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        },
+        Some(argument) => {
+            eprintln!("Unknown option '{}'. {}", argument, USAGE);
+            exit(2);
+        }
+    };
+
+    let mut database = match Database::db_open(&path) {
+        Ok(database) => database,
         Err(e) => {
             eprintln!("Failed to open database: {}", e);
             exit(1);
         }
     };
 
-    let mut input_buffer = InputBuffer::new();
+    let mut output = Output { mode: OutputMode::Tuple, headers: false };
+
+    // Commands given with -c or piped into stdin run without prompts and stop at the
+    // first error, which sets a nonzero exit code
+    let script = match commands {
+        Some(commands) => Some(commands),
+        None if !io::stdin().is_terminal() => {
+            let mut script = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut script) {
+                eprintln!("Failed to read input: {}", e);
+                exit(1);
+            }
+            Some(script)
+        }
+        None => None,
+    };
+    if let Some(script) = script {
+        let succeeded = run_script(&script, &mut database, &mut output, true);
+        database.db_close().expect("Error: Failed to properly close database");
+        exit(if succeeded { 0 } else { 1 });
+    }
+
+    let mut input_buffer = InputBuffer::with_line_editor();
+    // Lines of a statement whose `;` has not been read yet
+    let mut statement = String::new();
 
     loop {
-        InputBuffer::print_prompt();
-        input_buffer.read_input();
-
-        if input_buffer.buffer.chars().nth(0) == Some('.') {
-            match do_meta_command(&input_buffer, &mut table) {
-                MetaCommandResult::MetaCommandSuccess => continue,
-                MetaCommandResult::MetaCommandUnrecognizedCommand => {
-                    println!("Unrecognized command '{}'", input_buffer.buffer);
-                    continue;
+        let prompt = InputBuffer::prompt(&statement);
+        let complete = |before: &str, word: &str| completions(before, word, &database);
+        match input_buffer.read_line(prompt, &complete) {
+            ReadResult::ReadSuccess => (),
+            ReadResult::ReadInterrupted => {
+                statement.clear();
+                continue;
+            }
+            ReadResult::ReadEndOfInput => {
+                println!();
+                // Like a script, the input may end without a `;` after the last statement
+                if !statement.is_empty() {
+                    input_buffer.add_history(statement.trim());
+                    run_input(statement.trim(), &mut database, &mut output);
                 }
+                break;
             }
         }
 
-        let mut statement = Statement::default();
-        match prepare_statement(&input_buffer, &mut statement) {
-            PrepareResult::PrepareSuccess => (),
-            PrepareResult::PrepareNegativeId => {
-                println!("ID must be positive.");
-                continue;
-            },
-            PrepareResult::PrepareSyntaxError => {
-                println!("Syntax error. Could not parse statement.");
-                continue;
-            },
-            PrepareResult::PrepareUnrecognizedStatement => {
-                println!("Unrecognized keyword at start of '{}'", input_buffer.buffer);
-                continue;
-            },
-            PrepareResult::PrepareStringTooLong => {
-                println!("String is too long.");
-                continue;
-            }
+        if statement.is_empty() && input_buffer.buffer.starts_with('.') {
+            let command = input_buffer.buffer.clone();
+            input_buffer.add_history(&command);
+            run_input(&command, &mut database, &mut output);
+            continue;
         }
 
-        match execute_statement(&statement, &mut table) {
-            ExecuteResult::ExecuteSuccess => println!("Executed."),
-            ExecuteResult::ExecuteTableFull => println!("Error: Table full."),
-            ExecuteResult::ExecuteFailed => println!("Error: No command given."),
-            ExecuteResult::ExecuteDuplicateKey => println!("Error: Duplicate key."),
+        // The history gets whole statements, however many lines they were typed on
+        statement.push_str(&input_buffer.buffer);
+        statement.push('\n');
+        while let Some(end) = Lexer::statement_end(&statement) {
+            input_buffer.add_history(statement[..end].trim());
+            run_input(statement[..end].trim(), &mut database, &mut output);
+            statement.drain(..end);
+        }
+        if Lexer::is_blank(&statement) {
+            statement.clear();
         }
     }
+    database.db_close().expect("Error: Failed to properly close database");
 }
 
 
-fn do_meta_command(input_buffer: &InputBuffer, table: &mut Table) -> MetaCommandResult {
-    if input_buffer.buffer.eq(".exit") {
-        table.db_close().expect("Error: Failed to properly close database");
-        exit(0);
-    } else if input_buffer.buffer.eq(".btree") {
-        println!("Tree:");
-        LeafNode::print_leaf_node(table.pager.get_page(0).unwrap());
-        return MetaCommandResult::MetaCommandSuccess
-    } else {
-        return MetaCommandResult::MetaCommandUnrecognizedCommand
+/// Words that complete the word being typed: meta commands at the start of a line, and
+/// otherwise keywords and table names. Keywords are lowercase unless the word has capitals.
+fn completions(before: &str, word: &str, database: &Database) -> Vec<String> {
+    let matches = |candidate: &str| candidate.get(..word.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(word));
+    if word.starts_with('.') && before.trim().is_empty() {
+        return META_COMMANDS.iter().filter(|command| matches(command)).map(|command| command.to_string()).collect();
+    }
+    if word.is_empty() {
+        return Vec::new();
     }
+
+    let lowercase = !word.chars().any(char::is_uppercase);
+    let keywords = Keyword::ALL.iter().map(|keyword| match lowercase {
+        true => keyword.to_string().to_lowercase(),
+        false => keyword.to_string(),
+    });
+    let tables = database.catalog.tables.iter().map(|table| table.name.clone());
+    keywords.chain(tables).filter(|candidate| matches(candidate)).collect()
 }
 
 
-fn prepare_statement(input_buffer: &InputBuffer, statement: &mut Statement) -> PrepareResult {
-    let trimmed_input = input_buffer.buffer.trim();
+/// Runs a meta command or a statement and prints its outcome. Returns whether it succeeded.
+fn run_input(input: &str, database: &mut Database, output: &mut Output) -> bool {
+    if input.starts_with('.') {
+        return match do_meta_command(input, database, output) {
+            MetaCommandResult::MetaCommandSuccess => true,
+            MetaCommandResult::MetaCommandUnrecognizedCommand => {
+                println!("Unrecognized command '{}'", input);
+                false
+            }
+            MetaCommandResult::MetaCommandFailed => false,
+        };
+    }
 
-    return if trimmed_input.len() > 6 && &trimmed_input[..6] == "insert" {
-        prepare_insert(input_buffer, statement)
-    } else if trimmed_input == "select" {
-        statement.statement_type = StatementType::StatementSelect;
-        PrepareResult::PrepareSuccess
-    } else {
-        PrepareResult::PrepareUnrecognizedStatement
+    let mut statement = Statement::default();
+    match prepare_statement(input, database, &mut statement) {
+        PrepareResult::PrepareSuccess => (),
+        PrepareResult::PrepareSyntaxError(e) => {
+            println!("Syntax error at {}.", e);
+            return false;
+        },
+        PrepareResult::PrepareUnknownTable(name) => {
+            println!("No such table '{}'.", name);
+            return false;
+        },
+        PrepareResult::PrepareUnknownColumn(name) => {
+            println!("No such column '{}'.", name);
+            return false;
+        },
+        PrepareResult::PrepareDuplicateColumn(name) => {
+            println!("Column '{}' is given more than once.", name);
+            return false;
+        },
+        PrepareResult::PrepareValueCountMismatch => {
+            println!("Number of values does not match the number of columns.");
+            return false;
+        },
+        PrepareResult::PrepareExpressionError(e) => {
+            println!("Error: {}.", e);
+            return false;
+        },
+        PrepareResult::PrepareRowTooLarge => {
+            println!("Row is too large.");
+            return false;
+        },
+        PrepareResult::PrepareTypeMismatch(column) => {
+            println!("Type mismatch for column '{}'.", column);
+            return false;
+        },
+        PrepareResult::PrepareTableExists(name) => {
+            println!("Table '{}' already exists.", name);
+            return false;
+        },
+        PrepareResult::PrepareIndexExists(name) => {
+            println!("Index '{}' already exists.", name);
+            return false;
+        },
+        PrepareResult::PrepareInvalidSchema(e) => {
+            println!("Error: {}.", e);
+            return false;
+        }
+        PrepareResult::PrepareUnknownParameter(name) => {
+            println!("No such parameter '{}'.", name);
+            return false;
+        }
     }
-}
 
+    match execute_statement(&statement, database, output) {
+        ExecuteResult::ExecuteSuccess => {
+            // Rows of other modes are not followed by it, so they can be read by tools
+            let has_rows = statement.explain || matches!(statement.statement_type, StatementType::StatementSelect);
+            if output.mode == OutputMode::Tuple || !has_rows {
+                println!("Executed.");
+            }
+            return true;
+        }
+        result => println!("Error: {}.", result),
+    }
+    false
+}
 
-fn prepare_insert(input_buffer: &InputBuffer, statement: &mut Statement) -> PrepareResult {
-    statement.statement_type = StatementType::StatementInsert;
 
-    let mut line_split = input_buffer.buffer.split_whitespace();
-    if let [Some(keyword), Some(id_str), Some(username), Some(email)]
-        = std::array::from_fn(|_| line_split.next()) {
-        if keyword != "insert" {
-            return PrepareResult::PrepareSyntaxError;
-        }
+/// Runs the statements and meta commands of a script in order, as if they were typed
+/// at the prompt. A statement ends at a `;` outside of quotes and may span lines, and a
+/// meta command takes the rest of its line. Returns whether all of them succeeded,
+/// stopping at the first failure if `stop_on_error` is set.
+fn run_script(script: &str, database: &mut Database, output: &mut Output, stop_on_error: bool) -> bool {
+    let mut rest = script;
+    let mut succeeded = true;
 
-        if let Ok(id) = id_str.parse::<i32>() {
-            if id < 0 {
-                return PrepareResult::PrepareNegativeId;
+    loop {
+        rest = rest.trim_start();
+        let end = if rest.is_empty() {
+            return succeeded;
+        } else if rest.starts_with('.') || rest.starts_with("--") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else {
+            Lexer::statement_end(rest).unwrap_or(rest.len())
+        };
+
+        let (input, remaining) = rest.split_at(end);
+        if !input.starts_with("--") && !run_input(input.trim_end(), database, output) {
+            if stop_on_error {
+                return false;
             }
+            succeeded = false;
+        }
+        rest = remaining;
+    }
+}
 
-            let id = id as u32;
-            let username_bytes = username.as_bytes();
-            let email_bytes = email.as_bytes();
 
-            if username_bytes.len() > USERNAME_SIZE || email_bytes.len() > EMAIL_SIZE {
-                return  PrepareResult::PrepareStringTooLong;
-            }
+fn do_meta_command(input: &str, database: &mut Database, output: &mut Output) -> MetaCommandResult {
+    let mut arguments = input.split_whitespace();
+    let command = arguments.next().unwrap_or_default();
+    let argument = arguments.next();
 
-            statement.row_to_insert.id = id;
-            statement.row_to_insert.username[..username_bytes.len()].copy_from_slice(username_bytes);
-            statement.row_to_insert.email[..email_bytes.len()].copy_from_slice(email_bytes);
+    if command.eq(".exit") {
+        database.db_close().expect("Error: Failed to properly close database");
+        exit(0);
+    } else if command.eq(".btree") {
+        // Without a name, the first table in the catalog
+        let Some(name) = argument.or(database.catalog.tables.first().map(|t| t.name.as_str())) else {
+            return MetaCommandResult::MetaCommandSuccess;
+        };
+        let name = name.to_string();
+        let Some(mut table) = database.table(&name) else {
+            println!("No such table '{}'.", name);
+            return MetaCommandResult::MetaCommandFailed;
+        };
 
-            return PrepareResult::PrepareSuccess
+        println!("Tree:");
+        let root_page_num = table.entry.root_page_num;
+        table.print_tree(root_page_num, 0).expect("Failed to print tree");
+        MetaCommandResult::MetaCommandSuccess
+    } else if command.eq(".schema") {
+        let tables = database.catalog.tables.iter()
+            .filter(|t| argument.is_none_or(|name| t.name.eq_ignore_ascii_case(name)));
+        for table in tables {
+            println!("{}:", table.name);
+            for (i, column) in table.schema.columns.iter().enumerate() {
+                let mut constraints = String::new();
+                if table.schema.primary_key == [i] {
+                    constraints.push_str(" PRIMARY KEY");
+                    if table.schema.autoincrement {
+                        constraints.push_str(" AUTOINCREMENT");
+                    }
+                } else if column.not_null && !table.schema.primary_key.contains(&i) {
+                    constraints.push_str(" NOT NULL");
+                }
+                println!("  {} {}{}", column.name, column.column_type, constraints);
+            }
+            if table.schema.primary_key.len() > 1 {
+                let names: Vec<&str> = table.schema.primary_key.iter().map(|&i| table.schema.columns[i].name.as_str()).collect();
+                println!("  PRIMARY KEY ({})", names.join(", "));
+            }
+            for index in &table.indexes {
+                let kind = if index.unique { "UNIQUE INDEX" } else { "INDEX" };
+                println!("  {} {} ({})", kind, index.name(), index.column_names(&table.schema));
+            }
+        }
+        MetaCommandResult::MetaCommandSuccess
+    } else if command.eq(".import") {
+        let json = argument == Some("--json");
+        let path = if json { arguments.next() } else { argument };
+        let (Some(path), Some(name)) = (path, arguments.next()) else {
+            println!("Usage: .import [--json] FILE TABLE");
+            return MetaCommandResult::MetaCommandFailed;
+        };
+        let result = File::open(path).and_then(|file| match json {
+            true => database.import_json(name, BufReader::new(file)),
+            false => database.import_csv(name, BufReader::new(file)),
+        });
+        match result {
+            Ok(report) => {
+                for (line, e) in &report.errors {
+                    println!("Line {}: {}.", line, e);
+                }
+                println!("Imported {} rows, {} failed.", report.imported, report.errors.len());
+                match report.errors.is_empty() {
+                    true => MetaCommandResult::MetaCommandSuccess,
+                    false => MetaCommandResult::MetaCommandFailed,
+                }
+            }
+            Err(e) => {
+                println!("Error: {}.", e);
+                MetaCommandResult::MetaCommandFailed
+            }
+        }
+    } else if command.eq(".export") {
+        let json = argument == Some("--json");
+        let name = if json { arguments.next() } else { argument };
+        let (Some(name), Some(path)) = (name, arguments.next()) else {
+            println!("Usage: .export [--json] TABLE FILE");
+            return MetaCommandResult::MetaCommandFailed;
+        };
+        if database.catalog.find(name).is_none() {
+            println!("No such table '{}'.", name);
+            return MetaCommandResult::MetaCommandFailed;
+        }
+        let result = File::create(path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            let count = match json {
+                true => database.export_json(name, &mut out)?,
+                false => database.export_csv(name, &mut out)?,
+            };
+            out.flush()?;
+            Ok(count)
+        });
+        match result {
+            Ok(count) => {
+                println!("Exported {} rows.", count);
+                MetaCommandResult::MetaCommandSuccess
+            }
+            Err(e) => {
+                println!("Error: {}.", e);
+                MetaCommandResult::MetaCommandFailed
+            }
+        }
+    } else if command.eq(".dump") {
+        let mut out = io::stdout().lock();
+        match database.dump(argument, &mut out).and_then(|()| out.flush()) {
+            Ok(()) => MetaCommandResult::MetaCommandSuccess,
+            Err(e) => {
+                println!("Error: {}.", e);
+                MetaCommandResult::MetaCommandFailed
+            }
         }
+    } else if command.eq(".sequence") {
+        let sequence = arguments.next().and_then(|sequence| sequence.parse().ok());
+        let (Some(name), Some(sequence)) = (argument, sequence) else {
+            println!("Usage: .sequence TABLE ROWID");
+            return MetaCommandResult::MetaCommandFailed;
+        };
+        match database.set_sequence(name, sequence) {
+            Ok(()) => MetaCommandResult::MetaCommandSuccess,
+            Err(e) => {
+                println!("Error: {}.", e);
+                MetaCommandResult::MetaCommandFailed
+            }
+        }
+    } else if command.eq(".read") {
+        let Some(path) = argument else {
+            println!("Usage: .read FILE");
+            return MetaCommandResult::MetaCommandFailed;
+        };
+        match fs::read_to_string(path) {
+            Ok(script) if run_script(&script, database, output, false) => MetaCommandResult::MetaCommandSuccess,
+            Ok(_) => MetaCommandResult::MetaCommandFailed,
+            Err(e) => {
+                println!("Error: {}.", e);
+                MetaCommandResult::MetaCommandFailed
+            }
+        }
+    } else if command.eq(".mode") {
+        match argument.map(OutputMode::from_name) {
+            Some(Some(mode)) => {
+                output.mode = mode;
+                MetaCommandResult::MetaCommandSuccess
+            }
+            Some(None) => {
+                let names: Vec<&str> = OutputMode::ALL.iter().map(OutputMode::name).collect();
+                println!("Unknown mode '{}'. Modes are {}.", argument.unwrap_or_default(), names.join(", "));
+                MetaCommandResult::MetaCommandFailed
+            }
+            None => {
+                println!("{}", output.mode.name());
+                MetaCommandResult::MetaCommandSuccess
+            }
+        }
+    } else if command.eq(".headers") {
+        match argument {
+            Some("on") => output.headers = true,
+            Some("off") => output.headers = false,
+            _ => {
+                println!("Usage: .headers on|off");
+                return MetaCommandResult::MetaCommandFailed;
+            }
+        }
+        MetaCommandResult::MetaCommandSuccess
+    } else {
+        MetaCommandResult::MetaCommandUnrecognizedCommand
     }
-
-    PrepareResult::PrepareSyntaxError
 }
 
 
-fn execute_insert(statement: &Statement, table: &mut Table) -> ExecuteResult {
-    table.insert_row(&statement.row_to_insert)
+fn prepare_statement(input: &str, database: &Database, statement: &mut Statement) -> PrepareResult {
+    match Parser::parse(input) {
+        Ok(parsed) => database.prepare_parsed(parsed, statement),
+        Err(e) => PrepareResult::PrepareSyntaxError(e),
+    }
 }
 
 
-fn execute_select(table: &mut Table) -> ExecuteResult {
-    table.print_all_cursor()
-}
+/// Compiles the statement into a program for the virtual machine and runs it, or
+/// prints the plan and the program for `explain`. Only a select has its rows written
+/// in the output mode, so other statements print nothing but their result.
+fn execute_statement(statement: &Statement, database: &mut Database, output: &Output) -> ExecuteResult {
+    let program = Program::compile(statement, &database.catalog);
 
+    if statement.explain {
+        println!("Plan:");
+        for step in program.plan() {
+            println!("  {}", step);
+        }
+        println!("Program:");
+        for (address, instruction) in program.instructions.iter().enumerate() {
+            println!("  {:>4}  {}", address, instruction);
+        }
+        return ExecuteResult::ExecuteSuccess;
+    }
+    if !matches!(statement.statement_type, StatementType::StatementSelect) {
+        return program.execute(database, &mut |_| ());
+    }
 
-fn execute_statement(statement: &Statement, table: &mut Table) -> ExecuteResult {
-    return match statement.statement_type {
-        StatementType::StatementInsert => execute_insert(statement, table),
-        StatementType::StatementSelect => execute_select(table),
-        StatementType::None => ExecuteResult::ExecuteFailed
+    let mut formatter = Formatter::new(io::stdout().lock(), output.mode, output.headers, statement.column_names.clone());
+    let mut written = Ok(());
+    let result = program.execute(database, &mut |row| {
+        if written.is_ok() {
+            written = formatter.write_row(row);
+        }
+    });
+    if let Err(e) = written.and_then(|()| formatter.finish()) {
+        eprintln!("Failed to write the result: {}", e);
     }
+    result
 }
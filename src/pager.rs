@@ -1,13 +1,19 @@
 use std::fs::{File, OpenOptions};
 use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
-use crate::data_consts::{PAGE_SIZE, TABLE_MAX_PAGES};
+use crate::data_consts::{PAGER_CACHE_PAGES, PAGE_SIZE, TABLE_MAX_PAGES};
 
 
 pub struct Pager {
     pub file: File,
     pub file_length: u64,
     pub num_pages: usize,
-    pub pages: [Option<Box<[u8; PAGE_SIZE]>>; TABLE_MAX_PAGES]
+    /// Pages in memory, indexed by page number. Grows with the file.
+    pub pages: Vec<Option<Box<[u8; PAGE_SIZE]>>>,
+    /// Number of every page in memory, swept by a clock hand when one must be evicted.
+    cached: Vec<usize>,
+    /// Whether each page was used since the hand last passed it, indexed by page number.
+    referenced: Vec<bool>,
+    hand: usize,
 }
 
 
@@ -17,21 +23,25 @@ impl Pager {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(filename)?;
 
         let file_length = file.metadata()?.len();
-        let pages = unsafe { std::mem::zeroed() };
         let num_pages = (file_length / PAGE_SIZE as u64) as usize;
+        let pages = std::iter::repeat_with(|| None).take(num_pages).collect();
 
-        if file_length % PAGE_SIZE as u64 != 0 {
-            return Err(io::Error::new(ErrorKind::Other, "DB file is not a whole number of pages. Corrupt file."));
+        if !file_length.is_multiple_of(PAGE_SIZE as u64) {
+            return Err(io::Error::other("DB file is not a whole number of pages. Corrupt file."));
         }
 
         Ok(Pager {
             file,
             file_length,
             num_pages,
-            pages
+            pages,
+            cached: Vec::new(),
+            referenced: vec![false; num_pages],
+            hand: 0,
         })
     }
 
@@ -41,11 +51,12 @@ impl Pager {
             return Err(io::Error::new(ErrorKind::InvalidInput, "page number out of bounds"));
         }
 
-        if let Some(page) = &self.pages[page_num] {
+        if let Some(Some(page)) = self.pages.get(page_num) {
             self.file.seek(SeekFrom::Start((page_num * PAGE_SIZE) as u64))?;
             self.file.write_all(&page[..PAGE_SIZE])?;
+            self.file_length = self.file_length.max(((page_num + 1) * PAGE_SIZE) as u64);
         } else {
-            return Err(io::Error::new(ErrorKind::Other, "Tried to flush null page"));
+            return Err(io::Error::other("Tried to flush null page"));
         }
 
         Ok(())
@@ -57,7 +68,16 @@ impl Pager {
             return Err(io::Error::new(ErrorKind::InvalidInput, "Page number out of bounds"));
         }
 
+        if page_num >= self.pages.len() {
+            self.pages.resize_with(page_num + 1, || None);
+        }
+        if page_num >= self.referenced.len() {
+            self.referenced.resize(page_num + 1, false);
+        }
+        self.referenced[page_num] = true;
+
         if self.pages[page_num].is_none() {
+            let slot = self.make_room()?;
             // Cache miss. Allocate memory and load from file.
             let mut page = Box::new([0u8; PAGE_SIZE]);
             let num_pages = (self.file_length / PAGE_SIZE as u64) as usize;
@@ -67,7 +87,7 @@ impl Pager {
             }
 
             // We might save a partial page at the end of the file
-            let partial_page = self.file_length % PAGE_SIZE as u64 != 0;
+            let partial_page = !self.file_length.is_multiple_of(PAGE_SIZE as u64);
             if page_num < num_pages || (page_num == num_pages && partial_page) {
                 self.file.seek(SeekFrom::Start((page_num * PAGE_SIZE) as u64))?;
                 let bytes_to_read = if page_num == num_pages && partial_page {
@@ -79,9 +99,42 @@ impl Pager {
             }
 
             self.pages[page_num] = Some(page);
+            match slot {
+                Some(slot) => self.cached[slot] = page_num,
+                None => self.cached.push(page_num),
+            }
         }
 
         Ok(self.pages[page_num].as_mut().unwrap())
     }
-}
 
+
+    /// Once the cache is full, writes back and drops the first page the clock hand
+    /// finds unused since it last passed, and returns its slot for the page to load.
+    fn make_room(&mut self) -> io::Result<Option<usize>> {
+        if self.cached.len() < PAGER_CACHE_PAGES {
+            return Ok(None);
+        }
+
+        loop {
+            let slot = self.hand;
+            self.hand = (self.hand + 1) % self.cached.len();
+
+            let page_num = self.cached[slot];
+            if std::mem::take(&mut self.referenced[page_num]) {
+                continue;
+            }
+            // Pages are handed out mutably, so any of them may have changed
+            self.pager_flush(page_num)?;
+            self.pages[page_num] = None;
+            return Ok(Some(slot));
+        }
+    }
+
+
+    /// Until we start recycling free pages, new pages will always go onto the end
+    /// of the database file.
+    pub fn get_unused_page_num(&self) -> usize {
+        self.num_pages
+    }
+}
@@ -0,0 +1,677 @@
+use crate::ast::{
+    AggregateFunction, BinaryOperator, ColumnDefinition, CreateIndex, CreateTable, Delete, Expr, Insert, Join, JoinKind,
+    OrderingTerm, Select, SelectItem, Statement, TableReference, UnaryOperator, Update,
+};
+use crate::lexer::{Keyword, Lexer, ParseError, Token, TokenKind};
+use crate::value::Value;
+
+
+/// Recursive-descent parser over the tokens of a single statement.
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Name of every parameter seen so far, `None` for `?`. Parameter `i` is at `i - 1`.
+    parameters: Vec<Option<String>>,
+}
+
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser { tokens, position: 0, parameters: Vec::new() }
+    }
+
+
+    /// Parses one statement, optionally terminated by a semicolon.
+    pub fn parse(sql: &str) -> Result<Statement, ParseError> {
+        Self::parse_with_parameters(sql).map(|(statement, _)| statement)
+    }
+
+
+    /// Parses one statement, and also returns the names of its parameters in order of
+    /// their numbers, `None` for `?`.
+    pub fn parse_with_parameters(sql: &str) -> Result<(Statement, Vec<Option<String>>), ParseError> {
+        let mut parser = Parser::new(Lexer::tokenize(sql)?);
+        let statement = parser.parse_statement()?;
+
+        parser.consume(&TokenKind::Semicolon);
+        parser.expect(&TokenKind::Eof)?;
+
+        Ok((statement, parser.parameters))
+    }
+
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+
+    fn next(&mut self) -> &Token {
+        let token = &self.tokens[self.position];
+        // The trailing Eof token is never consumed
+        if token.kind != TokenKind::Eof {
+            self.position += 1;
+        }
+        token
+    }
+
+
+    /// Consumes the next token if it matches.
+    fn consume(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+
+    fn consume_keyword(&mut self, keyword: Keyword) -> bool {
+        self.consume(&TokenKind::Keyword(keyword))
+    }
+
+
+    fn expect(&mut self, kind: &TokenKind) -> Result<(), ParseError> {
+        if self.consume(kind) {
+            Ok(())
+        } else {
+            Err(self.error(&kind.to_string()))
+        }
+    }
+
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), ParseError> {
+        self.expect(&TokenKind::Keyword(keyword))
+    }
+
+
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        match &self.peek().kind {
+            TokenKind::Identifier(name) => {
+                let name = name.clone();
+                self.next();
+                Ok(name)
+            }
+            _ => Err(self.error("identifier")),
+        }
+    }
+
+
+    /// An "expected X, found Y" error at the current token.
+    fn error(&self, expected: &str) -> ParseError {
+        let token = self.peek();
+        ParseError {
+            message: format!("expected {}, found {}", expected, token.kind),
+            line: token.line,
+            column: token.column,
+        }
+    }
+
+
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        match self.peek().kind {
+            TokenKind::Keyword(Keyword::Create) => {
+                self.next();
+                let unique = self.consume_keyword(Keyword::Unique);
+                if unique || self.consume_keyword(Keyword::Index) {
+                    self.parse_create_index(unique).map(Statement::CreateIndex)
+                } else {
+                    self.parse_create_table().map(Statement::CreateTable)
+                }
+            }
+            TokenKind::Keyword(Keyword::Insert) => self.parse_insert().map(Statement::Insert),
+            TokenKind::Keyword(Keyword::Select) => self.parse_select().map(|select| Statement::Select(Box::new(select))),
+            TokenKind::Keyword(Keyword::Update) => self.parse_update().map(Statement::Update),
+            TokenKind::Keyword(Keyword::Delete) => self.parse_delete().map(Statement::Delete),
+            TokenKind::Keyword(Keyword::Explain) => {
+                self.next();
+                self.parse_statement().map(|statement| Statement::Explain(Box::new(statement)))
+            }
+            _ => Err(self.error("CREATE, INSERT, SELECT, UPDATE, DELETE or EXPLAIN")),
+        }
+    }
+
+
+    fn parse_create_table(&mut self) -> Result<CreateTable, ParseError> {
+        self.expect_keyword(Keyword::Table)?;
+        let if_not_exists = self.consume_keyword(Keyword::If);
+        if if_not_exists {
+            self.expect_keyword(Keyword::Not)?;
+            self.expect_keyword(Keyword::Exists)?;
+        }
+        let table_name = self.expect_identifier()?;
+        self.expect(&TokenKind::LeftParen)?;
+
+        let mut columns = Vec::new();
+        let mut primary_key = None;
+        let mut unique = Vec::new();
+        loop {
+            if self.consume_keyword(Keyword::Primary) {
+                self.expect_keyword(Keyword::Key)?;
+                self.expect(&TokenKind::LeftParen)?;
+                primary_key = Some(self.parse_list(Self::expect_identifier)?);
+                self.expect(&TokenKind::RightParen)?;
+            } else if self.consume_keyword(Keyword::Unique) {
+                self.expect(&TokenKind::LeftParen)?;
+                unique.push(self.parse_list(Self::expect_identifier)?);
+                self.expect(&TokenKind::RightParen)?;
+            } else {
+                columns.push(self.parse_column_definition()?);
+            }
+
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RightParen)?;
+
+        Ok(CreateTable { table_name, if_not_exists, columns, primary_key, unique })
+    }
+
+
+    /// Parses the rest of a create index, after `create index` or `create unique`.
+    fn parse_create_index(&mut self, unique: bool) -> Result<CreateIndex, ParseError> {
+        if unique {
+            self.expect_keyword(Keyword::Index)?;
+        }
+        let index_name = self.expect_identifier()?;
+        self.expect_keyword(Keyword::On)?;
+        let table_name = self.expect_identifier()?;
+        self.expect(&TokenKind::LeftParen)?;
+        let columns = self.parse_list(Self::expect_identifier)?;
+        self.expect(&TokenKind::RightParen)?;
+
+        Ok(CreateIndex { unique, index_name, table_name, columns })
+    }
+
+
+    fn parse_column_definition(&mut self) -> Result<ColumnDefinition, ParseError> {
+        let name = self.expect_identifier()?;
+        let type_name = self.expect_identifier()?;
+        let mut column = ColumnDefinition {
+            name,
+            type_name,
+            primary_key: false,
+            autoincrement: false,
+            not_null: false,
+            unique: false,
+        };
+
+        loop {
+            if self.consume_keyword(Keyword::Primary) {
+                self.expect_keyword(Keyword::Key)?;
+                column.primary_key = true;
+                column.autoincrement = self.consume_keyword(Keyword::Autoincrement);
+            } else if self.consume_keyword(Keyword::Not) {
+                self.expect_keyword(Keyword::Null)?;
+                column.not_null = true;
+            } else if self.consume_keyword(Keyword::Unique) {
+                column.unique = true;
+            } else {
+                return Ok(column);
+            }
+        }
+    }
+
+
+    fn parse_insert(&mut self) -> Result<Insert, ParseError> {
+        self.expect_keyword(Keyword::Insert)?;
+        self.expect_keyword(Keyword::Into)?;
+        let table_name = self.expect_identifier()?;
+
+        let columns = if self.consume(&TokenKind::LeftParen) {
+            let columns = self.parse_list(Self::expect_identifier)?;
+            self.expect(&TokenKind::RightParen)?;
+            Some(columns)
+        } else {
+            None
+        };
+
+        self.expect_keyword(Keyword::Values)?;
+        self.expect(&TokenKind::LeftParen)?;
+        let values = self.parse_list(Self::parse_expr)?;
+        self.expect(&TokenKind::RightParen)?;
+
+        Ok(Insert { table_name, columns, values })
+    }
+
+
+    fn parse_select(&mut self) -> Result<Select, ParseError> {
+        self.expect_keyword(Keyword::Select)?;
+        let projection = self.parse_list(Self::parse_select_item)?;
+        self.expect_keyword(Keyword::From)?;
+        let from = self.parse_table_reference()?;
+
+        let mut joins = Vec::new();
+        while let Some(kind) = self.parse_join_kind()? {
+            let table = self.parse_table_reference()?;
+            let constraint = if self.consume_keyword(Keyword::On) {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            joins.push(Join { kind, table, constraint });
+        }
+
+        let where_clause = self.parse_where_clause()?;
+
+        let (group_by, having) = if self.consume_keyword(Keyword::Group) {
+            self.expect_keyword(Keyword::By)?;
+            let group_by = self.parse_list(Self::parse_expr)?;
+            let having = if self.consume_keyword(Keyword::Having) {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            (group_by, having)
+        } else {
+            (Vec::new(), None)
+        };
+
+        let order_by = if self.consume_keyword(Keyword::Order) {
+            self.expect_keyword(Keyword::By)?;
+            self.parse_list(Self::parse_ordering_term)?
+        } else {
+            Vec::new()
+        };
+
+        let (limit, offset) = if self.consume_keyword(Keyword::Limit) {
+            let limit = self.parse_expr()?;
+            let offset = if self.consume_keyword(Keyword::Offset) {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            (Some(limit), offset)
+        } else {
+            (None, None)
+        };
+
+        Ok(Select { projection, from, joins, where_clause, group_by, having, order_by, limit, offset })
+    }
+
+
+    fn parse_table_reference(&mut self) -> Result<TableReference, ParseError> {
+        let table_name = self.expect_identifier()?;
+        let alias = if self.consume_keyword(Keyword::As) || matches!(self.peek().kind, TokenKind::Identifier(_)) {
+            Some(self.expect_identifier()?)
+        } else {
+            None
+        };
+
+        Ok(TableReference { table_name, alias })
+    }
+
+
+    /// Consumes the keywords that start a join, if there is one.
+    fn parse_join_kind(&mut self) -> Result<Option<JoinKind>, ParseError> {
+        let kind = if self.consume_keyword(Keyword::Left) {
+            self.consume_keyword(Keyword::Outer);
+            JoinKind::Left
+        } else if self.consume_keyword(Keyword::Inner) || self.peek().kind == TokenKind::Keyword(Keyword::Join) {
+            JoinKind::Inner
+        } else {
+            return Ok(None);
+        };
+
+        self.expect_keyword(Keyword::Join)?;
+        Ok(Some(kind))
+    }
+
+
+    fn parse_ordering_term(&mut self) -> Result<OrderingTerm, ParseError> {
+        let expr = self.parse_expr()?;
+        let descending = if self.consume_keyword(Keyword::Desc) {
+            true
+        } else {
+            self.consume_keyword(Keyword::Asc);
+            false
+        };
+
+        Ok(OrderingTerm { expr, descending })
+    }
+
+
+    fn parse_select_item(&mut self) -> Result<SelectItem, ParseError> {
+        if self.consume(&TokenKind::Star) {
+            return Ok(SelectItem::Wildcard);
+        }
+
+        let expr = self.parse_expr()?;
+        let alias = if self.consume_keyword(Keyword::As) {
+            Some(self.expect_identifier()?)
+        } else {
+            None
+        };
+
+        Ok(SelectItem::Expr { expr, alias })
+    }
+
+
+    fn parse_update(&mut self) -> Result<Update, ParseError> {
+        self.expect_keyword(Keyword::Update)?;
+        let table_name = self.expect_identifier()?;
+        self.expect_keyword(Keyword::Set)?;
+        let assignments = self.parse_list(|parser| {
+            let column = parser.expect_identifier()?;
+            parser.expect(&TokenKind::Equals)?;
+            Ok((column, parser.parse_expr()?))
+        })?;
+        let where_clause = self.parse_where_clause()?;
+
+        Ok(Update { table_name, assignments, where_clause })
+    }
+
+
+    fn parse_delete(&mut self) -> Result<Delete, ParseError> {
+        self.expect_keyword(Keyword::Delete)?;
+        self.expect_keyword(Keyword::From)?;
+        let table_name = self.expect_identifier()?;
+        let where_clause = self.parse_where_clause()?;
+
+        Ok(Delete { table_name, where_clause })
+    }
+
+
+    fn parse_where_clause(&mut self) -> Result<Option<Expr>, ParseError> {
+        if self.consume_keyword(Keyword::Where) {
+            Ok(Some(self.parse_expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+
+    /// Parses one or more comma separated items.
+    fn parse_list<T>(&mut self, parse_item: fn(&mut Self) -> Result<T, ParseError>) -> Result<Vec<T>, ParseError> {
+        let mut items = vec![parse_item(self)?];
+        while self.consume(&TokenKind::Comma) {
+            items.push(parse_item(self)?);
+        }
+
+        Ok(items)
+    }
+
+
+    /// Operator precedence, from loosest to tightest: OR, AND, NOT, comparisons
+    /// (including IS, LIKE and IN), addition and subtraction, multiplication,
+    /// division and modulo, unary minus.
+    pub fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        self.parse_or()
+    }
+
+
+    fn binary(operator: BinaryOperator, left: Expr, right: Expr) -> Expr {
+        Expr::Binary { operator, left: Box::new(left), right: Box::new(right) }
+    }
+
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_and()?;
+        while self.consume_keyword(Keyword::Or) {
+            expr = Self::binary(BinaryOperator::Or, expr, self.parse_and()?);
+        }
+
+        Ok(expr)
+    }
+
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_not()?;
+        while self.consume_keyword(Keyword::And) {
+            expr = Self::binary(BinaryOperator::And, expr, self.parse_not()?);
+        }
+
+        Ok(expr)
+    }
+
+
+    fn parse_not(&mut self) -> Result<Expr, ParseError> {
+        if self.consume_keyword(Keyword::Not) {
+            let operand = Box::new(self.parse_not()?);
+            return Ok(Expr::Unary { operator: UnaryOperator::Not, operand });
+        }
+
+        self.parse_comparison()
+    }
+
+
+    fn parse_comparison(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_additive()?;
+
+        loop {
+            let operator = match self.peek().kind {
+                TokenKind::Equals => BinaryOperator::Equal,
+                TokenKind::NotEquals => BinaryOperator::NotEqual,
+                TokenKind::Less => BinaryOperator::Less,
+                TokenKind::LessEquals => BinaryOperator::LessEqual,
+                TokenKind::Greater => BinaryOperator::Greater,
+                TokenKind::GreaterEquals => BinaryOperator::GreaterEqual,
+                TokenKind::Keyword(Keyword::Is) => {
+                    self.next();
+                    let negated = self.consume_keyword(Keyword::Not);
+                    self.expect_keyword(Keyword::Null)?;
+                    expr = Expr::IsNull { operand: Box::new(expr), negated };
+                    continue;
+                }
+                TokenKind::Keyword(Keyword::Not | Keyword::Like | Keyword::In) => {
+                    let negated = self.consume_keyword(Keyword::Not);
+                    let operand = Box::new(expr);
+
+                    expr = if self.consume_keyword(Keyword::Like) {
+                        let pattern = Box::new(self.parse_additive()?);
+                        Expr::Like { operand, pattern, negated }
+                    } else if self.consume_keyword(Keyword::In) {
+                        self.expect(&TokenKind::LeftParen)?;
+                        let list = self.parse_list(Self::parse_expr)?;
+                        self.expect(&TokenKind::RightParen)?;
+                        Expr::InList { operand, list, negated }
+                    } else {
+                        return Err(self.error("LIKE or IN"));
+                    };
+                    continue;
+                }
+                _ => return Ok(expr),
+            };
+
+            self.next();
+            expr = Self::binary(operator, expr, self.parse_additive()?);
+        }
+    }
+
+
+    fn parse_additive(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_multiplicative()?;
+
+        loop {
+            let operator = match self.peek().kind {
+                TokenKind::Plus => BinaryOperator::Add,
+                TokenKind::Minus => BinaryOperator::Subtract,
+                _ => return Ok(expr),
+            };
+
+            self.next();
+            expr = Self::binary(operator, expr, self.parse_multiplicative()?);
+        }
+    }
+
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_unary()?;
+
+        loop {
+            let operator = match self.peek().kind {
+                TokenKind::Star => BinaryOperator::Multiply,
+                TokenKind::Slash => BinaryOperator::Divide,
+                TokenKind::Percent => BinaryOperator::Modulo,
+                _ => return Ok(expr),
+            };
+
+            self.next();
+            expr = Self::binary(operator, expr, self.parse_unary()?);
+        }
+    }
+
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        if self.consume(&TokenKind::Minus) {
+            // The digits are negated with the sign attached, as i64::MIN has no positive i64
+            if let TokenKind::Integer(digits) = self.peek().kind {
+                if let Some(i) = 0i64.checked_sub_unsigned(digits) {
+                    self.next();
+                    return Ok(Expr::Literal(Value::Integer(i)));
+                }
+            }
+
+            let operand = self.parse_unary()?;
+            // Fold negative numbers so they stay literals
+            return Ok(match operand {
+                Expr::Literal(Value::Integer(i)) if i != i64::MIN => Expr::Literal(Value::Integer(-i)),
+                Expr::Literal(Value::Real(r)) => Expr::Literal(Value::Real(-r)),
+                operand => Expr::Unary { operator: UnaryOperator::Negate, operand: Box::new(operand) },
+            });
+        }
+
+        if self.consume(&TokenKind::Plus) {
+            return self.parse_unary();
+        }
+
+        self.parse_primary()
+    }
+
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let expr = match &self.peek().kind {
+            TokenKind::Keyword(Keyword::Null) => Expr::Literal(Value::Null),
+            TokenKind::Keyword(Keyword::True) => Expr::Literal(Value::Boolean(true)),
+            TokenKind::Keyword(Keyword::False) => Expr::Literal(Value::Boolean(false)),
+            // Integers too large for i64 fall back to reals
+            TokenKind::Integer(digits) => match i64::try_from(*digits) {
+                Ok(i) => Expr::Literal(Value::Integer(i)),
+                Err(_) => Expr::Literal(Value::Real(*digits as f64)),
+            },
+            TokenKind::Real(r) => Expr::Literal(Value::Real(*r)),
+            TokenKind::String(s) => Expr::Literal(Value::Text(s.clone())),
+            TokenKind::Blob(bytes) => Expr::Literal(Value::Blob(bytes.clone())),
+            TokenKind::Parameter(name) => {
+                let name = name.clone();
+                let existing = name.as_ref().and_then(|name| self.parameters.iter().position(|p| p.as_ref() == Some(name)));
+                let index = match existing {
+                    Some(i) => i + 1,
+                    None => {
+                        self.parameters.push(name.clone());
+                        self.parameters.len()
+                    }
+                };
+                Expr::Parameter { index, name }
+            }
+            TokenKind::Identifier(_) if self.tokens[self.position + 1].kind == TokenKind::LeftParen => {
+                return self.parse_aggregate();
+            }
+            TokenKind::Identifier(_) => {
+                let name = self.expect_identifier()?;
+                if self.consume(&TokenKind::Dot) {
+                    return Ok(Expr::Column { table: Some(name), name: self.expect_identifier()? });
+                }
+                return Ok(Expr::Column { table: None, name });
+            }
+            TokenKind::LeftParen => {
+                self.next();
+                let expr = self.parse_expr()?;
+                self.expect(&TokenKind::RightParen)?;
+                return Ok(expr);
+            }
+            _ => return Err(self.error("expression")),
+        };
+
+        self.next();
+        Ok(expr)
+    }
+
+
+    /// `<function>(<expr>)` or `count(*)`.
+    fn parse_aggregate(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek().clone();
+        let name = self.expect_identifier()?;
+        let Some(function) = AggregateFunction::from_name(&name) else {
+            return Err(ParseError {
+                message: format!("unknown function '{}'", name),
+                line: token.line,
+                column: token.column,
+            });
+        };
+
+        self.expect(&TokenKind::LeftParen)?;
+        let argument = if function == AggregateFunction::Count && self.consume(&TokenKind::Star) {
+            None
+        } else {
+            Some(Box::new(self.parse_expr()?))
+        };
+        self.expect(&TokenKind::RightParen)?;
+
+        Ok(Expr::Aggregate { function, argument })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn insert_values(sql: &str) -> Vec<Expr> {
+        match Parser::parse(sql) {
+            Ok(Statement::Insert(insert)) => insert.values,
+            other => panic!("Expected an insert, got {:?}", other),
+        }
+    }
+
+
+    #[test]
+    fn negative_integer_literals_fold_with_their_sign() {
+        let values = insert_values("insert into t values (-9223372036854775808, -5, - -5, 9223372036854775808)");
+
+        assert_eq!(values[0], Expr::Literal(Value::Integer(i64::MIN)));
+        assert_eq!(values[1], Expr::Literal(Value::Integer(-5)));
+        assert_eq!(values[2], Expr::Literal(Value::Integer(5)));
+        assert_eq!(values[3], Expr::Literal(Value::Real(9223372036854775808.0)));
+    }
+
+
+    #[test]
+    fn integers_below_i64_min_are_reals() {
+        let values = insert_values("insert into t values (-9223372036854775809)");
+
+        assert_eq!(values[0], Expr::Literal(Value::Real(-9223372036854775809.0)));
+    }
+
+
+    fn error_position(sql: &str) -> (usize, usize) {
+        let error = Parser::parse(sql).expect_err(sql);
+        (error.line, error.column)
+    }
+
+
+    #[test]
+    fn lexer_errors_point_at_the_offending_character() {
+        assert_eq!(error_position("select 1 # 2"), (1, 10));
+        assert_eq!(error_position("select 1,\n  'abc"), (2, 3));
+        assert_eq!(error_position("select 1,\n\t\"abc"), (2, 2));
+    }
+
+
+    #[test]
+    fn parser_errors_point_at_the_unexpected_token() {
+        assert_eq!(error_position("select id\nfrom users whre id = 1"), (2, 17));
+        assert_eq!(error_position("insert into t values (1,, 2)"), (1, 25));
+        assert_eq!(error_position("select *\nfrom"), (2, 5));
+    }
+
+
+    #[test]
+    fn error_messages_start_with_their_position() {
+        let error = Parser::parse("select 1 # 2").unwrap_err();
+
+        assert_eq!(error.to_string(), "line 1, column 10: unexpected character '#'");
+    }
+}
@@ -0,0 +1,456 @@
+use crate::aggregate::Aggregation;
+use crate::ast;
+use crate::catalog::{Catalog, TableEntry};
+use crate::data_consts::ROW_SIZE;
+use crate::expression::{BindError, Expression, Scope};
+use crate::lexer::ParseError;
+use crate::schema::{Column, Schema};
+use crate::select::{IndexScan, Join};
+use crate::statement::{Statement, StatementType};
+use crate::table::Row;
+use crate::value::{ColumnType, Value};
+
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug)]
+pub enum PrepareResult {
+    PrepareSuccess,
+    PrepareSyntaxError(ParseError),
+    PrepareUnknownTable(String),
+    PrepareUnknownColumn(String),
+    PrepareDuplicateColumn(String),
+    PrepareValueCountMismatch,
+    PrepareExpressionError(String),
+    PrepareRowTooLarge,
+    PrepareTypeMismatch(String),
+    PrepareTableExists(String),
+    /// A table or index with the name of a new index already exists.
+    PrepareIndexExists(String),
+    /// The columns of a create table do not form a valid schema.
+    PrepareInvalidSchema(String),
+    /// A prepared statement has no parameter with the number or name.
+    PrepareUnknownParameter(String),
+}
+
+
+impl From<BindError> for PrepareResult {
+    fn from(error: BindError) -> Self {
+        match error {
+            BindError::UnknownColumn(name) => PrepareResult::PrepareUnknownColumn(name),
+            BindError::Misuse(message) => PrepareResult::PrepareExpressionError(message),
+        }
+    }
+}
+
+
+/// Checks the parsed statement against the catalog and fills in `statement`, binding
+/// column names to column indexes and evaluating constants.
+pub fn prepare_statement(parsed: ast::Statement, catalog: &Catalog, statement: &mut Statement) -> PrepareResult {
+    let parsed = match parsed {
+        ast::Statement::Explain(explained) => {
+            statement.explain = true;
+            return prepare_statement(*explained, catalog, statement);
+        }
+        parsed => parsed,
+    };
+
+    let table_name = match &parsed {
+        ast::Statement::CreateTable(create) => {
+            return prepare_create_table(create, catalog, statement);
+        }
+        ast::Statement::CreateIndex(create) => {
+            return prepare_create_index(create, catalog, statement);
+        }
+        ast::Statement::Insert(insert) => &insert.table_name,
+        ast::Statement::Select(select) => &select.from.table_name,
+        ast::Statement::Update(update) => &update.table_name,
+        ast::Statement::Delete(delete) => &delete.table_name,
+        ast::Statement::Explain(_) => unreachable!(),
+    };
+    let Some(entry) = catalog.find(table_name) else {
+        return PrepareResult::PrepareUnknownTable(table_name.clone());
+    };
+    statement.table_name = entry.name.clone();
+
+    match parsed {
+        ast::Statement::CreateTable(_) | ast::Statement::CreateIndex(_) | ast::Statement::Explain(_) => unreachable!(),
+        ast::Statement::Insert(insert) => prepare_insert(insert, &entry.schema, statement),
+        ast::Statement::Select(select) => prepare_select(*select, catalog, statement),
+        ast::Statement::Update(update) => prepare_update(update, entry, statement),
+        ast::Statement::Delete(delete) => {
+            statement.statement_type = StatementType::StatementDelete;
+            prepare_where(delete.where_clause, &Scope::new(&delete.table_name, &entry.schema), entry, statement)
+        }
+    }
+}
+
+
+fn prepare_create_table(create: &ast::CreateTable, catalog: &Catalog, statement: &mut Statement) -> PrepareResult {
+    statement.statement_type = StatementType::StatementCreateTable;
+
+    // Without a schema the statement compiles to nothing
+    if create.if_not_exists && catalog.find(&create.table_name).is_some() {
+        return PrepareResult::PrepareSuccess;
+    }
+    if catalog.find(&create.table_name).is_some() || catalog.find_index(&create.table_name).is_some() {
+        return PrepareResult::PrepareTableExists(create.table_name.clone());
+    }
+
+    let mut columns: Vec<Column> = Vec::with_capacity(create.columns.len());
+    for definition in &create.columns {
+        if columns.iter().any(|c| c.name.eq_ignore_ascii_case(&definition.name)) {
+            return PrepareResult::PrepareDuplicateColumn(definition.name.clone());
+        }
+        let Some(column_type) = ColumnType::from_name(&definition.type_name) else {
+            let message = format!("unknown column type '{}'", definition.type_name);
+            return PrepareResult::PrepareInvalidSchema(message);
+        };
+
+        let mut column = Column::new(&definition.name, column_type);
+        column.not_null = definition.not_null;
+        columns.push(column);
+    }
+
+    // Like `Schema::new`, the first column is the key unless one is declared
+    let key_columns: Vec<usize> = (0..columns.len()).filter(|&i| create.columns[i].primary_key).collect();
+    let primary_key = match (&create.primary_key, key_columns.len()) {
+        (Some(_), 1..) | (None, 2..) => {
+            return PrepareResult::PrepareInvalidSchema("a table can only have one PRIMARY KEY".to_string());
+        }
+        (Some(names), _) => {
+            let mut primary_key = Vec::with_capacity(names.len());
+            for name in names {
+                let Some(column) = columns.iter().position(|c| c.name.eq_ignore_ascii_case(name)) else {
+                    return PrepareResult::PrepareUnknownColumn(name.clone());
+                };
+                if primary_key.contains(&column) {
+                    return PrepareResult::PrepareDuplicateColumn(name.clone());
+                }
+                primary_key.push(column);
+            }
+            primary_key
+        }
+        (None, 1) => key_columns,
+        (None, _) => vec![0],
+    };
+
+    let mut schema = Schema::with_primary_key(columns, primary_key);
+    if create.columns.iter().any(|c| c.autoincrement) {
+        if schema.rowid_column().is_none() {
+            let message = "AUTOINCREMENT is only allowed on a single INTEGER PRIMARY KEY".to_string();
+            return PrepareResult::PrepareInvalidSchema(message);
+        }
+        schema = schema.autoincrement();
+    }
+
+    // Each UNIQUE constraint gets its own index, column constraints first
+    let mut unique_constraints: Vec<Vec<usize>> = (0..create.columns.len())
+        .filter(|&i| create.columns[i].unique)
+        .map(|i| vec![i])
+        .collect();
+    for names in &create.unique {
+        match resolve_columns(names, &schema) {
+            Ok(columns) => unique_constraints.push(columns),
+            Err(result) => return result,
+        }
+    }
+
+    statement.table_name = create.table_name.clone();
+    statement.table_schema = Some(schema);
+    statement.unique_constraints = unique_constraints;
+    PrepareResult::PrepareSuccess
+}
+
+
+fn prepare_create_index(create: &ast::CreateIndex, catalog: &Catalog, statement: &mut Statement) -> PrepareResult {
+    statement.statement_type = StatementType::StatementCreateIndex;
+
+    if catalog.find(&create.index_name).is_some() || catalog.find_index(&create.index_name).is_some() {
+        return PrepareResult::PrepareIndexExists(create.index_name.clone());
+    }
+    let Some(entry) = catalog.find(&create.table_name) else {
+        return PrepareResult::PrepareUnknownTable(create.table_name.clone());
+    };
+
+    match resolve_columns(&create.columns, &entry.schema) {
+        Ok(columns) => statement.index_columns = columns,
+        Err(result) => return result,
+    }
+
+    statement.index_name = create.index_name.clone();
+    statement.index_unique = create.unique;
+    statement.table_name = entry.name.clone();
+    PrepareResult::PrepareSuccess
+}
+
+
+/// Looks up the columns an index or a constraint lists, each of which may be given once.
+fn resolve_columns(names: &[String], schema: &Schema) -> Result<Vec<usize>, PrepareResult> {
+    let mut columns = Vec::with_capacity(names.len());
+    for name in names {
+        let Some(column) = schema.column_index(name) else {
+            return Err(PrepareResult::PrepareUnknownColumn(name.clone()));
+        };
+        if columns.contains(&column) {
+            return Err(PrepareResult::PrepareDuplicateColumn(name.clone()));
+        }
+        columns.push(column);
+    }
+
+    Ok(columns)
+}
+
+
+fn prepare_insert(insert: ast::Insert, schema: &Schema, statement: &mut Statement) -> PrepareResult {
+    statement.statement_type = StatementType::StatementInsert;
+
+    let mut exprs: Vec<Option<ast::Expr>> = vec![None; schema.num_columns()];
+
+    match insert.columns {
+        Some(columns) => {
+            if columns.len() != insert.values.len() {
+                return PrepareResult::PrepareValueCountMismatch;
+            }
+
+            for (name, expr) in columns.into_iter().zip(insert.values) {
+                let Some(column) = schema.column_index(&name) else {
+                    return PrepareResult::PrepareUnknownColumn(name);
+                };
+                if exprs[column].is_some() {
+                    return PrepareResult::PrepareDuplicateColumn(name);
+                }
+                exprs[column] = Some(expr);
+            }
+        }
+        None => {
+            let mut values = insert.values;
+
+            // The rowid may be left out, in which case the table allocates one
+            if let Some(rowid_column) = schema.rowid_column() {
+                if values.len() + 1 == schema.num_columns() {
+                    values.insert(rowid_column, ast::Expr::Literal(Value::Null));
+                }
+            }
+
+            if values.len() != schema.num_columns() {
+                return PrepareResult::PrepareValueCountMismatch;
+            }
+
+            exprs = values.into_iter().map(Some).collect();
+        }
+    }
+
+    let mut values = Vec::with_capacity(exprs.len());
+    for (expr, column) in exprs.into_iter().zip(&schema.columns) {
+        let expression = match expr.map(|expr| Expression::bind(&expr, &Scope::empty())).transpose() {
+            Ok(expression) => expression.unwrap_or(Expression::Literal(Value::Null)),
+            Err(e) => return e.into(),
+        };
+        if expression.has_parameters() {
+            values.push(expression);
+            continue;
+        }
+
+        let value = match expression.evaluate_values(&[]) {
+            Ok(value) => value,
+            Err(e) => return PrepareResult::PrepareExpressionError(e),
+        };
+        match value.coerce(column.column_type) {
+            Some(value) => values.push(Expression::Literal(value)),
+            None => return PrepareResult::PrepareTypeMismatch(column.name.clone()),
+        }
+    }
+
+    // Rows with parameters are checked once their values are known
+    let literals: Option<Vec<Value>> = values.iter()
+        .map(|value| match value {
+            Expression::Literal(value) => Some(value.clone()),
+            _ => None,
+        })
+        .collect();
+    if literals.is_some_and(|literals| Row::new(literals).serialized_size() > ROW_SIZE) {
+        return PrepareResult::PrepareRowTooLarge;
+    }
+
+    statement.values_to_insert = values;
+    PrepareResult::PrepareSuccess
+}
+
+
+fn prepare_select(select: ast::Select, catalog: &Catalog, statement: &mut Statement) -> PrepareResult {
+    statement.statement_type = StatementType::StatementSelect;
+
+    let Some(entry) = catalog.find(&select.from.table_name) else {
+        return PrepareResult::PrepareUnknownTable(select.from.table_name.clone());
+    };
+    let mut scope = Scope::new(select.from.name(), &entry.schema);
+
+    // Each ON condition sees the tables joined so far
+    for join in &select.joins {
+        let Some(joined) = catalog.find(&join.table.table_name) else {
+            return PrepareResult::PrepareUnknownTable(join.table.table_name.clone());
+        };
+        let offset = scope.num_columns();
+        scope.join(join.table.name(), &joined.schema);
+
+        let condition = match join.constraint.as_ref().map(|c| Expression::bind(c, &scope)).transpose() {
+            Ok(condition) => condition,
+            Err(e) => return e.into(),
+        };
+        statement.joins.push(Join::new(join.kind, &joined.name, &joined.schema, condition, offset));
+    }
+
+    // Aggregates turn the select into one over groups, a single one without GROUP BY
+    let is_grouped = !select.group_by.is_empty()
+        || select.projection.iter().any(|item| matches!(item, ast::SelectItem::Expr { expr, .. } if expr.contains_aggregate()))
+        || select.order_by.iter().any(|term| term.expr.contains_aggregate());
+
+    let mut aggregation = None;
+    if is_grouped {
+        let group_by = match select.group_by.iter().map(|expr| Expression::bind(expr, &scope)).collect() {
+            Ok(group_by) => group_by,
+            Err(e) => return e.into(),
+        };
+        aggregation = Some(Aggregation { group_by, ..Aggregation::default() });
+    }
+
+    let mut bind = |expr: &ast::Expr| match &mut aggregation {
+        Some(aggregation) => {
+            Expression::bind_grouped(expr, &scope, &aggregation.group_by, &mut aggregation.aggregates)
+        }
+        None => Expression::bind(expr, &scope),
+    };
+
+    for item in &select.projection {
+        match item {
+            ast::SelectItem::Wildcard => {
+                for (table, column) in scope.columns() {
+                    let expr = ast::Expr::Column { table: Some(table.to_string()), name: column.name.clone() };
+                    match bind(&expr) {
+                        Ok(expression) => statement.projection.push(expression),
+                        Err(e) => return e.into(),
+                    }
+                    statement.column_names.push(column.name.clone());
+                }
+            }
+            ast::SelectItem::Expr { expr, alias } => {
+                match bind(expr) {
+                    Ok(expression) => statement.projection.push(expression),
+                    Err(e) => return e.into(),
+                }
+                statement.column_names.push(alias.clone().unwrap_or_else(|| expr.to_string()));
+            }
+        }
+    }
+
+    // Terms may also name an output column by position or alias
+    for term in &select.order_by {
+        let expression = match &term.expr {
+            ast::Expr::Literal(Value::Integer(position)) => {
+                match statement.projection.get((*position as usize).wrapping_sub(1)) {
+                    Some(expression) => expression.clone(),
+                    None => {
+                        let message = format!("ORDER BY position {} is out of range", position);
+                        return PrepareResult::PrepareExpressionError(message);
+                    }
+                }
+            }
+            ast::Expr::Column { table: None, name }
+                if matches!(scope.resolve(None, name), Err(BindError::UnknownColumn(_))) =>
+            {
+                match statement.column_names.iter().position(|c| c.eq_ignore_ascii_case(name)) {
+                    Some(i) => statement.projection[i].clone(),
+                    None => return PrepareResult::PrepareUnknownColumn(name.clone()),
+                }
+            }
+            expr => match bind(expr) {
+                Ok(expression) => expression,
+                Err(e) => return e.into(),
+            },
+        };
+
+        statement.order_by.push((expression, term.descending));
+    }
+
+    if let Some(having) = &select.having {
+        match bind(having) {
+            Ok(having) => aggregation.as_mut().unwrap().having = Some(having),
+            Err(e) => return e.into(),
+        }
+    }
+    statement.aggregation = aggregation;
+
+    statement.limit = match select.limit.map(|expr| prepare_count(&expr, "LIMIT")).transpose() {
+        Ok(limit) => limit,
+        Err(result) => return result,
+    };
+    statement.offset = match select.offset.map(|expr| prepare_count(&expr, "OFFSET")).transpose() {
+        Ok(offset) => offset,
+        Err(result) => return result,
+    };
+
+    prepare_where(select.where_clause, &scope, entry, statement)
+}
+
+
+/// Binds a LIMIT or OFFSET, which must be a non-negative integer. Expressions of
+/// parameters are checked by [`evaluate_count`] once the values are bound.
+fn prepare_count(expr: &ast::Expr, clause: &str) -> Result<Expression, PrepareResult> {
+    let expression = Expression::bind(expr, &Scope::empty()).map_err(PrepareResult::from)?;
+    if expression.has_parameters() {
+        return Ok(expression);
+    }
+
+    let count = evaluate_count(&expression, &[], clause).map_err(PrepareResult::PrepareExpressionError)?;
+    Ok(Expression::Literal(Value::Integer(count as i64)))
+}
+
+
+/// Evaluates a LIMIT or OFFSET given the values of the parameters.
+pub fn evaluate_count(expression: &Expression, parameters: &[Value], clause: &str) -> Result<usize, String> {
+    match expression.evaluate_with(&[], parameters)? {
+        Value::Integer(count) if count >= 0 => Ok(count as usize),
+        _ => Err(format!("{} must be a non-negative integer", clause)),
+    }
+}
+
+
+fn prepare_update(update: ast::Update, entry: &TableEntry, statement: &mut Statement) -> PrepareResult {
+    statement.statement_type = StatementType::StatementUpdate;
+    let schema = &entry.schema;
+    let scope = Scope::new(&update.table_name, schema);
+
+    for (name, expr) in &update.assignments {
+        let Some(column) = schema.column_index(name) else {
+            return PrepareResult::PrepareUnknownColumn(name.clone());
+        };
+        if statement.assignments.iter().any(|(c, _)| *c == column) {
+            return PrepareResult::PrepareDuplicateColumn(name.clone());
+        }
+
+        match Expression::bind(expr, &scope) {
+            Ok(expression) => statement.assignments.push((column, expression)),
+            Err(e) => return e.into(),
+        }
+    }
+
+    prepare_where(update.where_clause, &scope, entry, statement)
+}
+
+
+/// Binds the WHERE clause, and picks the range of the table's primary key or of one of
+/// its indexes that holds the matching rows.
+fn prepare_where(where_clause: Option<ast::Expr>, scope: &Scope, entry: &TableEntry, statement: &mut Statement) -> PrepareResult {
+    let Some(where_clause) = where_clause else {
+        return PrepareResult::PrepareSuccess;
+    };
+
+    match Expression::bind(&where_clause, scope) {
+        Ok(predicate) => {
+            statement.index_scan = IndexScan::find(entry, &predicate);
+            statement.predicate = Some(predicate);
+            PrepareResult::PrepareSuccess
+        }
+        Err(e) => e.into(),
+    }
+}
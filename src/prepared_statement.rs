@@ -0,0 +1,125 @@
+use crate::ast;
+use crate::database::Database;
+use crate::parser::Parser;
+use crate::prepare::{self, PrepareResult};
+use crate::statement::Statement;
+use crate::table::{ExecuteResult, Row};
+use crate::value::Value;
+use crate::vm::Program;
+
+
+/// A compiled statement with `?` and `:name` parameters, which can be executed any
+/// number of times with different values bound to them. Values are loaded into the
+/// program's registers, never read as SQL text, so they cannot change what it does.
+pub struct PreparedStatement {
+    program: Program,
+    explain: bool,
+    /// Name of every parameter, `None` for `?`. Parameter `i` is at `i - 1`.
+    parameters: Vec<Option<String>>,
+    values: Vec<Option<Value>>,
+}
+
+
+impl Database {
+    /// Parses the statement, checks it against the tables and compiles it once for
+    /// every [`PreparedStatement::execute`].
+    pub fn prepare(&self, sql: &str) -> Result<PreparedStatement, PrepareResult> {
+        let (parsed, parameters) = Parser::parse_with_parameters(sql).map_err(PrepareResult::PrepareSyntaxError)?;
+
+        let mut statement = Statement { num_parameters: parameters.len(), ..Statement::default() };
+        match self.prepare_parsed(parsed, &mut statement) {
+            PrepareResult::PrepareSuccess => (),
+            result => return Err(result),
+        }
+
+        Ok(PreparedStatement {
+            program: Program::compile(&statement, &self.catalog),
+            explain: statement.explain,
+            values: vec![None; parameters.len()],
+            parameters,
+        })
+    }
+
+
+    /// Checks the parsed statement against the tables and fills in `statement`. A range
+    /// of the primary key is only read when keys are ordered bytewise, since the rows of
+    /// a range of values are not next to each other in any other order.
+    pub fn prepare_parsed(&self, parsed: ast::Statement, statement: &mut Statement) -> PrepareResult {
+        let result = prepare::prepare_statement(parsed, &self.catalog, statement);
+
+        let key_range = statement.index_scan.as_ref().is_some_and(|scan| scan.index_name.is_none());
+        if key_range && !self.key_comparator.is_bytewise() {
+            statement.index_scan = None;
+        }
+        result
+    }
+}
+
+
+impl PreparedStatement {
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+
+    /// The number of a named parameter, given with or without its leading `:`.
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        let name = name.strip_prefix(':').unwrap_or(name);
+        self.parameters.iter().position(|p| p.as_deref() == Some(name)).map(|i| i + 1)
+    }
+
+
+    /// Binds a value to a parameter, numbered from 1. The value stays bound for every
+    /// following execution until it is replaced.
+    pub fn bind(&mut self, index: usize, value: Value) -> Result<(), PrepareResult> {
+        match index.checked_sub(1).and_then(|i| self.values.get_mut(i)) {
+            Some(bound) => {
+                *bound = Some(value);
+                Ok(())
+            }
+            None => Err(PrepareResult::PrepareUnknownParameter(index.to_string())),
+        }
+    }
+
+
+    /// Binds a value to a named parameter, given with or without its leading `:`.
+    pub fn bind_name(&mut self, name: &str, value: Value) -> Result<(), PrepareResult> {
+        match self.parameter_index(name) {
+            Some(index) => self.bind(index, value),
+            None => Err(PrepareResult::PrepareUnknownParameter(name.to_string())),
+        }
+    }
+
+
+    /// Unbinds every parameter.
+    pub fn clear_bindings(&mut self) {
+        self.values.fill(None);
+    }
+
+
+    /// Runs the statement with the bound values, passing every result row to `emit`.
+    /// Fails with a `PrepareResult` if a parameter is unbound. `explain` emits one row
+    /// per step of the plan, without needing the values.
+    pub fn execute(&self, database: &mut Database, emit: &mut dyn FnMut(&Row)) -> Result<ExecuteResult, PrepareResult> {
+        if self.explain {
+            for step in self.program.plan() {
+                emit(&Row::new(vec![Value::Text(step)]));
+            }
+            return Ok(ExecuteResult::ExecuteSuccess);
+        }
+
+        let mut values = Vec::with_capacity(self.values.len());
+        for (i, value) in self.values.iter().enumerate() {
+            let Some(value) = value else {
+                let parameter = match &self.parameters[i] {
+                    Some(name) => format!(":{}", name),
+                    None => format!("parameter {}", i + 1),
+                };
+                return Err(PrepareResult::PrepareExpressionError(format!("no value is bound to {}", parameter)));
+            };
+            values.push(value.clone());
+        }
+
+        Ok(self.program.execute_with(database, &values, emit))
+    }
+}
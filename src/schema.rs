@@ -0,0 +1,132 @@
+use crate::value::{ColumnType, Value};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub column_type: ColumnType,
+    pub not_null: bool,
+}
+
+
+impl Column {
+    pub fn new(name: &str, column_type: ColumnType) -> Self {
+        Column {
+            name: name.to_string(),
+            column_type,
+            not_null: false,
+        }
+    }
+
+
+    pub fn not_null(mut self) -> Self {
+        self.not_null = true;
+        self
+    }
+}
+
+
+/// Column layout of a table. Primary key columns are implicitly NOT NULL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub columns: Vec<Column>,
+    /// Indexes of the primary key columns, in key order.
+    pub primary_key: Vec<usize>,
+    /// Allocated rowids are never reused, even after the largest row is gone.
+    pub autoincrement: bool,
+}
+
+
+impl Schema {
+    /// Creates a schema whose primary key is the first column.
+    pub fn new(columns: Vec<Column>) -> Self {
+        Self::with_primary_key(columns, vec![0])
+    }
+
+
+    pub fn with_primary_key(mut columns: Vec<Column>, primary_key: Vec<usize>) -> Self {
+        assert!(!primary_key.is_empty(), "Primary key needs at least one column");
+
+        for &column in &primary_key {
+            columns[column].not_null = true;
+        }
+
+        Schema { columns, primary_key, autoincrement: false }
+    }
+
+
+    /// Allocate rowids above the highest one ever used instead of above the current max key.
+    pub fn autoincrement(mut self) -> Self {
+        assert!(self.rowid_column().is_some(), "AUTOINCREMENT requires a single INTEGER primary key");
+        self.autoincrement = true;
+        self
+    }
+
+
+    /// The primary key column if it is a single INTEGER, which can be allocated
+    /// automatically when a row is inserted without it.
+    pub fn rowid_column(&self) -> Option<usize> {
+        match self.primary_key.as_slice() {
+            [column] if self.columns[*column].column_type == ColumnType::Integer => Some(*column),
+            _ => None,
+        }
+    }
+
+
+    /// The schema used when a database is opened without one: `(id, username, email)`.
+    pub fn users() -> Self {
+        Schema::new(vec![
+            Column::new("id", ColumnType::Integer),
+            Column::new("username", ColumnType::Text),
+            Column::new("email", ColumnType::Text),
+        ])
+    }
+
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+
+    /// Extracts the primary key values from a full row.
+    pub fn key_values(&self, values: &[Value]) -> Vec<Value> {
+        self.primary_key.iter().map(|&i| values[i].clone()).collect()
+    }
+
+
+    /// Returns the first column whose value does not match its declared type.
+    /// NULL matches every type.
+    pub fn type_check(&self, values: &[Value]) -> Result<(), &Column> {
+        for (column, value) in self.columns.iter().zip(values) {
+            if value.column_type().is_some_and(|t| t != column.column_type) {
+                return Err(column);
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /// Returns the first NOT NULL column that holds a NULL value.
+    pub fn check_not_null(&self, values: &[Value]) -> Result<(), &Column> {
+        for (column, value) in self.columns.iter().zip(values) {
+            if column.not_null && value.is_null() {
+                return Err(column);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema::users()
+    }
+}
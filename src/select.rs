@@ -0,0 +1,258 @@
+use crate::ast::{BinaryOperator, JoinKind};
+use crate::catalog::TableEntry;
+use crate::database::Database;
+use crate::expression::Expression;
+use crate::schema::Schema;
+use crate::statement::Statement;
+use crate::table::{ExecuteResult, Row};
+use crate::value::{ColumnType, Value};
+use crate::vm::Program;
+
+
+/// A table joined to the rows of the tables before it.
+pub struct Join {
+    pub kind: JoinKind,
+    pub table_name: String,
+    /// ON condition, evaluated against the joined row.
+    pub condition: Option<Expression>,
+    /// Primary key of the matching row, evaluated against the row being joined to.
+    /// Set when the condition requires the key to equal it, so the joined row can be
+    /// found with a key lookup instead of a scan.
+    pub key_lookup: Option<Expression>,
+    /// Number of columns the table adds to the row.
+    pub num_columns: usize,
+}
+
+
+impl Join {
+    /// `offset` is the number of columns of the tables before this one.
+    pub fn new(kind: JoinKind, table_name: &str, schema: &Schema, condition: Option<Expression>, offset: usize) -> Self {
+        let key_lookup = match (schema.primary_key.as_slice(), &condition) {
+            ([key_column], Some(condition)) => Self::find_key_lookup(condition, offset + key_column, offset),
+            _ => None,
+        };
+
+        Join {
+            kind,
+            table_name: table_name.to_string(),
+            condition,
+            key_lookup,
+            num_columns: schema.num_columns(),
+        }
+    }
+
+
+    /// Looks for `key = <expr>` among the terms ANDed together in the condition, where
+    /// the expression only refers to columns before `offset`.
+    fn find_key_lookup(condition: &Expression, key_column: usize, offset: usize) -> Option<Expression> {
+        let Expression::Binary { operator, left, right } = condition else {
+            return None;
+        };
+
+        match operator {
+            BinaryOperator::And => Self::find_key_lookup(left, key_column, offset)
+                .or_else(|| Self::find_key_lookup(right, key_column, offset)),
+            BinaryOperator::Equal => {
+                let is_key = |e: &Expression| *e == Expression::Column(key_column);
+                let is_outer = |e: &Expression| e.max_column().is_none_or(|column| column < offset);
+
+                if is_key(left) && is_outer(right) {
+                    Some(right.as_ref().clone())
+                } else if is_key(right) && is_outer(left) {
+                    Some(left.as_ref().clone())
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+
+/// A range of the primary key or of an index that is read instead of scanning the FROM
+/// table, when the WHERE clause bounds the first key column. The WHERE clause is still
+/// tested on every row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IndexScan {
+    /// The index read, or `None` to read the table itself in primary key order.
+    pub index_name: Option<String>,
+    /// The first key column, as a column of the table.
+    pub column: usize,
+    /// Smallest value of the column, and whether it is included. Without a lower bound
+    /// it is the smallest value of the column type, which leaves out NULLs. Bounds are
+    /// literals converted to the column type, or expressions of parameters that are
+    /// converted once the values are bound.
+    pub lower: (Expression, bool),
+    /// Largest value of the column, and whether it is included.
+    pub upper: Option<(Expression, bool)>,
+    /// The bounds as shown in the plan, like `id>? AND id<?`.
+    pub constraint: String,
+}
+
+
+impl IndexScan {
+    /// Picks the primary key or an index of the table for the predicate, preferring one
+    /// whose first column the predicate requires to equal a constant over one it only
+    /// bounds, and the primary key over an index.
+    pub fn find(entry: &TableEntry, predicate: &Expression) -> Option<Self> {
+        let mut terms = Vec::new();
+        Self::collect_terms(predicate, &mut terms);
+
+        let primary_key = entry.schema.primary_key.first().map(|&column| (None, column));
+        let indexes = entry.indexes.iter().map(|index| (Some(index.name()), index.columns[0]));
+
+        let mut best: Option<IndexScan> = None;
+        for (index_name, column) in primary_key.into_iter().chain(indexes) {
+            let Some(scan) = Self::find_range(&entry.schema, &terms, column) else {
+                continue;
+            };
+            let scan = IndexScan { index_name: index_name.map(String::from), ..scan };
+            if best.as_ref().is_none_or(|best| scan.is_equality() && !best.is_equality()) {
+                best = Some(scan);
+            }
+        }
+
+        best
+    }
+
+
+    /// The range of the column that the terms ANDed together bound, read from the table.
+    fn find_range(schema: &Schema, terms: &[&Expression], column: usize) -> Option<Self> {
+        let column_type = schema.columns[column].column_type;
+        let mut lower = None;
+        let mut upper = None;
+
+        for term in terms {
+            match Self::find_bound(term, column, column_type) {
+                Some((BinaryOperator::Equal, value)) => {
+                    lower = Some((value.clone(), true));
+                    upper = Some((value, true));
+                    break;
+                }
+                Some((operator @ (BinaryOperator::Greater | BinaryOperator::GreaterEqual), value)) => {
+                    lower = lower.or(Some((value, operator == BinaryOperator::GreaterEqual)));
+                }
+                Some((operator, value)) => {
+                    upper = upper.or(Some((value, operator == BinaryOperator::LessEqual)));
+                }
+                None => (),
+            }
+        }
+
+        if lower.is_none() && upper.is_none() {
+            return None;
+        }
+
+        let name = &schema.columns[column].name;
+        let constraint = match (&lower, &upper) {
+            (Some(lower), Some(upper)) if lower.1 && lower == upper => format!("{}=?", name),
+            _ => {
+                let lower = lower.as_ref().map(|(_, inclusive)| if *inclusive { ">=" } else { ">" });
+                let upper = upper.as_ref().map(|(_, inclusive)| if *inclusive { "<=" } else { "<" });
+                let bounds: Vec<String> = lower.into_iter().chain(upper).map(|operator| format!("{}{}?", name, operator)).collect();
+                bounds.join(" AND ")
+            }
+        };
+
+        Some(IndexScan {
+            index_name: None,
+            column,
+            lower: lower.unwrap_or_else(|| (Expression::Literal(Self::smallest_value(column_type)), true)),
+            upper,
+            constraint,
+        })
+    }
+
+
+    /// Whether the scan only reads entries equal to one value.
+    fn is_equality(&self) -> bool {
+        self.upper.as_ref().is_some_and(|upper| upper.1 && self.lower.1 && upper.0 == self.lower.0)
+    }
+
+
+    /// What is read, as shown in the plan, like `PRIMARY KEY (id=?)`.
+    pub fn access_path(&self) -> String {
+        match &self.index_name {
+            Some(name) => format!("INDEX {} ({})", name, self.constraint),
+            None => format!("PRIMARY KEY ({})", self.constraint),
+        }
+    }
+
+
+    fn collect_terms<'e>(predicate: &'e Expression, terms: &mut Vec<&'e Expression>) {
+        match predicate {
+            Expression::Binary { operator: BinaryOperator::And, left, right } => {
+                Self::collect_terms(left, terms);
+                Self::collect_terms(right, terms);
+            }
+            term => terms.push(term),
+        }
+    }
+
+
+    /// Reads a term comparing the column with a constant as `<column> <operator> <value>`,
+    /// with the value converted to the column type. NULL and values of other types give
+    /// no bound.
+    fn find_bound(term: &Expression, column: usize, column_type: ColumnType) -> Option<(BinaryOperator, Expression)> {
+        let Expression::Binary { operator, left, right } = term else {
+            return None;
+        };
+
+        let is_column = |e: &Expression| *e == Expression::Column(column);
+        let (operator, constant) = if is_column(left) && right.max_column().is_none() {
+            (*operator, right)
+        } else if is_column(right) && left.max_column().is_none() {
+            let flipped = match operator {
+                BinaryOperator::Less => BinaryOperator::Greater,
+                BinaryOperator::LessEqual => BinaryOperator::GreaterEqual,
+                BinaryOperator::Greater => BinaryOperator::Less,
+                BinaryOperator::GreaterEqual => BinaryOperator::LessEqual,
+                operator => *operator,
+            };
+            (flipped, left)
+        } else {
+            return None;
+        };
+
+        if !matches!(
+            operator,
+            BinaryOperator::Equal
+                | BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual
+        ) {
+            return None;
+        }
+
+        if constant.has_parameters() {
+            return Some((operator, constant.as_ref().clone()));
+        }
+        let value = constant.evaluate_values(&[]).ok()?.coerce(column_type)?;
+        (!value.is_null()).then_some((operator, Expression::Literal(value)))
+    }
+
+
+    /// The value every other value of the type sorts after.
+    fn smallest_value(column_type: ColumnType) -> Value {
+        match column_type {
+            ColumnType::Integer => Value::Integer(i64::MIN),
+            ColumnType::Real => Value::Real(f64::NEG_INFINITY),
+            ColumnType::Text => Value::Text(String::new()),
+            ColumnType::Blob => Value::Blob(Vec::new()),
+            ColumnType::Boolean => Value::Boolean(false),
+        }
+    }
+}
+
+
+impl Database {
+    /// Runs a select, passing every result row to `emit`. Without an ORDER BY the rows
+    /// are emitted during the scan, which stops as soon as the LIMIT is reached.
+    /// Otherwise they go through an external sort first. Aggregating selects emit
+    /// their groups once the scan is complete.
+    pub fn select(&mut self, statement: &Statement, emit: &mut dyn FnMut(&Row)) -> ExecuteResult {
+        Program::compile(statement, &self.catalog).execute(self, emit)
+    }
+}
@@ -0,0 +1,244 @@
+use std::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use crate::table::Row;
+use crate::value::Value;
+
+
+/// External merge sort of rows by precomputed sort keys. Rows are collected in memory
+/// until they exceed the memory limit, then sorted and written to a temporary file as
+/// a run. Finishing merges all runs. The sort is stable.
+pub struct Sorter {
+    /// Sort direction of each key, `true` for descending.
+    descending: Vec<bool>,
+    memory_limit: usize,
+    buffer: Vec<(Vec<Value>, Row)>,
+    buffer_size: usize,
+    runs: Vec<SortRun>,
+}
+
+
+impl Sorter {
+    pub fn new(descending: Vec<bool>, memory_limit: usize) -> Self {
+        Sorter {
+            descending,
+            memory_limit,
+            buffer: Vec::new(),
+            buffer_size: 0,
+            runs: Vec::new(),
+        }
+    }
+
+
+    pub fn add(&mut self, keys: Vec<Value>, row: Row) -> io::Result<()> {
+        self.buffer_size += keys.iter().map(Value::serialized_size).sum::<usize>() + row.serialized_size();
+        self.buffer.push((keys, row));
+
+        if self.buffer_size > self.memory_limit {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+
+    /// Sorts the buffered rows and writes them out as a new run.
+    fn spill(&mut self) -> io::Result<()> {
+        self.sort_buffer();
+        let mut run = SortRun::create()?;
+
+        {
+            let mut writer = BufWriter::new(&mut run.file);
+            for (keys, row) in self.buffer.drain(..) {
+                let mut values = keys;
+                values.extend(row.values);
+                let record = Row::new(values);
+
+                let mut bytes = vec![0; record.serialized_size()];
+                record.serialize_row(&mut bytes);
+                writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+                writer.write_all(&bytes)?;
+            }
+            writer.flush()?;
+        }
+
+        self.buffer_size = 0;
+        self.runs.push(run);
+        Ok(())
+    }
+
+
+    fn sort_buffer(&mut self) {
+        let descending = &self.descending;
+        self.buffer.sort_by(|(a, _), (b, _)| Self::compare_keys(descending, a, b));
+    }
+
+
+    /// Returns the rows in sorted order.
+    pub fn finish(mut self) -> io::Result<SortedRows> {
+        if self.runs.is_empty() {
+            self.sort_buffer();
+            return Ok(SortedRows::Memory(self.buffer.into_iter()));
+        }
+
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        let num_keys = self.descending.len();
+        let mut readers = Vec::with_capacity(self.runs.len());
+        for run in self.runs {
+            readers.push(RunReader::open(run, num_keys)?);
+        }
+
+        let mut heads = Vec::with_capacity(readers.len());
+        for reader in &mut readers {
+            heads.push(reader.next_record()?);
+        }
+
+        Ok(SortedRows::Merge { descending: self.descending, readers, heads })
+    }
+
+
+    fn compare_keys(descending: &[bool], a: &[Value], b: &[Value]) -> Ordering {
+        for ((a, b), &descending) in a.iter().zip(b).zip(descending) {
+            let ordering = Self::compare_values(a, b);
+            let ordering = if descending { ordering.reverse() } else { ordering };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    }
+
+
+    /// Total order used for sorting: NULL first, then numbers, text and blobs.
+    /// Values of the same kind compare as in expressions.
+    pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+        let rank = |value: &Value| match value {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) | Value::Boolean(_) => 1,
+            Value::Text(_) => 2,
+            Value::Blob(_) => 3,
+        };
+
+        a.partial_compare(b).unwrap_or_else(|| rank(a).cmp(&rank(b)))
+    }
+}
+
+
+pub enum SortedRows {
+    Memory(std::vec::IntoIter<(Vec<Value>, Row)>),
+    /// Merge of spilled runs, holding the next record of every run.
+    Merge {
+        descending: Vec<bool>,
+        readers: Vec<RunReader>,
+        heads: Vec<Option<(Vec<Value>, Row)>>,
+    },
+}
+
+
+impl Iterator for SortedRows {
+    type Item = io::Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SortedRows::Memory(rows) => rows.next().map(|(_, row)| Ok(row)),
+            SortedRows::Merge { descending, readers, heads } => {
+                // The first of equal heads wins, which keeps the merge stable
+                let mut smallest: Option<usize> = None;
+                for (i, head) in heads.iter().enumerate() {
+                    let Some((keys, _)) = head else { continue };
+                    let replace = match smallest {
+                        None => true,
+                        Some(j) => {
+                            let (smallest_keys, _) = heads[j].as_ref().unwrap();
+                            Sorter::compare_keys(descending, keys, smallest_keys) == Ordering::Less
+                        }
+                    };
+                    if replace {
+                        smallest = Some(i);
+                    }
+                }
+
+                let i = smallest?;
+                let (_, row) = heads[i].take().unwrap();
+                match readers[i].next_record() {
+                    Ok(head) => heads[i] = head,
+                    Err(e) => return Some(Err(e)),
+                }
+
+                Some(Ok(row))
+            }
+        }
+    }
+}
+
+
+/// A temporary file holding one sorted run, removed when dropped.
+struct SortRun {
+    path: PathBuf,
+    file: File,
+}
+
+
+impl SortRun {
+    fn create() -> io::Result<Self> {
+        static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+        let run_number = NEXT_RUN.fetch_add(1, AtomicOrdering::Relaxed);
+        let path = std::env::temp_dir()
+            .join(format!("crablite-sort-{}-{}.tmp", std::process::id(), run_number));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(SortRun { path, file })
+    }
+}
+
+
+impl Drop for SortRun {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+
+/// Reads the records of a run back in order. Records are a u32 length followed by a
+/// serialized row holding the sort keys and then the row's own values.
+pub struct RunReader {
+    reader: BufReader<File>,
+    num_keys: usize,
+    _run: SortRun,
+}
+
+
+impl RunReader {
+    fn open(run: SortRun, num_keys: usize) -> io::Result<Self> {
+        let file = File::open(&run.path)?;
+        Ok(RunReader { reader: BufReader::new(file), num_keys, _run: run })
+    }
+
+
+    fn next_record(&mut self) -> io::Result<Option<(Vec<Value>, Row)>> {
+        let mut length = [0u8; 4];
+        match self.reader.read_exact(&mut length) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let mut bytes = vec![0; u32::from_le_bytes(length) as usize];
+        self.reader.read_exact(&mut bytes)?;
+
+        let mut keys = Row::deserialize_row(&bytes)?.values;
+        let values = keys.split_off(self.num_keys);
+        Ok(Some((keys, Row::new(values))))
+    }
+}
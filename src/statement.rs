@@ -1,15 +1,60 @@
-use crate::Row;
+use crate::aggregate::Aggregation;
+use crate::schema::Schema;
+use crate::select::{IndexScan, Join};
+use crate::expression::Expression;
 
 pub enum StatementType {
+    StatementCreateTable,
+    StatementCreateIndex,
     StatementInsert,
     StatementSelect,
+    StatementUpdate,
+    StatementDelete,
     None,
 }
 
 
 pub struct Statement {
     pub statement_type: StatementType,
-    pub row_to_insert: Row
+    /// Print how the statement would run instead of running it.
+    pub explain: bool,
+    /// The table the statement applies to, the first one in the FROM clause of a select.
+    pub table_name: String,
+    /// Columns of the table a create table defines.
+    pub table_schema: Option<Schema>,
+    /// Columns of every UNIQUE constraint of a create table.
+    pub unique_constraints: Vec<Vec<usize>>,
+    /// Name and table columns of the index a create index defines.
+    pub index_name: String,
+    pub index_columns: Vec<usize>,
+    pub index_unique: bool,
+    /// Value of every column of the inserted row: a literal of the column type, or an
+    /// expression of parameters that is converted to it when the statement runs.
+    pub values_to_insert: Vec<Expression>,
+    /// Output values of a select, and their column names.
+    pub projection: Vec<Expression>,
+    pub column_names: Vec<String>,
+    /// Tables joined to the first one in a select, in order.
+    pub joins: Vec<Join>,
+    /// Range of the primary key or of an index that the rows of a select, update or
+    /// delete are read from, instead of scanning the table.
+    pub index_scan: Option<IndexScan>,
+    /// GROUP BY and aggregates of a select. When set, the projection and ORDER BY are
+    /// evaluated against the group rows it produces.
+    pub aggregation: Option<Aggregation>,
+    /// Sort keys of a select, evaluated against the table row, and whether each is descending.
+    pub order_by: Vec<(Expression, bool)>,
+    /// Maximum number of rows a select returns, after skipping `offset` rows. Both are
+    /// non-negative integer literals, or expressions of parameters checked when the
+    /// statement runs.
+    pub limit: Option<Expression>,
+    pub offset: Option<Expression>,
+    /// Column index and new value of every `set` in an update.
+    pub assignments: Vec<(usize, Expression)>,
+    /// Rows a select, update or delete applies to. `None` means every row.
+    pub predicate: Option<Expression>,
+    /// Number of parameters the statement's expressions refer to.
+    pub num_parameters: usize,
 }
 
 
@@ -17,7 +62,25 @@ impl Default for Statement {
     fn default() -> Self {
         Statement {
             statement_type: StatementType::None,
-            row_to_insert: Row::default()
+            explain: false,
+            table_name: String::new(),
+            table_schema: None,
+            unique_constraints: Vec::new(),
+            index_name: String::new(),
+            index_columns: Vec::new(),
+            index_unique: false,
+            values_to_insert: Vec::new(),
+            projection: Vec::new(),
+            column_names: Vec::new(),
+            joins: Vec::new(),
+            index_scan: None,
+            aggregation: None,
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+            assignments: Vec::new(),
+            predicate: None,
+            num_parameters: 0,
         }
     }
-}
\ No newline at end of file
+}
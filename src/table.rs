@@ -1,117 +1,73 @@
-use std::{io, ptr};
-use crate::leaf_node::LeafNode;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use crate::catalog::TableEntry;
+use crate::internal_node::InternalNode;
+use crate::key::{BytewiseComparator, Key, KeyComparator};
+use crate::leaf_node::{LeafNode, NodeType};
 use crate::cursor::Cursor;
 use crate::pager::{Pager};
 use crate::data_consts::*;
+use crate::value::Value;
 
 
-#[derive(Debug)]
-#[repr(C)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Row {
-    pub id: u32,
-    pub username: [u8; USERNAME_SIZE],
-    pub email: [u8; EMAIL_SIZE],
+    pub values: Vec<Value>,
 }
 
 
 impl Row {
-    pub fn new(id: u32, username: &str, email: &str) -> Self {
-        let mut row = Row {
-            id,
-            username: [0; 32],
-            email: [0; 255]
-        };
+    /// Record header: the number of values in the row.
+    const NUM_VALUES_SIZE: usize = std::mem::size_of::<u16>();
 
-        let username_bytes = username.as_bytes();
-        let email_bytes = email.as_bytes();
 
-        row.username[..username.len()].copy_from_slice(username_bytes);
-        row.email[..email.len()].copy_from_slice(email_bytes);
-        row
+    pub fn new(values: Vec<Value>) -> Self {
+        Row { values }
     }
 
 
-    pub fn serialize_row_unsafe(&self, destination: &mut [u8]) {
-        assert_eq!(destination.len(), EMAIL_OFFSET + EMAIL_SIZE);
-
-        unsafe {
-            let source_ptr = self as *const Row as *const u8;
-            ptr::copy_nonoverlapping(
-                source_ptr.add(ID_OFFSET),
-                destination.as_mut_ptr().add(ID_OFFSET),
-                ID_SIZE);
-            ptr::copy_nonoverlapping(
-                source_ptr.add(USERNAME_OFFSET),
-                destination.as_mut_ptr().add(USERNAME_OFFSET),
-                USERNAME_SIZE);
-            ptr::copy_nonoverlapping(
-                source_ptr.add(EMAIL_OFFSET),
-                destination.as_mut_ptr().add(EMAIL_OFFSET),
-                EMAIL_SIZE);
-        }
+    pub fn serialized_size(&self) -> usize {
+        Self::NUM_VALUES_SIZE + self.values.iter().map(Value::serialized_size).sum::<usize>()
     }
 
 
     pub fn serialize_row(&self, destination: &mut [u8]) {
-        let id_bytes = self.id.to_le_bytes();
-        destination[ID_OFFSET..ID_OFFSET + ID_SIZE].copy_from_slice(&id_bytes);
-
-        let username_length = self.username.len().min(USERNAME_SIZE);
-        destination[USERNAME_OFFSET..USERNAME_OFFSET + username_length]
-            .copy_from_slice(&self.username[..username_length]);
+        assert!(self.serialized_size() <= destination.len(), "Row does not fit in destination");
 
-        for i in username_length..USERNAME_SIZE {
-            destination[USERNAME_OFFSET + i] = 0;
+        destination[..Self::NUM_VALUES_SIZE].copy_from_slice(&(self.values.len() as u16).to_le_bytes());
+        let mut offset = Self::NUM_VALUES_SIZE;
+        for value in &self.values {
+            offset += value.serialize(&mut destination[offset..]);
         }
 
-        let email_length = self.email.len().min(EMAIL_SIZE);
-        destination[EMAIL_OFFSET..EMAIL_OFFSET + email_length]
-            .copy_from_slice(&self.email[..email_length]);
-
-        for i in email_length..EMAIL_SIZE {
-            destination[EMAIL_OFFSET + i] = 0;
-        }
+        destination[offset..].fill(0);
     }
 
 
-    pub fn deserialize_row(source: &[u8]) -> Self {
-        let id = u32::from_le_bytes([source[0], source[1], source[2], source[3]]);
-        let mut username = [0; USERNAME_SIZE];
-        username.copy_from_slice(&source[ID_OFFSET + ID_SIZE..USERNAME_OFFSET + USERNAME_SIZE]);
-        let mut email = [0; EMAIL_SIZE];
-        email.copy_from_slice(&source[EMAIL_OFFSET..EMAIL_OFFSET + EMAIL_SIZE]);
+    /// Reads a row written by `serialize_row`. Fails with `InvalidData` if the bytes
+    /// are not a row.
+    pub fn deserialize_row(source: &[u8]) -> io::Result<Self> {
+        let num_values = source.get(..Self::NUM_VALUES_SIZE)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Corrupt record: row is empty"))?;
+        let mut values = Vec::with_capacity(num_values);
+        let mut offset = Self::NUM_VALUES_SIZE;
 
-        Row { id, username, email }
-    }
-
-
-    pub fn deserialize_row_existing_ref(source: &[u8], destination: &mut Row) {
-        let (id_bytes, rest) = source.split_at(ID_SIZE);
-        destination.id = u32::from_ne_bytes(id_bytes.try_into().unwrap());
-
-        let (username_bytes, rest) = rest.split_at(USERNAME_SIZE);
-        destination.username.copy_from_slice(username_bytes);
+        for _ in 0..num_values {
+            let (value, size) = Value::deserialize(&source[offset..])?;
+            values.push(value);
+            offset += size;
+        }
 
-        let (email_bytes, _) = rest.split_at(EMAIL_SIZE);
-        destination.email.copy_from_slice(email_bytes);
+        Ok(Row { values })
     }
 
 
     pub fn print_row(&self) {
-        let username = String::from_utf8_lossy(&self.username);
-        let email = String::from_utf8_lossy(&self.email);
-        println!("({}, {}, {})", self.id, username.trim_end(), email.trim_end());
-    }
-}
-
-
-impl Default for Row {
-    fn default() -> Self {
-        Row {
-            id: 0,
-            username: [0; USERNAME_SIZE],
-            email: [0; EMAIL_SIZE],
-        }
+        let values: Vec<String> = self.values.iter().map(Value::to_string).collect();
+        println!("({})", values.join(", "));
     }
 }
 
@@ -119,41 +75,170 @@ impl Default for Row {
 pub enum ExecuteResult {
     ExecuteSuccess,
     ExecuteTableFull,
+    /// The statement could not be run, such as when a page cannot be read or written,
+    /// or a table it was compiled against is gone. Details go to stderr where known.
     ExecuteFailed,
     ExecuteDuplicateKey,
+    ExecuteTypeMismatch,
+    ExecuteRowTooLarge,
+    ExecuteKeyTooLarge,
+    /// NULL given for the named NOT NULL column.
+    ExecuteNotNullViolation(String),
+    /// A value of the named UNIQUE columns is already taken by another row.
+    ExecuteUniqueViolation(String),
+    /// An expression could not be evaluated, such as comparing text with a number.
+    ExecuteExpressionError(String),
+    /// A table with the name already exists.
+    ExecuteTableExists(String),
+    /// A table or index with the name already exists.
+    ExecuteIndexExists(String),
+    /// Bulk loading needs an empty table.
+    ExecuteTableNotEmpty,
+    /// Bulk loaded rows are not in primary key order.
+    ExecuteUnsortedKeys,
+    /// Every rowid is taken, or an AUTOINCREMENT table has handed out the largest one.
+    ExecuteNoFreeRowid,
+}
+
+
+/// Describes a failure the way the REPL reports it, without the `Error:` prefix.
+impl fmt::Display for ExecuteResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecuteResult::ExecuteSuccess => f.write_str("Executed"),
+            ExecuteResult::ExecuteTableFull => f.write_str("Table full"),
+            ExecuteResult::ExecuteFailed => f.write_str("Statement could not be executed"),
+            ExecuteResult::ExecuteDuplicateKey => f.write_str("Duplicate key"),
+            ExecuteResult::ExecuteTypeMismatch => f.write_str("Row does not match table schema"),
+            ExecuteResult::ExecuteRowTooLarge => f.write_str("Row is too large"),
+            ExecuteResult::ExecuteKeyTooLarge => f.write_str("Primary key is too large"),
+            ExecuteResult::ExecuteNotNullViolation(column) => write!(f, "NOT NULL constraint failed for column '{}'", column),
+            ExecuteResult::ExecuteUniqueViolation(columns) => write!(f, "UNIQUE constraint failed for column '{}'", columns),
+            ExecuteResult::ExecuteExpressionError(e) => f.write_str(e),
+            ExecuteResult::ExecuteTableExists(name) => write!(f, "Table '{}' already exists", name),
+            ExecuteResult::ExecuteIndexExists(name) => write!(f, "Index '{}' already exists", name),
+            ExecuteResult::ExecuteTableNotEmpty => f.write_str("Table is not empty"),
+            ExecuteResult::ExecuteUnsortedKeys => f.write_str("Rows are not sorted by primary key"),
+            ExecuteResult::ExecuteNoFreeRowid => f.write_str("No free rowid"),
+        }
+    }
 }
 
 
-pub struct Table {
-    pub pager: Pager,
-    pub root_page_num: usize,
+/// One table of the database: its B-tree in the shared pager and its catalog entry.
+pub struct Table<'a> {
+    pub pager: &'a mut Pager,
+    pub entry: &'a mut TableEntry,
+    pub key_comparator: &'a dyn KeyComparator,
 }
 
 
-impl Table {
-    pub fn db_open(filename: &str) -> io::Result<Self> {
-        let mut pager = Pager::pager_open(filename)?;
+impl Table<'_> {
+    /// Inserts the row. A NULL in a single INTEGER primary key column is replaced by
+    /// the next free rowid.
+    pub fn insert_row(&mut self, row_to_insert: &Row) -> ExecuteResult {
+        let mut row_to_insert = Cow::Borrowed(row_to_insert);
+
+        if let Some(rowid_column) = self.entry.schema.rowid_column() {
+            if row_to_insert.values.get(rowid_column).is_some_and(Value::is_null) {
+                let Some(rowid) = self.next_rowid() else {
+                    return ExecuteResult::ExecuteNoFreeRowid;
+                };
+                row_to_insert.to_mut().values[rowid_column] = Value::Integer(rowid);
+            }
+        }
+
+        let key_to_insert = match self.check_row(&row_to_insert) {
+            Ok(key) => key,
+            Err(result) => return result,
+        };
 
-        if pager.num_pages == 0 {
-            // New database file. Initialize page 0 as leaf node.
-            let root_node = pager.get_page(0)?;
-            LeafNode::initialize_leaf_node(root_node);
+        let index_rows = self.index_rows(&row_to_insert);
+        for (i, index_row) in index_rows.iter().enumerate() {
+            if let Err(result) = self.index_tree(i).check_row(index_row) {
+                return result;
+            }
         }
 
-        Ok(
+        // A split allocates at most one page per level plus a new root, in every tree
+        match self.pages_needed() {
+            Ok(pages) if self.pager.num_pages + pages < TABLE_MAX_PAGES => (),
+            Ok(_) => return ExecuteResult::ExecuteTableFull,
+            Err(_) => return ExecuteResult::ExecuteFailed,
+        }
+
+        let (cursor_page_num, cursor_cell_num) = Cursor::table_find_position(self, &key_to_insert);
+
+        let node = self.pager.get_page(cursor_page_num).expect("Failed to retrieve page");
+        let num_cells = LeafNode::leaf_node_num_cells(node) as usize;
+
+        if cursor_cell_num < num_cells {
+            let key_at_index = LeafNode::leaf_node_key(node, cursor_cell_num);
+            if self.key_comparator.compare(key_at_index, &key_to_insert) == Ordering::Equal {
+                return ExecuteResult::ExecuteDuplicateKey;
+            }
+        }
+
+        if let Err(result) = self.check_unique(&[&row_to_insert], &[]) {
+            return result;
+        }
+
+        let mut cursor = Cursor {
+            table: self,
+            page_num: cursor_page_num,
+            cell_num: cursor_cell_num,
+            end_of_table: false,
+        };
+
+        if let Err(e) = LeafNode::leaf_node_insert(&mut cursor, &key_to_insert, &row_to_insert) {
+            eprintln!("Failed to insert row: {:?}", e);
+            return ExecuteResult::ExecuteFailed;
+        }
+
+        for (i, index_row) in index_rows.iter().enumerate() {
+            match self.index_tree(i).insert_row(index_row) {
+                ExecuteResult::ExecuteSuccess => (),
+                result => return result,
+            }
+        }
+
+        if self.entry.schema.autoincrement {
+            if let Some(&Value::Integer(rowid)) = self.entry.schema.rowid_column().map(|c| &row_to_insert.values[c]) {
+                // Stored in the catalog when the database is closed
+                if rowid > self.entry.sequence {
+                    self.entry.sequence = rowid;
+                }
+            }
+        }
+
+        ExecuteResult::ExecuteSuccess
+    }
+
+
+    /// The tree of the table's `i`th index. Index keys are ordered bytewise, whatever
+    /// order the table's primary keys are kept in.
+    pub(crate) fn index_tree(&mut self, i: usize) -> Table<'_> {
         Table {
-            pager,
-            root_page_num: 0
-        })
+            pager: &mut *self.pager,
+            entry: &mut self.entry.indexes[i].tree,
+            key_comparator: &BytewiseComparator,
+        }
     }
 
 
-    pub fn db_close(&mut self) -> io::Result<()> {
-        let pager = &mut self.pager;
+    /// The row every index stores for a table row.
+    pub(crate) fn index_rows(&self, row: &Row) -> Vec<Row> {
+        self.entry.indexes.iter().map(|index| index.index_row(&self.entry.schema, &row.values)).collect()
+    }
+
 
-        for i in 0..pager.num_pages {
-            if pager.pages[i].is_some() {
-                pager.pager_flush(i)?;
+    /// Removes the entries of a table row from every index.
+    fn delete_index_rows(&mut self, row: &Row) -> io::Result<()> {
+        for (i, index_row) in self.index_rows(row).iter().enumerate() {
+            let key = Key::encode(&index_row.values);
+            let mut index = self.index_tree(i);
+            if index.contains_key(&key) {
+                index.delete_key(&key)?;
             }
         }
 
@@ -161,121 +246,351 @@ impl Table {
     }
 
 
-    pub fn insert_row(&mut self, row_to_insert: &Row) -> ExecuteResult {
-        let root_page_num = self.root_page_num;
-
-        let node = match self.pager.get_page(root_page_num) {
-            Ok(page) => page,
-            Err(_) => return ExecuteResult::ExecuteFailed,
-        };
+    /// Checks that the rows would not take values of a unique index from any row other
+    /// than those with the `replaced` keys, sorted bytewise, or from each other.
+    fn check_unique(&mut self, rows: &[&Row], replaced: &[Vec<u8>]) -> Result<(), ExecuteResult> {
+        for i in 0..self.entry.indexes.len() {
+            if !self.entry.indexes[i].unique {
+                continue;
+            }
 
-        let num_cells = *LeafNode::leaf_node_num_cells(node) as usize;
-        if num_cells >= LeafNode::LEAF_NODE_MAX_CELLS {
-            return ExecuteResult::ExecuteTableFull;
+            let index = &self.entry.indexes[i];
+            let mut taken: Vec<Vec<u8>> = rows.iter().filter_map(|row| index.unique_prefix(&row.values)).collect();
+            taken.sort();
+            let repeated = taken.windows(2).any(|pair| pair[0] == pair[1]);
+            if repeated || taken.iter().any(|prefix| self.unique_conflict(i, prefix, replaced)) {
+                let index = &self.entry.indexes[i];
+                return Err(ExecuteResult::ExecuteUniqueViolation(index.column_names(&self.entry.schema)));
+            }
         }
 
-        let key_to_insert = row_to_insert.id;
-        let (cursor_page_num, cursor_cell_num) = Cursor::table_find_position(self, key_to_insert);
+        Ok(())
+    }
 
-        let node = self.pager.get_page(cursor_page_num).expect("Failed to retrieve page");
 
-        if cursor_cell_num < num_cells {
-            let key_at_index = u32::from_le_bytes(
-                LeafNode::leaf_node_key(node, cursor_cell_num)
-                    .try_into()
-                    .unwrap(),
-            );
-            if key_at_index == key_to_insert {
-                return ExecuteResult::ExecuteDuplicateKey;
+    /// Whether the `i`th index has an entry starting with the prefix for a row whose
+    /// key is not one of the `replaced` keys.
+    fn unique_conflict(&mut self, i: usize, prefix: &[u8], replaced: &[Vec<u8>]) -> bool {
+        let key_columns = self.entry.schema.primary_key.len();
+        let mut index = self.index_tree(i);
+        let mut cursor = Cursor::table_find(&mut index, prefix);
+
+        while !cursor.end_of_table && cursor.cursor_key().starts_with(prefix) {
+            // The entry ends with the key of its row
+            let entry = Key::decode(cursor.cursor_key());
+            let key = Key::encode(&entry[entry.len() - key_columns..]);
+            if replaced.binary_search(&key).is_err() {
+                return true;
             }
+            cursor.cursor_advance();
+        }
+
+        false
+    }
+
+
+    /// Pages an insert may allocate: the depth of the table and of each of its indexes.
+    fn pages_needed(&mut self) -> io::Result<usize> {
+        let mut pages = self.tree_depth()?;
+        for i in 0..self.entry.indexes.len() {
+            pages += self.index_tree(i).tree_depth()?;
         }
 
+        Ok(pages)
+    }
+
+
+    /// Checks the row against the schema and the size limits, and returns its encoded key.
+    pub(crate) fn check_row(&self, row: &Row) -> Result<Vec<u8>, ExecuteResult> {
+        if row.values.len() != self.entry.schema.num_columns() || self.entry.schema.type_check(&row.values).is_err() {
+            return Err(ExecuteResult::ExecuteTypeMismatch);
+        }
+
+        if let Err(column) = self.entry.schema.check_not_null(&row.values) {
+            return Err(ExecuteResult::ExecuteNotNullViolation(column.name.clone()));
+        }
+
+        if row.serialized_size() > ROW_SIZE {
+            return Err(ExecuteResult::ExecuteRowTooLarge);
+        }
+
+        let key = Key::encode(&self.entry.schema.key_values(&row.values));
+        if key.len() > KEY_SIZE {
+            return Err(ExecuteResult::ExecuteKeyTooLarge);
+        }
+
+        Ok(key)
+    }
+
+
+    /// Whether a row with exactly this key is stored.
+    fn contains_key(&mut self, key: &[u8]) -> bool {
+        let (page_num, cell_num) = Cursor::table_find_position(self, key);
+        let node = self.pager.get_page(page_num).expect("Failed to retrieve page");
+
+        cell_num < LeafNode::leaf_node_num_cells(node) as usize
+            && self.key_comparator.compare(LeafNode::leaf_node_key(node, cell_num), key) == Ordering::Equal
+    }
+
+
+    /// Deletes the row with the key, and its entries in every index.
+    pub(crate) fn delete_row(&mut self, key: &[u8]) -> io::Result<()> {
+        let row = Row::deserialize_row(Cursor::table_find(self, key).cursor_value())?;
+        self.delete_index_rows(&row)?;
+        self.delete_key(key)
+    }
+
+
+    fn delete_key(&mut self, key: &[u8]) -> io::Result<()> {
+        let (page_num, cell_num) = Cursor::table_find_position(self, key);
         let mut cursor = Cursor {
             table: self,
-            page_num: cursor_page_num,
-            cell_num: cursor_cell_num,
+            page_num,
+            cell_num,
             end_of_table: false,
         };
 
-        if let Err(e) = LeafNode::leaf_node_insert(&mut cursor, row_to_insert.id, row_to_insert) {
-            eprintln!("Failed to insert row: {:?}", e);
-            return ExecuteResult::ExecuteFailed;
+        LeafNode::leaf_node_delete(&mut cursor)
+    }
+
+
+    /// Writes the row over the row with the same key, replacing the index entries
+    /// whose indexed columns changed. The row is not checked.
+    pub(crate) fn update_row(&mut self, key: &[u8], row: &Row) -> ExecuteResult {
+        let (page_num, cell_num) = Cursor::table_find_position(self, key);
+        let node = self.pager.get_page(page_num).expect("Failed to retrieve page");
+        let old_row = match Row::deserialize_row(LeafNode::leaf_node_value(node, cell_num)) {
+            Ok(old_row) => old_row,
+            Err(e) => {
+                eprintln!("Failed to update row: {:?}", e);
+                return ExecuteResult::ExecuteFailed;
+            }
+        };
+        row.serialize_row(LeafNode::leaf_node_value(node, cell_num));
+
+        let old_index_rows = self.index_rows(&old_row);
+        for (i, index_row) in self.index_rows(row).iter().enumerate() {
+            if *index_row == old_index_rows[i] {
+                continue;
+            }
+            let mut index = self.index_tree(i);
+            if let Err(e) = index.delete_key(&Key::encode(&old_index_rows[i].values)) {
+                eprintln!("Failed to update index: {:?}", e);
+                return ExecuteResult::ExecuteFailed;
+            }
+            match index.insert_row(index_row) {
+                ExecuteResult::ExecuteSuccess => (),
+                result => return result,
+            }
         }
 
         ExecuteResult::ExecuteSuccess
     }
 
 
-    pub fn insert_row_str(&mut self, id: u32, username: &str, email: &str) -> ExecuteResult {
-        let username_bytes = username.as_bytes();
-        let email_bytes = email.as_bytes();
+    /// Checks the rows an update writes in place of the rows with the given keys, before
+    /// the first one is written, and returns whether each moves to another key. Rows
+    /// keeping their key are written over, the others are all deleted before they are
+    /// inserted again, so a new row may take a key or unique value that is free or held
+    /// by any replaced row, but not one taken by another new row. The file must also
+    /// have room for every page the writes may allocate.
+    pub(crate) fn check_replacements(&mut self, replacements: &[(&[u8], Row)]) -> Result<Vec<bool>, ExecuteResult> {
+        let mut new_keys = Vec::with_capacity(replacements.len());
+        for (_, row) in replacements {
+            new_keys.push(self.check_row(row)?);
+        }
 
-        if username_bytes.len() > USERNAME_SIZE || email_bytes.len() > EMAIL_SIZE {
-            return ExecuteResult::ExecuteFailed;
+        let comparator = self.key_comparator;
+        let moved: Vec<bool> = replacements.iter().zip(&new_keys)
+            .map(|((key, _), new_key)| comparator.compare(key, new_key) != Ordering::Equal)
+            .collect();
+        let num_moved = moved.iter().filter(|&&moved| moved).count();
+
+        // A moved row is inserted again, while one written over may replace its index entries
+        let mut index_pages = 0;
+        for i in 0..self.entry.indexes.len() {
+            index_pages += self.index_tree(i).tree_depth().map_err(|_| ExecuteResult::ExecuteFailed)?;
+        }
+        let insert_pages = self.pages_needed().map_err(|_| ExecuteResult::ExecuteFailed)?;
+        let pages = num_moved * insert_pages + (replacements.len() - num_moved) * index_pages;
+        if self.pager.num_pages + pages >= TABLE_MAX_PAGES {
+            return Err(ExecuteResult::ExecuteTableFull);
         }
 
-        let row = Row::new(id, username, email);
+        let mut replaced: Vec<Vec<u8>> = replacements.iter().map(|(key, _)| key.to_vec()).collect();
+        replaced.sort();
+        let new_rows: Vec<&Row> = replacements.iter().map(|(_, row)| row).collect();
+        self.check_unique(&new_rows, &replaced)?;
+
+        replaced.sort_by(|a, b| comparator.compare(a, b));
+        new_keys.sort_by(|a, b| comparator.compare(a, b));
+        for (i, new_key) in new_keys.iter().enumerate() {
+            let repeated = i > 0 && comparator.compare(&new_keys[i - 1], new_key) == Ordering::Equal;
+            let vacated = replaced.binary_search_by(|key| comparator.compare(key, new_key)).is_ok();
+            if repeated || (!vacated && self.contains_key(new_key)) {
+                return Err(ExecuteResult::ExecuteDuplicateKey);
+            }
+        }
 
-        self.insert_row(&row)
+        Ok(moved)
     }
 
 
-    // pub fn print_all(&mut self) -> ExecuteResult {
-    //     let mut row = Row::default();
-    //     for i in 0..self.pager.num_pages {
-    //         Row::deserialize_row_existing_ref(self.row_slot(i), &mut row);
-    //         row.print_row();
-    //     }
-    //
-    //     ExecuteResult::ExecuteSuccess
-    // }
+    /// One above the largest key, read from the rightmost leaf, or from every leaf when
+    /// keys are not ordered bytewise. AUTOINCREMENT tables also stay above every rowid
+    /// handed out before. Once the largest rowid is taken, other tables reuse the
+    /// smallest unused positive one. Returns `None` once rowids run out.
+    pub fn next_rowid(&mut self) -> Option<i64> {
+        let max_key = if self.key_comparator.is_bytewise() {
+            let mut cursor = Cursor::table_end(self);
+            if cursor.cell_num == 0 {
+                0
+            } else {
+                cursor.cell_num -= 1;
+                match Key::decode(cursor.cursor_key()).first() {
+                    Some(Value::Integer(key)) => *key,
+                    _ => return None,
+                }
+            }
+        } else {
+            let mut max_key = 0;
+            let mut cursor = Cursor::table_start(self);
+            while !cursor.end_of_table {
+                match Key::decode(cursor.cursor_key()).first() {
+                    Some(Value::Integer(key)) => max_key = max_key.max(*key),
+                    _ => return None,
+                }
+                cursor.cursor_advance();
+            }
+            max_key
+        };
+
+        let high_water_mark = if self.entry.schema.autoincrement { max_key.max(self.entry.sequence) } else { max_key };
+        match high_water_mark.checked_add(1) {
+            None if !self.entry.schema.autoincrement => self.unused_rowid(),
+            next => next,
+        }
+    }
 
 
-    pub fn print_all_cursor(&mut self) -> ExecuteResult {
+    /// The smallest positive rowid no row has, found by reading every key.
+    fn unused_rowid(&mut self) -> Option<i64> {
+        let mut rowids = Vec::new();
         let mut cursor = Cursor::table_start(self);
-        let mut row = Row::default();
-
-        while !&cursor.end_of_table {
-            Row::deserialize_row_existing_ref(cursor.cursor_value(), &mut row);
-            row.print_row();
+        while !cursor.end_of_table {
+            if let Some(&Value::Integer(rowid)) = Key::decode(cursor.cursor_key()).first() {
+                rowids.push(rowid);
+            }
             cursor.cursor_advance();
         }
+        rowids.sort_unstable();
+
+        let mut candidate = 1;
+        for rowid in rowids {
+            match rowid.cmp(&candidate) {
+                Ordering::Less => (),
+                Ordering::Equal => candidate = candidate.checked_add(1)?,
+                Ordering::Greater => break,
+            }
+        }
+        Some(candidate)
+    }
 
-        ExecuteResult::ExecuteSuccess
+
+    /// Returns the largest key stored under the node.
+    pub fn get_node_max_key(&mut self, page_num: usize) -> io::Result<Vec<u8>> {
+        let node = self.pager.get_page(page_num)?;
+
+        match LeafNode::get_node_type(node) {
+            NodeType::NodeLeaf => {
+                let num_cells = LeafNode::leaf_node_num_cells(node) as usize;
+                Ok(LeafNode::leaf_node_key(node, num_cells - 1).to_vec())
+            }
+            NodeType::NodeInternal => {
+                let right_child = InternalNode::internal_node_right_child(node);
+                self.get_node_max_key(right_child as usize)
+            }
+        }
+    }
+
+
+    /// Number of levels in the tree, 1 when the root is a leaf.
+    pub fn tree_depth(&mut self) -> io::Result<usize> {
+        let mut depth = 1;
+        let mut page_num = self.entry.root_page_num;
+
+        loop {
+            let node = self.pager.get_page(page_num)?;
+            match LeafNode::get_node_type(node) {
+                NodeType::NodeLeaf => return Ok(depth),
+                NodeType::NodeInternal => {
+                    page_num = InternalNode::internal_node_child(node, 0) as usize;
+                    depth += 1;
+                }
+            }
+        }
     }
 
 
-    // pub fn get_row(&self, row_num: usize) -> Option<Row> {
-    //     if row_num >= self.pager.num_pages {
-    //         return None
-    //     }
-    //
-    //     let page_num = row_num / ROWS_PER_PAGE;
-    //     let row_offset = row_num % ROWS_PER_PAGE;
-    //
-    //     if let Some(page) = self.pager.pages.get(page_num).and_then(|page| page.as_ref()) {
-    //         let row_start = row_offset * ROW_SIZE;
-    //         let row_end = row_start + ROW_SIZE;
-    //
-    //         return if row_end <= page.len() {
-    //             let row_data = &page[row_start..row_end];
-    //             Some(Row::deserialize_row(row_data))
-    //         } else {
-    //             None
-    //         }
-    //     }
-    //
-    //     None
-    // }
-
-
-    // fn row_slot(&mut self, row_num: usize) -> &mut [u8] {
-    //     let page_num = row_num / ROWS_PER_PAGE;
-    //     let row_offset = row_num % ROWS_PER_PAGE;
-    //     let byte_offset = row_offset * ROW_SIZE;
-    //
-    //     let page = self.pager.get_page(page_num).unwrap();
-    //
-    //     &mut page[byte_offset..byte_offset + ROW_SIZE]
-    // }
-}
\ No newline at end of file
+    pub fn print_tree(&mut self, page_num: usize, indentation_level: usize) -> io::Result<()> {
+        let indent = "  ".repeat(indentation_level);
+        let node = self.pager.get_page(page_num)?;
+
+        match LeafNode::get_node_type(node) {
+            NodeType::NodeLeaf => {
+                let num_cells = LeafNode::leaf_node_num_cells(node) as usize;
+                println!("{}- leaf (size {})", indent, num_cells);
+                for i in 0..num_cells {
+                    println!("{}  - {}", indent, Key::format(LeafNode::leaf_node_key(node, i)));
+                }
+            }
+            NodeType::NodeInternal => {
+                let num_keys = InternalNode::internal_node_num_keys(node) as usize;
+                println!("{}- internal (size {})", indent, num_keys);
+                for i in 0..num_keys {
+                    let node = self.pager.get_page(page_num)?;
+                    let child = InternalNode::internal_node_child(node, i);
+                    let key = Key::format(InternalNode::internal_node_key(node, i));
+                    self.print_tree(child as usize, indentation_level + 1)?;
+                    println!("{}  - key {}", indent, key);
+                }
+
+                let node = self.pager.get_page(page_num)?;
+                let right_child = InternalNode::internal_node_right_child(node);
+                self.print_tree(right_child as usize, indentation_level + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /// Inserts into a table with the default `users` schema. Other tables are better
+    /// filled through [`Database::prepare`](crate::database::Database::prepare).
+    pub fn insert_row_str(&mut self, id: i64, username: &str, email: &str) -> ExecuteResult {
+        let row = Row::new(vec![
+            Value::Integer(id),
+            Value::Text(username.to_string()),
+            Value::Text(email.to_string()),
+        ]);
+
+        self.insert_row(&row)
+    }
+
+
+    /// Counts the rows from the `num_cells` headers of the leaves, without decoding any row.
+    pub fn count_rows(&mut self) -> io::Result<usize> {
+        let mut page_num = Cursor::table_start(self).page_num;
+        let mut count = 0;
+
+        loop {
+            let node = self.pager.get_page(page_num)?;
+            count += LeafNode::leaf_node_num_cells(node) as usize;
+
+            match LeafNode::leaf_node_next_leaf(node) {
+                0 => return Ok(count),
+                next_leaf => page_num = next_leaf as usize,
+            }
+        }
+    }
+}
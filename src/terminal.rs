@@ -0,0 +1,293 @@
+use std::io;
+
+
+/// The terminal switched out of line mode: input is read a byte at a time without
+/// echo, and Ctrl-C, Ctrl-Z and Ctrl-S reach the program as keys. Output is still
+/// processed, so `\n` starts a new line. The previous mode is restored when this is
+/// dropped, and on Unix also when the process exits without dropping it.
+///
+/// Raw mode is set through termios on Linux, Android, macOS and the BSDs, and through
+/// the console API on Windows, where the console also has to understand escape
+/// sequences. Other platforms have no raw mode.
+pub struct RawMode {
+    saved: sys::Saved,
+}
+
+
+impl RawMode {
+    /// Switches stdin out of line mode. Fails if it is not a terminal that supports it.
+    pub fn enter() -> io::Result<Self> {
+        sys::enter().map(|saved| RawMode { saved })
+    }
+}
+
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        sys::restore(&self.saved);
+    }
+}
+
+
+/// Waits up to `timeout_ms` for input on stdin, and returns whether there is some.
+pub fn input_ready(timeout_ms: u32) -> bool {
+    sys::input_ready(timeout_ms)
+}
+
+
+#[cfg(any(
+    all(
+        any(target_os = "linux", target_os = "android"),
+        any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64", target_arch = "riscv64"),
+    ),
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+))]
+mod sys {
+    use std::io;
+    use std::os::raw::{c_int, c_short};
+    use std::sync::OnceLock;
+
+    // Flag values differ on other Linux architectures, which have no raw mode
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mod layout {
+        pub type Flag = std::os::raw::c_uint;
+        pub type NFds = std::os::raw::c_ulong;
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        pub struct Termios {
+            pub c_iflag: Flag,
+            pub c_oflag: Flag,
+            pub c_cflag: Flag,
+            pub c_lflag: Flag,
+            c_line: u8,
+            pub c_cc: [u8; 32],
+            c_ispeed: Flag,
+            c_ospeed: Flag,
+        }
+
+        pub const ISIG: Flag = 0o1;
+        pub const ICANON: Flag = 0o2;
+        pub const ECHO: Flag = 0o10;
+        pub const IEXTEN: Flag = 0o100000;
+        pub const IXON: Flag = 0o2000;
+        pub const VTIME: usize = 5;
+        pub const VMIN: usize = 6;
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    mod layout {
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        pub type Flag = std::os::raw::c_ulong;
+        #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+        pub type Flag = std::os::raw::c_uint;
+        pub type NFds = std::os::raw::c_uint;
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        pub struct Termios {
+            pub c_iflag: Flag,
+            pub c_oflag: Flag,
+            pub c_cflag: Flag,
+            pub c_lflag: Flag,
+            pub c_cc: [u8; 20],
+            c_ispeed: Flag,
+            c_ospeed: Flag,
+        }
+
+        pub const ISIG: Flag = 0x80;
+        pub const ICANON: Flag = 0x100;
+        pub const ECHO: Flag = 0x8;
+        pub const IEXTEN: Flag = 0x400;
+        pub const IXON: Flag = 0x200;
+        pub const VMIN: usize = 16;
+        pub const VTIME: usize = 17;
+    }
+
+    use layout::*;
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+    }
+
+    extern "C" {
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, action: c_int, termios: *const Termios) -> c_int;
+        fn poll(fds: *mut PollFd, count: NFds, timeout: c_int) -> c_int;
+        fn atexit(callback: extern "C" fn()) -> c_int;
+    }
+
+    const STDIN: c_int = 0;
+    const TCSANOW: c_int = 0;
+    const POLLIN: c_short = 1;
+
+    pub type Saved = Termios;
+
+    /// The mode before raw mode was first entered, for `restore_at_exit`.
+    static ORIGINAL: OnceLock<Termios> = OnceLock::new();
+
+
+    pub fn enter() -> io::Result<Termios> {
+        // SAFETY: Termios only holds integers, so all zeros is a valid value, and
+        // tcgetattr writes no more than the struct of the C library.
+        let mut saved: Termios = unsafe { std::mem::zeroed() };
+        if unsafe { tcgetattr(STDIN, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = saved;
+        raw.c_lflag &= !(ICANON | ECHO | ISIG | IEXTEN);
+        raw.c_iflag &= !IXON;
+        raw.c_cc[VMIN] = 1;
+        raw.c_cc[VTIME] = 0;
+        // SAFETY: raw is a valid termios read from the terminal.
+        if unsafe { tcsetattr(STDIN, TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if ORIGINAL.set(saved).is_ok() {
+            // SAFETY: the callback only calls tcsetattr.
+            unsafe { atexit(restore_at_exit) };
+        }
+        Ok(saved)
+    }
+
+
+    pub fn restore(saved: &Termios) {
+        // SAFETY: saved was read from the terminal by tcgetattr.
+        unsafe { tcsetattr(STDIN, TCSANOW, saved) };
+    }
+
+
+    /// Restores the terminal when the process calls `exit` while still in raw mode.
+    extern "C" fn restore_at_exit() {
+        if let Some(original) = ORIGINAL.get() {
+            restore(original);
+        }
+    }
+
+
+    pub fn input_ready(timeout_ms: u32) -> bool {
+        let mut fd = PollFd { fd: STDIN, events: POLLIN, revents: 0 };
+        let timeout = c_int::try_from(timeout_ms).unwrap_or(c_int::MAX);
+        // SAFETY: fd is a single valid pollfd.
+        unsafe { poll(&mut fd, 1, timeout) > 0 }
+    }
+}
+
+
+#[cfg(windows)]
+mod sys {
+    use std::io;
+    use std::os::raw::c_void;
+
+    type Handle = *mut c_void;
+
+    extern "system" {
+        fn GetStdHandle(which: u32) -> Handle;
+        fn GetConsoleMode(console: Handle, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: Handle, mode: u32) -> i32;
+        fn WaitForSingleObject(handle: Handle, timeout_ms: u32) -> u32;
+    }
+
+    const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const ENABLE_PROCESSED_INPUT: u32 = 0x1;
+    const ENABLE_LINE_INPUT: u32 = 0x2;
+    const ENABLE_ECHO_INPUT: u32 = 0x4;
+    const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x200;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x4;
+    const WAIT_OBJECT_0: u32 = 0;
+
+    /// Modes of the console input and output.
+    pub type Saved = (u32, u32);
+
+
+    fn mode(console: Handle) -> io::Result<u32> {
+        let mut mode = 0;
+        // SAFETY: mode is a valid u32 to write to.
+        match unsafe { GetConsoleMode(console, &mut mode) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(mode),
+        }
+    }
+
+
+    fn set_mode(console: Handle, mode: u32) -> io::Result<()> {
+        // SAFETY: SetConsoleMode only reads its arguments.
+        match unsafe { SetConsoleMode(console, mode) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+
+    pub fn enter() -> io::Result<Saved> {
+        // SAFETY: GetStdHandle has no preconditions.
+        let (input, output) = unsafe { (GetStdHandle(STD_INPUT_HANDLE), GetStdHandle(STD_OUTPUT_HANDLE)) };
+        let saved = (mode(input)?, mode(output)?);
+
+        set_mode(output, saved.1 | ENABLE_VIRTUAL_TERMINAL_PROCESSING)?;
+        let raw = (saved.0 & !(ENABLE_PROCESSED_INPUT | ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT)) | ENABLE_VIRTUAL_TERMINAL_INPUT;
+        if let Err(e) = set_mode(input, raw) {
+            let _ = set_mode(output, saved.1);
+            return Err(e);
+        }
+        Ok(saved)
+    }
+
+
+    pub fn restore(saved: &Saved) {
+        // SAFETY: GetStdHandle has no preconditions.
+        let (input, output) = unsafe { (GetStdHandle(STD_INPUT_HANDLE), GetStdHandle(STD_OUTPUT_HANDLE)) };
+        let _ = set_mode(input, saved.0);
+        let _ = set_mode(output, saved.1);
+    }
+
+
+    pub fn input_ready(timeout_ms: u32) -> bool {
+        // SAFETY: the handle is the console input, which can be waited on.
+        unsafe { WaitForSingleObject(GetStdHandle(STD_INPUT_HANDLE), timeout_ms) == WAIT_OBJECT_0 }
+    }
+}
+
+
+#[cfg(not(any(
+    all(
+        any(target_os = "linux", target_os = "android"),
+        any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64", target_arch = "riscv64"),
+    ),
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+    windows,
+)))]
+mod sys {
+    use std::io;
+
+    pub type Saved = ();
+
+
+    pub fn enter() -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "no raw mode on this platform"))
+    }
+
+
+    pub fn restore(_: &()) {}
+
+
+    pub fn input_ready(_: u32) -> bool {
+        true
+    }
+}
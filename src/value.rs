@@ -0,0 +1,282 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, ErrorKind};
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+    Blob,
+    Boolean,
+}
+
+
+impl ColumnType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "INTEGER" | "INT" => Some(ColumnType::Integer),
+            "REAL" | "FLOAT" | "DOUBLE" => Some(ColumnType::Real),
+            "TEXT" | "VARCHAR" => Some(ColumnType::Text),
+            "BLOB" => Some(ColumnType::Blob),
+            "BOOLEAN" | "BOOL" => Some(ColumnType::Boolean),
+            _ => None,
+        }
+    }
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+            ColumnType::Blob => "BLOB",
+            ColumnType::Boolean => "BOOLEAN",
+        }
+    }
+}
+
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+    Boolean(bool),
+}
+
+
+impl Value {
+    /// Record encoding: every value is a one byte type tag followed by its payload.
+    /// Integers and reals are 8 bytes little endian, booleans a single byte,
+    /// text and blobs a u16 length followed by the raw bytes. NULL is a bare tag.
+    const TAG_NULL: u8 = 0;
+    const TAG_INTEGER: u8 = 1;
+    const TAG_REAL: u8 = 2;
+    const TAG_TEXT: u8 = 3;
+    const TAG_BLOB: u8 = 4;
+    const TAG_BOOLEAN: u8 = 5;
+    const TAG_SIZE: usize = std::mem::size_of::<u8>();
+    const LENGTH_SIZE: usize = std::mem::size_of::<u16>();
+
+
+    /// The type of the value, or `None` for NULL which belongs to every type.
+    pub fn column_type(&self) -> Option<ColumnType> {
+        match self {
+            Value::Null => None,
+            Value::Integer(_) => Some(ColumnType::Integer),
+            Value::Real(_) => Some(ColumnType::Real),
+            Value::Text(_) => Some(ColumnType::Text),
+            Value::Blob(_) => Some(ColumnType::Blob),
+            Value::Boolean(_) => Some(ColumnType::Boolean),
+        }
+    }
+
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+
+    /// Parses a literal as the given column type. Returns `None` if the literal
+    /// is not a valid value of that type. `null` is accepted for every type.
+    pub fn parse_literal(literal: &str, column_type: ColumnType) -> Option<Self> {
+        if literal.eq_ignore_ascii_case("null") {
+            return Some(Value::Null);
+        }
+
+        match column_type {
+            ColumnType::Integer => literal.parse::<i64>().ok().map(Value::Integer),
+            ColumnType::Real => literal.parse::<f64>().ok().map(Value::Real),
+            ColumnType::Text => Some(Value::Text(literal.to_string())),
+            ColumnType::Blob => Self::parse_blob_literal(literal).map(Value::Blob),
+            ColumnType::Boolean => match literal.to_ascii_lowercase().as_str() {
+                "true" | "1" => Some(Value::Boolean(true)),
+                "false" | "0" => Some(Value::Boolean(false)),
+                _ => None,
+            },
+        }
+    }
+
+
+    /// Converts the value to the given column type where no information is lost:
+    /// integers widen to reals, and 0 and 1 become booleans. NULL stays NULL, and -0.0
+    /// becomes 0.0 so that equal reals are stored and sought alike.
+    pub fn coerce(self, column_type: ColumnType) -> Option<Self> {
+        match (self, column_type) {
+            (Value::Null, _) => Some(Value::Null),
+            (Value::Integer(i), ColumnType::Real) => Some(Value::Real(i as f64)),
+            (Value::Real(0.0), ColumnType::Real) => Some(Value::Real(0.0)),
+            (Value::Integer(i @ (0 | 1)), ColumnType::Boolean) => Some(Value::Boolean(i == 1)),
+            (value, column_type) if value.column_type() == Some(column_type) => Some(value),
+            _ => None,
+        }
+    }
+
+
+    /// Orders values of comparable types. Integers, reals and booleans (as 0 and 1)
+    /// are all numbers, text and blobs only compare with their own type. Returns
+    /// `None` for NULL and for values that cannot be compared.
+    pub fn partial_compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Blob(a), Value::Blob(b)) => Some(a.cmp(b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (a, b) => a.as_number()?.partial_cmp(&b.as_number()?),
+        }
+    }
+
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Real(r) => Some(*r),
+            Value::Boolean(b) => Some(*b as u8 as f64),
+            _ => None,
+        }
+    }
+
+
+    /// Blob literals are written as `x'0A1B'`.
+    fn parse_blob_literal(literal: &str) -> Option<Vec<u8>> {
+        let hex = literal
+            .strip_prefix("x'")
+            .or_else(|| literal.strip_prefix("X'"))?
+            .strip_suffix('\'')?;
+
+        Self::parse_hex(hex)
+    }
+
+
+    /// Decodes the hex digits of a blob literal.
+    pub(crate) fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+
+
+    pub fn serialized_size(&self) -> usize {
+        Self::TAG_SIZE + match self {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) => 8,
+            Value::Boolean(_) => 1,
+            Value::Text(text) => Self::LENGTH_SIZE + text.len(),
+            Value::Blob(bytes) => Self::LENGTH_SIZE + bytes.len(),
+        }
+    }
+
+
+    /// Writes the value to the start of `destination` and returns the number of bytes written.
+    pub fn serialize(&self, destination: &mut [u8]) -> usize {
+        let (tag, payload_size) = match self {
+            Value::Null => (Self::TAG_NULL, 0),
+            Value::Integer(i) => {
+                destination[Self::TAG_SIZE..Self::TAG_SIZE + 8].copy_from_slice(&i.to_le_bytes());
+                (Self::TAG_INTEGER, 8)
+            }
+            Value::Real(r) => {
+                destination[Self::TAG_SIZE..Self::TAG_SIZE + 8].copy_from_slice(&r.to_le_bytes());
+                (Self::TAG_REAL, 8)
+            }
+            Value::Boolean(b) => {
+                destination[Self::TAG_SIZE] = *b as u8;
+                (Self::TAG_BOOLEAN, 1)
+            }
+            Value::Text(text) => (Self::TAG_TEXT, Self::serialize_bytes(text.as_bytes(), destination)),
+            Value::Blob(bytes) => (Self::TAG_BLOB, Self::serialize_bytes(bytes, destination)),
+        };
+
+        destination[0] = tag;
+        Self::TAG_SIZE + payload_size
+    }
+
+
+    fn serialize_bytes(bytes: &[u8], destination: &mut [u8]) -> usize {
+        let length_offset = Self::TAG_SIZE;
+        let data_offset = length_offset + Self::LENGTH_SIZE;
+
+        let length = u16::try_from(bytes.len()).expect("Value is too long to serialize");
+        destination[length_offset..data_offset].copy_from_slice(&length.to_le_bytes());
+        destination[data_offset..data_offset + bytes.len()].copy_from_slice(bytes);
+        Self::LENGTH_SIZE + bytes.len()
+    }
+
+
+    /// Reads a value from the start of `source` and returns it together with the
+    /// number of bytes consumed. Fails with `InvalidData` if the bytes are not a value.
+    pub fn deserialize(source: &[u8]) -> io::Result<(Self, usize)> {
+        let (&tag, payload) = source.split_first().ok_or_else(Self::truncated)?;
+
+        let (value, payload_size) = match tag {
+            Self::TAG_NULL => (Value::Null, 0),
+            Self::TAG_INTEGER => (Value::Integer(i64::from_le_bytes(Self::deserialize_fixed(payload)?)), 8),
+            Self::TAG_REAL => (Value::Real(f64::from_le_bytes(Self::deserialize_fixed(payload)?)), 8),
+            Self::TAG_BOOLEAN => (Value::Boolean(Self::deserialize_fixed::<1>(payload)?[0] != 0), 1),
+            Self::TAG_TEXT => {
+                let bytes = Self::deserialize_bytes(payload)?;
+                let size = Self::LENGTH_SIZE + bytes.len();
+                (Value::Text(String::from_utf8_lossy(bytes).into_owned()), size)
+            }
+            Self::TAG_BLOB => {
+                let bytes = Self::deserialize_bytes(payload)?;
+                (Value::Blob(bytes.to_vec()), Self::LENGTH_SIZE + bytes.len())
+            }
+            tag => return Err(io::Error::new(ErrorKind::InvalidData, format!("Corrupt record: unknown value tag {}", tag))),
+        };
+
+        Ok((value, Self::TAG_SIZE + payload_size))
+    }
+
+
+    fn deserialize_fixed<const N: usize>(payload: &[u8]) -> io::Result<[u8; N]> {
+        payload.get(..N).map(|bytes| bytes.try_into().unwrap()).ok_or_else(Self::truncated)
+    }
+
+
+    fn deserialize_bytes(payload: &[u8]) -> io::Result<&[u8]> {
+        let length = u16::from_le_bytes(Self::deserialize_fixed(payload)?) as usize;
+        payload.get(Self::LENGTH_SIZE..Self::LENGTH_SIZE + length).ok_or_else(Self::truncated)
+    }
+
+
+    fn truncated() -> io::Error {
+        io::Error::new(ErrorKind::InvalidData, "Corrupt record: value runs past the end")
+    }
+}
+
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("NULL"),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Real(r) => write!(f, "{:?}", r),
+            Value::Text(text) => f.write_str(text),
+            Value::Blob(bytes) => {
+                f.write_str("x'")?;
+                for byte in bytes {
+                    write!(f, "{:02X}", byte)?;
+                }
+                f.write_str("'")
+            }
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
@@ -0,0 +1,867 @@
+use std::cmp::Ordering;
+use std::fmt;
+use crate::aggregate::{Aggregation, HashAggregator};
+use crate::ast::BinaryOperator;
+use crate::cursor::Cursor;
+use crate::data_consts::{ROW_SIZE, SORT_MEMORY_LIMIT};
+use crate::database::Database;
+use crate::expression::Expression;
+use crate::key::Key;
+use crate::schema::Schema;
+use crate::sorter::{SortedRows, Sorter};
+use crate::table::{ExecuteResult, Row, Table};
+use crate::prepare;
+use crate::value::{ColumnType, Value};
+
+
+/// One step of a program. Instructions read and write numbered registers, and jump by
+/// instruction address. Cursors are numbered too, and are opened by `OpenRead` on a
+/// table or `OpenIndex` on an index.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    /// Opens a cursor on the table.
+    OpenRead { cursor: usize, table: String },
+    /// Opens a cursor on the table that may also change its rows.
+    OpenWrite { cursor: usize, table: String },
+    /// Opens a cursor on the index, whose rows are the index entries.
+    OpenIndex { cursor: usize, index: String },
+    /// Moves the cursor to the first row of its table, or jumps if the table is empty.
+    Rewind { cursor: usize, if_empty: usize },
+    /// Moves the cursor to the next row and jumps, unless it was on the last row.
+    Next { cursor: usize, if_more: usize },
+    /// Moves the cursor to the row whose primary key is in the register, or jumps if
+    /// there is no such row.
+    SeekKey { cursor: usize, key: usize, if_not_found: usize },
+    /// Moves the cursor to the first row or entry whose first key column is at least the
+    /// value in the register, or jumps if there is none.
+    SeekGe { cursor: usize, key: usize, if_empty: usize },
+    /// Moves the cursor to the table row the entry under the index cursor refers to.
+    SeekRow { cursor: usize, index_cursor: usize },
+    /// Reads a column of the row under the cursor into the register.
+    Column { cursor: usize, column: usize, target: usize },
+    /// Stores the number of rows in the cursor's table into the register.
+    Count { cursor: usize, target: usize },
+    /// Sets `count` registers from `target` on to NULL.
+    Null { target: usize, count: usize },
+    Value { value: Value, target: usize },
+    Copy { source: usize, target: usize },
+    /// Evaluates the expression against the row held in the registers from `row` on.
+    Eval { expression: Expression, row: usize, target: usize },
+    /// Compares two registers and jumps unless the comparison is true.
+    Compare { operator: BinaryOperator, left: usize, right: usize, if_false: usize },
+    /// Jumps if the register is true.
+    If { register: usize, target: usize },
+    /// Jumps unless the register is true, so also when it is NULL.
+    IfNot { register: usize, target: usize },
+    Goto { target: usize },
+    /// Adds the row held in `count` registers from `start` on to the aggregation.
+    AggStep { start: usize, count: usize },
+    /// Finishes the aggregation and moves to the first group, or jumps if there is none.
+    AggFinal { if_empty: usize },
+    /// Reads the current group row into the registers from `target` on.
+    GroupRow { target: usize },
+    /// Moves to the next group and jumps, unless it was the last one.
+    GroupNext { if_more: usize },
+    /// Adds the row in `count` registers from `row` on to the sort, ordered by the
+    /// `num_keys` registers from `keys` on.
+    SorterInsert { keys: usize, num_keys: usize, row: usize, count: usize },
+    /// Sorts the rows and moves to the first one, or jumps if there is none.
+    SorterSort { if_empty: usize },
+    /// Reads the current sorted row into the registers from `target` on.
+    SorterRow { target: usize },
+    /// Moves to the next sorted row and jumps, unless it was the last one.
+    SorterNext { if_more: usize },
+    /// Jumps while rows are being skipped by OFFSET.
+    Offset { target: usize },
+    /// Counts the row just emitted, and jumps once LIMIT rows have been.
+    Limit { target: usize },
+    /// Emits the `count` registers from `start` on as a result row.
+    ResultRow { start: usize, count: usize },
+    /// Creates the table, with a unique index for each of the `unique` column lists.
+    CreateTable { table: String, schema: Schema, unique: Vec<Vec<usize>> },
+    CreateIndex { index: String, table: String, columns: Vec<usize>, unique: bool },
+    /// Converts the value in the register to the column type, failing if it cannot be
+    /// converted without loss.
+    Cast { register: usize, column_type: ColumnType },
+    /// Jumps if the register is not NULL.
+    NotNull { register: usize, target: usize },
+    /// Stores the next free rowid of the cursor's table into the register.
+    NewRowid { cursor: usize, target: usize },
+    /// Encodes the `count` registers from `start` on as a record, stored into the
+    /// register as a blob.
+    MakeRecord { start: usize, count: usize, target: usize },
+    /// Inserts the record in the register into the cursor's table, with its index entries.
+    Insert { cursor: usize, record: usize },
+    /// Deletes the row under the cursor, with its index entries.
+    Delete { cursor: usize },
+    /// Writes the record in the register over the row under the cursor, whose key it
+    /// keeps, replacing the index entries of the columns that changed.
+    UpdateRow { cursor: usize, record: usize },
+    /// Adds the key of the row under the cursor to the row set, with the record in the
+    /// register if given.
+    RowSetAdd { cursor: usize, record: Option<usize> },
+    /// Checks that the records of the row set can replace the rows of their keys in the
+    /// cursor's table: each must fit the table, and may only take a key or a unique
+    /// value that is free or held by a replaced row. The file must have room for the
+    /// pages writing them may allocate.
+    RowSetCheck { cursor: usize },
+    /// Jumps if the record of the current entry keeps the key of its row, so the row
+    /// is written over rather than deleted and inserted again.
+    RowSetStays { target: usize },
+    /// Moves to the first entry of the row set, or jumps if it is empty.
+    RowSetRewind { if_empty: usize },
+    /// Moves the cursor to the row of the current entry's key.
+    RowSetSeek { cursor: usize },
+    /// Reads the record of the current entry into the register.
+    RowSetRecord { target: usize },
+    /// Moves to the next entry and jumps, unless it was the last one.
+    RowSetNext { if_more: usize },
+    Halt,
+}
+
+
+impl Instruction {
+    /// Points the jump of the instruction to `address`. Instructions that do not jump
+    /// are left as they are.
+    pub fn set_jump(&mut self, address: usize) {
+        match self {
+            Instruction::Rewind { if_empty: target, .. }
+            | Instruction::Next { if_more: target, .. }
+            | Instruction::SeekKey { if_not_found: target, .. }
+            | Instruction::SeekGe { if_empty: target, .. }
+            | Instruction::Compare { if_false: target, .. }
+            | Instruction::If { target, .. }
+            | Instruction::IfNot { target, .. }
+            | Instruction::Goto { target }
+            | Instruction::NotNull { target, .. }
+            | Instruction::AggFinal { if_empty: target }
+            | Instruction::GroupNext { if_more: target }
+            | Instruction::SorterSort { if_empty: target }
+            | Instruction::SorterNext { if_more: target }
+            | Instruction::Offset { target }
+            | Instruction::Limit { target }
+            | Instruction::RowSetStays { target }
+            | Instruction::RowSetRewind { if_empty: target }
+            | Instruction::RowSetNext { if_more: target } => *target = address,
+            _ => (),
+        }
+    }
+
+
+    /// Writes column indexes like `#0,#2`.
+    fn column_list(columns: &[usize]) -> String {
+        let columns: Vec<String> = columns.iter().map(|column| format!("#{}", column)).collect();
+        columns.join(",")
+    }
+}
+
+
+/// Writes the instruction as its name followed by its operands, like `Rewind cursor=0 if_empty=9`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, operands) = match self {
+            Instruction::OpenRead { cursor, table } => ("OpenRead", format!("cursor={} table={}", cursor, table)),
+            Instruction::OpenWrite { cursor, table } => ("OpenWrite", format!("cursor={} table={}", cursor, table)),
+            Instruction::OpenIndex { cursor, index } => ("OpenIndex", format!("cursor={} index={}", cursor, index)),
+            Instruction::Rewind { cursor, if_empty } => ("Rewind", format!("cursor={} if_empty={}", cursor, if_empty)),
+            Instruction::Next { cursor, if_more } => ("Next", format!("cursor={} if_more={}", cursor, if_more)),
+            Instruction::SeekKey { cursor, key, if_not_found } => {
+                ("SeekKey", format!("cursor={} key=r{} if_not_found={}", cursor, key, if_not_found))
+            }
+            Instruction::SeekGe { cursor, key, if_empty } => {
+                ("SeekGe", format!("cursor={} key=r{} if_empty={}", cursor, key, if_empty))
+            }
+            Instruction::SeekRow { cursor, index_cursor } => {
+                ("SeekRow", format!("cursor={} index_cursor={}", cursor, index_cursor))
+            }
+            Instruction::Column { cursor, column, target } => {
+                ("Column", format!("cursor={} column={} target=r{}", cursor, column, target))
+            }
+            Instruction::Count { cursor, target } => ("Count", format!("cursor={} target=r{}", cursor, target)),
+            Instruction::Null { target, count } => ("Null", format!("target=r{} count={}", target, count)),
+            Instruction::Value { value: Value::Text(text), target } => {
+                ("Value", format!("value='{}' target=r{}", text.replace('\'', "''"), target))
+            }
+            Instruction::Value { value, target } => ("Value", format!("value={} target=r{}", value, target)),
+            Instruction::Copy { source, target } => ("Copy", format!("source=r{} target=r{}", source, target)),
+            Instruction::Eval { expression, row, target } => {
+                ("Eval", format!("row=r{} target=r{} expression={}", row, target, expression))
+            }
+            Instruction::Compare { operator, left, right, if_false } => {
+                ("Compare", format!("r{} {} r{} if_false={}", left, operator, right, if_false))
+            }
+            Instruction::If { register, target } => ("If", format!("register=r{} target={}", register, target)),
+            Instruction::IfNot { register, target } => ("IfNot", format!("register=r{} target={}", register, target)),
+            Instruction::Goto { target } => ("Goto", format!("target={}", target)),
+            Instruction::AggStep { start, count } => ("AggStep", format!("start=r{} count={}", start, count)),
+            Instruction::AggFinal { if_empty } => ("AggFinal", format!("if_empty={}", if_empty)),
+            Instruction::GroupRow { target } => ("GroupRow", format!("target=r{}", target)),
+            Instruction::GroupNext { if_more } => ("GroupNext", format!("if_more={}", if_more)),
+            Instruction::SorterInsert { keys, num_keys, row, count } => {
+                ("SorterInsert", format!("keys=r{} num_keys={} row=r{} count={}", keys, num_keys, row, count))
+            }
+            Instruction::SorterSort { if_empty } => ("SorterSort", format!("if_empty={}", if_empty)),
+            Instruction::SorterRow { target } => ("SorterRow", format!("target=r{}", target)),
+            Instruction::SorterNext { if_more } => ("SorterNext", format!("if_more={}", if_more)),
+            Instruction::Offset { target } => ("Offset", format!("target={}", target)),
+            Instruction::Limit { target } => ("Limit", format!("target={}", target)),
+            Instruction::ResultRow { start, count } => ("ResultRow", format!("start=r{} count={}", start, count)),
+            Instruction::CreateTable { table, schema, unique } => {
+                let unique: Vec<String> = unique.iter().map(|columns| Self::column_list(columns)).collect();
+                let unique = if unique.is_empty() { String::new() } else { format!(" unique={}", unique.join(";")) };
+                ("CreateTable", format!("table={} columns={}{}", table, schema.num_columns(), unique))
+            }
+            Instruction::CreateIndex { index, table, columns, unique } => {
+                let columns = Self::column_list(columns);
+                ("CreateIndex", format!("index={} table={} columns={} unique={}", index, table, columns, unique))
+            }
+            Instruction::Cast { register, column_type } => {
+                ("Cast", format!("register=r{} type={}", register, column_type))
+            }
+            Instruction::NotNull { register, target } => ("NotNull", format!("register=r{} target={}", register, target)),
+            Instruction::NewRowid { cursor, target } => ("NewRowid", format!("cursor={} target=r{}", cursor, target)),
+            Instruction::MakeRecord { start, count, target } => {
+                ("MakeRecord", format!("start=r{} count={} target=r{}", start, count, target))
+            }
+            Instruction::Insert { cursor, record } => ("Insert", format!("cursor={} record=r{}", cursor, record)),
+            Instruction::Delete { cursor } => ("Delete", format!("cursor={}", cursor)),
+            Instruction::UpdateRow { cursor, record } => ("UpdateRow", format!("cursor={} record=r{}", cursor, record)),
+            Instruction::RowSetAdd { cursor, record: Some(record) } => {
+                ("RowSetAdd", format!("cursor={} record=r{}", cursor, record))
+            }
+            Instruction::RowSetAdd { cursor, record: None } => ("RowSetAdd", format!("cursor={}", cursor)),
+            Instruction::RowSetCheck { cursor } => ("RowSetCheck", format!("cursor={}", cursor)),
+            Instruction::RowSetStays { target } => ("RowSetStays", format!("target={}", target)),
+            Instruction::RowSetRewind { if_empty } => ("RowSetRewind", format!("if_empty={}", if_empty)),
+            Instruction::RowSetSeek { cursor } => ("RowSetSeek", format!("cursor={}", cursor)),
+            Instruction::RowSetRecord { target } => ("RowSetRecord", format!("target=r{}", target)),
+            Instruction::RowSetNext { if_more } => ("RowSetNext", format!("if_more={}", if_more)),
+            Instruction::Halt => ("Halt", String::new()),
+        };
+
+        if operands.is_empty() {
+            return f.write_str(name);
+        }
+        write!(f, "{:<13}{}", name, operands)
+    }
+}
+
+
+/// A compiled statement, run by [`Program::execute`].
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub num_registers: usize,
+    pub num_cursors: usize,
+    /// GROUP BY and aggregates that `AggStep` adds rows to.
+    pub aggregation: Option<Aggregation>,
+    /// Direction of every sort key `SorterInsert` is given, `true` for descending.
+    pub sort_order: Vec<bool>,
+    /// LIMIT and OFFSET of a select, evaluated when the program starts.
+    pub limit: Option<Expression>,
+    pub offset: Option<Expression>,
+    /// Number of parameters, whose values are loaded into the registers from
+    /// `parameters` on before the program runs.
+    pub num_parameters: usize,
+    pub parameters: usize,
+    /// What the seek of each cursor is bounded by, like `id=?`, for the plan. Empty for
+    /// cursors that scan.
+    pub constraints: Vec<String>,
+}
+
+
+impl Program {
+    /// Runs the program against the database, passing every result row to `emit`.
+    pub fn execute(&self, database: &mut Database, emit: &mut dyn FnMut(&Row)) -> ExecuteResult {
+        self.execute_with(database, &[], emit)
+    }
+
+
+    /// Runs the program with the values of its parameters, parameter `i` being
+    /// `parameters[i - 1]`. Parameters without a value are NULL.
+    pub fn execute_with(&self, database: &mut Database, parameters: &[Value], emit: &mut dyn FnMut(&Row)) -> ExecuteResult {
+        let mut machine = Machine::new(self, parameters);
+
+        match machine.run(database, emit) {
+            Ok(()) => ExecuteResult::ExecuteSuccess,
+            Err(result) => result,
+        }
+    }
+}
+
+
+impl Program {
+    /// Describes how the program reads its tables, one line per step. Nested loops
+    /// are indented below the loop they run in. A table opened for writing is read for
+    /// an UPDATE when its new rows are checked as a set, and for a DELETE otherwise.
+    pub fn plan(&self) -> Vec<String> {
+        let mut tables = vec![""; self.num_cursors];
+        let mut indexes = vec![false; self.num_cursors];
+        let mut written = vec![false; self.num_cursors];
+        let mut read = vec![false; self.num_cursors];
+        let mut plan = Vec::new();
+        let mut aggregates = false;
+        let mut sorts = false;
+
+        let updates = self.instructions.iter().any(|instruction| matches!(instruction, Instruction::RowSetCheck { .. }));
+        let purpose = |written: bool| match (written, updates) {
+            (false, _) => "",
+            (true, true) => " FOR UPDATE",
+            (true, false) => " FOR DELETE",
+        };
+
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::OpenRead { cursor, table } => tables[*cursor] = table,
+                Instruction::OpenWrite { cursor, table } => {
+                    tables[*cursor] = table;
+                    written[*cursor] = true;
+                }
+                Instruction::OpenIndex { cursor, index } => {
+                    tables[*cursor] = index;
+                    indexes[*cursor] = true;
+                }
+                Instruction::Rewind { cursor, .. } => {
+                    read[*cursor] = true;
+                    plan.push(format!("{}SCAN {}{}", "  ".repeat(*cursor), tables[*cursor], purpose(written[*cursor])));
+                }
+                Instruction::SeekKey { cursor, .. } | Instruction::SeekGe { cursor, .. } if !indexes[*cursor] => {
+                    read[*cursor] = true;
+                    let indentation = "  ".repeat(*cursor);
+                    let (table, constraint) = (tables[*cursor], &self.constraints[*cursor]);
+                    plan.push(format!("{}SEARCH {} USING PRIMARY KEY ({}){}", indentation, table, constraint, purpose(written[*cursor])));
+                }
+                Instruction::SeekRow { cursor, index_cursor } => {
+                    read[*cursor] = true;
+                    let indentation = "  ".repeat(*cursor);
+                    let (table, index, constraint) = (tables[*cursor], tables[*index_cursor], &self.constraints[*cursor]);
+                    plan.push(format!("{}SEARCH {} USING INDEX {} ({}){}", indentation, table, index, constraint, purpose(written[*cursor])));
+                }
+                Instruction::Count { cursor, .. } => {
+                    plan.push(format!("COUNT ROWS OF {} FROM LEAF HEADERS", tables[*cursor]));
+                }
+                Instruction::AggStep { .. } if !aggregates => {
+                    aggregates = true;
+                    plan.push("USE HASH AGGREGATION".to_string());
+                }
+                Instruction::SorterInsert { .. } if !sorts => {
+                    sorts = true;
+                    plan.push("USE EXTERNAL SORT FOR ORDER BY".to_string());
+                }
+                Instruction::CreateTable { table, .. } => plan.push(format!("CREATE TABLE {}", table)),
+                Instruction::CreateIndex { index, table, .. } => plan.push(format!("CREATE INDEX {} ON {}", index, table)),
+                // The rows an update inserts again were read by the loop already shown
+                Instruction::Insert { cursor, .. } if !read[*cursor] => plan.push(format!("INSERT INTO {}", tables[*cursor])),
+                _ => (),
+            }
+        }
+
+        plan
+    }
+}
+
+
+/// A cursor of a running program. The position is kept between instructions, while the
+/// pager may be used for other tables, and the row under it is decoded once.
+struct MachineCursor {
+    table: String,
+    /// Whether `table` names an index.
+    index: bool,
+    page_num: usize,
+    cell_num: usize,
+    end_of_table: bool,
+    row: Option<Row>,
+}
+
+
+impl MachineCursor {
+    fn new(table: &str, index: bool) -> Self {
+        MachineCursor {
+            table: table.to_string(),
+            index,
+            page_num: 0,
+            cell_num: 0,
+            end_of_table: true,
+            row: None,
+        }
+    }
+
+
+    fn move_to(&mut self, cursor: &Cursor) {
+        self.page_num = cursor.page_num;
+        self.cell_num = cursor.cell_num;
+        self.end_of_table = cursor.end_of_table;
+        self.row = None;
+    }
+
+
+    fn cursor<'a, 'b>(&self, table: &'a mut Table<'b>) -> Cursor<'a, 'b> {
+        Cursor {
+            table,
+            page_num: self.page_num,
+            cell_num: self.cell_num,
+            end_of_table: self.end_of_table,
+        }
+    }
+}
+
+
+/// State of a running program.
+struct Machine<'a> {
+    program: &'a Program,
+    registers: Vec<Value>,
+    cursors: Vec<Option<MachineCursor>>,
+    aggregator: Option<HashAggregator<'a>>,
+    groups: std::vec::IntoIter<Row>,
+    group: Option<Row>,
+    sorter: Option<Sorter>,
+    sorted: Option<SortedRows>,
+    sorted_row: Option<Row>,
+    offset: usize,
+    remaining: Option<usize>,
+    /// The rows an update or delete changes, and the entry being read.
+    row_set: Vec<RowSetEntry>,
+    row_set_entry: usize,
+}
+
+
+/// A row an update or delete changes.
+struct RowSetEntry {
+    key: Vec<u8>,
+    /// The new record of the row, for an update.
+    record: Option<Vec<u8>>,
+    /// Whether the new record has another key, found by `RowSetCheck`.
+    moved: bool,
+}
+
+
+impl<'a> Machine<'a> {
+    fn new(program: &'a Program, parameters: &[Value]) -> Self {
+        let sorter = (!program.sort_order.is_empty())
+            .then(|| Sorter::new(program.sort_order.clone(), SORT_MEMORY_LIMIT));
+
+        let mut registers = vec![Value::Null; program.num_registers];
+        let count = parameters.len().min(program.num_parameters);
+        registers[program.parameters..program.parameters + count].clone_from_slice(&parameters[..count]);
+
+        Machine {
+            program,
+            registers,
+            cursors: (0..program.num_cursors).map(|_| None).collect(),
+            aggregator: program.aggregation.as_ref().map(HashAggregator::new),
+            groups: Vec::new().into_iter(),
+            group: None,
+            sorter,
+            sorted: None,
+            sorted_row: None,
+            offset: 0,
+            remaining: None,
+            row_set: Vec::new(),
+            row_set_entry: 0,
+        }
+    }
+
+
+    /// The registers holding the values of the parameters.
+    fn parameters<'r>(registers: &'r [Value], program: &Program) -> &'r [Value] {
+        &registers[program.parameters..program.parameters + program.num_parameters]
+    }
+
+
+    fn run(&mut self, database: &mut Database, emit: &mut dyn FnMut(&Row)) -> Result<(), ExecuteResult> {
+        let parameters = Self::parameters(&self.registers, self.program);
+        let count = |expression: &Option<Expression>, clause| {
+            expression.as_ref()
+                .map(|expression| prepare::evaluate_count(expression, parameters, clause))
+                .transpose()
+                .map_err(ExecuteResult::ExecuteExpressionError)
+        };
+        self.remaining = count(&self.program.limit, "LIMIT")?;
+        self.offset = count(&self.program.offset, "OFFSET")?.unwrap_or(0);
+
+        let mut pc = 0;
+
+        while let Some(instruction) = self.program.instructions.get(pc) {
+            pc += 1;
+
+            match instruction {
+                Instruction::OpenRead { cursor, table } => {
+                    if database.catalog.find(table).is_none() {
+                        return Err(ExecuteResult::ExecuteFailed);
+                    }
+                    self.cursors[*cursor] = Some(MachineCursor::new(table, false));
+                }
+                Instruction::OpenWrite { cursor, table } => {
+                    if database.catalog.find(table).is_none() {
+                        return Err(ExecuteResult::ExecuteFailed);
+                    }
+                    self.cursors[*cursor] = Some(MachineCursor::new(table, false));
+                }
+                Instruction::OpenIndex { cursor, index } => {
+                    if database.catalog.find_index(index).is_none() {
+                        return Err(ExecuteResult::ExecuteFailed);
+                    }
+                    self.cursors[*cursor] = Some(MachineCursor::new(index, true));
+                }
+                Instruction::Rewind { cursor, if_empty } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    state.move_to(&Cursor::table_start(&mut table));
+                    if state.end_of_table {
+                        pc = *if_empty;
+                    }
+                }
+                Instruction::Next { cursor, if_more } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    if state.end_of_table {
+                        continue;
+                    }
+                    let mut table = Self::open_cursor(database, state)?;
+                    let mut advanced = state.cursor(&mut table);
+                    advanced.cursor_advance();
+                    state.move_to(&advanced);
+                    if !state.end_of_table {
+                        pc = *if_more;
+                    }
+                }
+                Instruction::SeekKey { cursor, key, if_not_found } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    if !Self::seek(state, &mut table, &self.registers[*key]) {
+                        pc = *if_not_found;
+                    }
+                }
+                Instruction::SeekGe { cursor, key, if_empty } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    state.move_to(&Cursor::table_seek(&mut table, &self.registers[*key]));
+                    if state.end_of_table {
+                        pc = *if_empty;
+                    }
+                }
+                Instruction::SeekRow { cursor, index_cursor } => {
+                    let state = Self::cursor(&mut self.cursors, *index_cursor)?;
+                    let mut index = Self::open_cursor(database, state)?;
+                    let entry = Key::decode(state.cursor(&mut index).cursor_key());
+
+                    // The entry ends with the primary key of the row
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    let key_columns = table.entry.schema.primary_key.len();
+                    let key = Key::encode(&entry[entry.len() - key_columns..]);
+                    let comparator = table.key_comparator;
+                    let mut found = Cursor::table_find(&mut table, &key);
+                    if found.end_of_table || comparator.compare(found.cursor_key(), &key) != Ordering::Equal {
+                        eprintln!("Index entry refers to a missing row of {}.", state.table);
+                        return Err(ExecuteResult::ExecuteFailed);
+                    }
+                    state.move_to(&found);
+                }
+                Instruction::Column { cursor, column, target } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    if state.row.is_none() {
+                        let mut table = Self::open_cursor(database, state)?;
+                        let row = Row::deserialize_row(state.cursor(&mut table).cursor_value()).map_err(|e| Self::failed("read row", e))?;
+                        state.row = Some(row);
+                    }
+                    let values = &state.row.as_ref().unwrap().values;
+                    self.registers[*target] = values.get(*column).cloned().unwrap_or(Value::Null);
+                }
+                Instruction::Count { cursor, target } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let count = Self::open_cursor(database, state)?.count_rows()
+                        .map_err(|e| Self::failed("count rows", e))?;
+                    self.registers[*target] = Value::Integer(count as i64);
+                }
+                Instruction::Null { target, count } => {
+                    self.registers[*target..*target + *count].fill(Value::Null);
+                }
+                Instruction::Value { value, target } => self.registers[*target] = value.clone(),
+                Instruction::Copy { source, target } => self.registers[*target] = self.registers[*source].clone(),
+                Instruction::Eval { expression, row, target } => {
+                    let parameters = Self::parameters(&self.registers, self.program);
+                    self.registers[*target] = expression.evaluate_with(&self.registers[*row..], parameters)
+                        .map_err(ExecuteResult::ExecuteExpressionError)?;
+                }
+                Instruction::Compare { operator, left, right, if_false } => {
+                    let left = self.registers[*left].clone();
+                    let right = self.registers[*right].clone();
+                    let result = Expression::binary(*operator, left, right)
+                        .map_err(ExecuteResult::ExecuteExpressionError)?;
+                    if !Self::is_true(&result)? {
+                        pc = *if_false;
+                    }
+                }
+                Instruction::If { register, target } => {
+                    if Self::is_true(&self.registers[*register])? {
+                        pc = *target;
+                    }
+                }
+                Instruction::IfNot { register, target } => {
+                    if !Self::is_true(&self.registers[*register])? {
+                        pc = *target;
+                    }
+                }
+                Instruction::Goto { target } => pc = *target,
+                Instruction::AggStep { start, count } => {
+                    let aggregator = self.aggregator.as_mut().ok_or(ExecuteResult::ExecuteFailed)?;
+                    let row = Row::new(self.registers[*start..*start + *count].to_vec());
+                    let parameters = Self::parameters(&self.registers, self.program);
+                    aggregator.add(&row, parameters).map_err(ExecuteResult::ExecuteExpressionError)?;
+                }
+                Instruction::AggFinal { if_empty } => {
+                    let aggregator = self.aggregator.take().ok_or(ExecuteResult::ExecuteFailed)?;
+                    self.groups = aggregator.finish().into_iter();
+                    self.group = self.groups.next();
+                    if self.group.is_none() {
+                        pc = *if_empty;
+                    }
+                }
+                Instruction::GroupRow { target } => {
+                    let group = self.group.as_ref().ok_or(ExecuteResult::ExecuteFailed)?;
+                    self.registers[*target..*target + group.values.len()].clone_from_slice(&group.values);
+                }
+                Instruction::GroupNext { if_more } => {
+                    self.group = self.groups.next();
+                    if self.group.is_some() {
+                        pc = *if_more;
+                    }
+                }
+                Instruction::SorterInsert { keys, num_keys, row, count } => {
+                    let sorter = self.sorter.as_mut().ok_or(ExecuteResult::ExecuteFailed)?;
+                    let keys = self.registers[*keys..*keys + *num_keys].to_vec();
+                    let row = Row::new(self.registers[*row..*row + *count].to_vec());
+                    sorter.add(keys, row).map_err(|e| Self::failed("sort rows", e))?;
+                }
+                Instruction::SorterSort { if_empty } => {
+                    let sorter = self.sorter.take().ok_or(ExecuteResult::ExecuteFailed)?;
+                    self.sorted = Some(sorter.finish().map_err(|e| Self::failed("sort rows", e))?);
+                    if !self.next_sorted()? {
+                        pc = *if_empty;
+                    }
+                }
+                Instruction::SorterRow { target } => {
+                    let row = self.sorted_row.as_ref().ok_or(ExecuteResult::ExecuteFailed)?;
+                    self.registers[*target..*target + row.values.len()].clone_from_slice(&row.values);
+                }
+                Instruction::SorterNext { if_more } => {
+                    if self.next_sorted()? {
+                        pc = *if_more;
+                    }
+                }
+                Instruction::Offset { target } => {
+                    if self.offset > 0 {
+                        self.offset -= 1;
+                        pc = *target;
+                    }
+                }
+                Instruction::Limit { target } => {
+                    if let Some(remaining) = &mut self.remaining {
+                        *remaining = remaining.saturating_sub(1);
+                        if *remaining == 0 {
+                            pc = *target;
+                        }
+                    }
+                }
+                Instruction::ResultRow { start, count } => {
+                    emit(&Row::new(self.registers[*start..*start + *count].to_vec()));
+                }
+                Instruction::CreateTable { table, schema, unique } => {
+                    Self::check(database.create_table(table, schema.clone(), unique))?;
+                }
+                Instruction::CreateIndex { index, table, columns, unique } => {
+                    Self::check(database.create_index(index, table, columns.clone(), *unique))?;
+                }
+                Instruction::Cast { register, column_type } => {
+                    let value = std::mem::replace(&mut self.registers[*register], Value::Null);
+                    self.registers[*register] = value.coerce(*column_type).ok_or(ExecuteResult::ExecuteTypeMismatch)?;
+                }
+                Instruction::NotNull { register, target } => {
+                    if !self.registers[*register].is_null() {
+                        pc = *target;
+                    }
+                }
+                Instruction::NewRowid { cursor, target } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let rowid = Self::open_cursor(database, state)?.next_rowid().ok_or(ExecuteResult::ExecuteNoFreeRowid)?;
+                    self.registers[*target] = Value::Integer(rowid);
+                }
+                Instruction::MakeRecord { start, count, target } => {
+                    let row = Row::new(self.registers[*start..*start + *count].to_vec());
+                    // Values made at run time, such as bound parameters, are only checked here
+                    if row.serialized_size() > ROW_SIZE {
+                        return Err(ExecuteResult::ExecuteRowTooLarge);
+                    }
+                    let mut record = vec![0; row.serialized_size()];
+                    row.serialize_row(&mut record);
+                    self.registers[*target] = Value::Blob(record);
+                }
+                Instruction::Insert { cursor, record } => {
+                    let row = Self::record_row(&self.registers[*record])?;
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    Self::check(Self::open_cursor(database, state)?.insert_row(&row))?;
+                    // The insert may have reshaped the leaf the cursor was on
+                    state.end_of_table = true;
+                    state.row = None;
+                }
+                Instruction::Delete { cursor } => {
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    let key = state.cursor(&mut table).cursor_key().to_vec();
+                    table.delete_row(&key).map_err(|e| Self::failed("delete row", e))?;
+                    state.end_of_table = true;
+                    state.row = None;
+                }
+                Instruction::UpdateRow { cursor, record } => {
+                    let row = Self::record_row(&self.registers[*record])?;
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    let key = state.cursor(&mut table).cursor_key().to_vec();
+                    Self::check(table.update_row(&key, &row))?;
+                    state.row = None;
+                }
+                Instruction::RowSetAdd { cursor, record } => {
+                    let record = match record {
+                        Some(record) => Some(Self::record(&self.registers[*record])?.to_vec()),
+                        None => None,
+                    };
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    let key = state.cursor(&mut table).cursor_key().to_vec();
+                    self.row_set.push(RowSetEntry { key, record, moved: false });
+                }
+                Instruction::RowSetCheck { cursor } => {
+                    let mut replacements = Vec::with_capacity(self.row_set.len());
+                    for entry in &self.row_set {
+                        let record = entry.record.as_ref().ok_or(ExecuteResult::ExecuteFailed)?;
+                        let row = Row::deserialize_row(record).map_err(|e| Self::failed("read row", e))?;
+                        replacements.push((entry.key.as_slice(), row));
+                    }
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let moved = Self::open_cursor(database, state)?.check_replacements(&replacements)?;
+                    for (entry, moved) in self.row_set.iter_mut().zip(moved) {
+                        entry.moved = moved;
+                    }
+                }
+                Instruction::RowSetStays { target } => {
+                    let entry = self.row_set.get(self.row_set_entry).ok_or(ExecuteResult::ExecuteFailed)?;
+                    if !entry.moved {
+                        pc = *target;
+                    }
+                }
+                Instruction::RowSetRewind { if_empty } => {
+                    self.row_set_entry = 0;
+                    if self.row_set.is_empty() {
+                        pc = *if_empty;
+                    }
+                }
+                Instruction::RowSetSeek { cursor } => {
+                    let key = &self.row_set.get(self.row_set_entry).ok_or(ExecuteResult::ExecuteFailed)?.key;
+                    let state = Self::cursor(&mut self.cursors, *cursor)?;
+                    let mut table = Self::open_cursor(database, state)?;
+                    let comparator = table.key_comparator;
+                    let mut found = Cursor::table_find(&mut table, key);
+                    if found.end_of_table || comparator.compare(found.cursor_key(), key) != Ordering::Equal {
+                        eprintln!("Row set refers to a missing row of {}.", state.table);
+                        return Err(ExecuteResult::ExecuteFailed);
+                    }
+                    state.move_to(&found);
+                }
+                Instruction::RowSetRecord { target } => {
+                    let entry = self.row_set.get(self.row_set_entry).ok_or(ExecuteResult::ExecuteFailed)?;
+                    let record = entry.record.as_ref().ok_or(ExecuteResult::ExecuteFailed)?;
+                    self.registers[*target] = Value::Blob(record.clone());
+                }
+                Instruction::RowSetNext { if_more } => {
+                    self.row_set_entry += 1;
+                    if self.row_set_entry < self.row_set.len() {
+                        pc = *if_more;
+                    }
+                }
+                Instruction::Halt => break,
+            }
+        }
+
+        Ok(())
+    }
+
+
+    fn cursor(cursors: &mut [Option<MachineCursor>], cursor: usize) -> Result<&mut MachineCursor, ExecuteResult> {
+        cursors.get_mut(cursor).and_then(Option::as_mut).ok_or(ExecuteResult::ExecuteFailed)
+    }
+
+
+    fn open_table<'d>(database: &'d mut Database, name: &str) -> Result<Table<'d>, ExecuteResult> {
+        database.table(name).ok_or(ExecuteResult::ExecuteFailed)
+    }
+
+
+    /// Opens the table or index the cursor reads.
+    fn open_cursor<'d>(database: &'d mut Database, state: &MachineCursor) -> Result<Table<'d>, ExecuteResult> {
+        match state.index {
+            true => database.index(&state.table).ok_or(ExecuteResult::ExecuteFailed),
+            false => Self::open_table(database, &state.table),
+        }
+    }
+
+
+    /// Positions the cursor on the row with the primary key `value`. Returns whether
+    /// there is one.
+    fn seek(state: &mut MachineCursor, table: &mut Table, value: &Value) -> bool {
+        let key_column = &table.entry.schema.columns[table.entry.schema.primary_key[0]];
+        let Some(value) = value.clone().coerce(key_column.column_type).filter(|value| !value.is_null()) else {
+            state.end_of_table = true;
+            return false;
+        };
+
+        let key = Key::encode(&[value]);
+        let comparator = table.key_comparator;
+        let mut cursor = Cursor::table_find(table, &key);
+        let found = !cursor.end_of_table && comparator.compare(cursor.cursor_key(), &key) == Ordering::Equal;
+
+        state.move_to(&cursor);
+        state.end_of_table |= !found;
+        found
+    }
+
+
+    /// The encoded row of a record made by `MakeRecord`.
+    fn record(value: &Value) -> Result<&[u8], ExecuteResult> {
+        match value {
+            Value::Blob(record) => Ok(record),
+            _ => Err(ExecuteResult::ExecuteFailed),
+        }
+    }
+
+
+    fn record_row(value: &Value) -> Result<Row, ExecuteResult> {
+        Row::deserialize_row(Self::record(value)?).map_err(|e| Self::failed("read record", e))
+    }
+
+
+    fn next_sorted(&mut self) -> Result<bool, ExecuteResult> {
+        let sorted = self.sorted.as_mut().ok_or(ExecuteResult::ExecuteFailed)?;
+        self.sorted_row = sorted.next().transpose().map_err(|e| Self::failed("sort rows", e))?;
+        Ok(self.sorted_row.is_some())
+    }
+
+
+    fn is_true(value: &Value) -> Result<bool, ExecuteResult> {
+        let truth = Expression::truth(value).map_err(ExecuteResult::ExecuteExpressionError)?;
+        Ok(truth.unwrap_or(false))
+    }
+
+
+    fn check(result: ExecuteResult) -> Result<(), ExecuteResult> {
+        match result {
+            ExecuteResult::ExecuteSuccess => Ok(()),
+            result => Err(result),
+        }
+    }
+
+
+    fn failed(action: &str, error: std::io::Error) -> ExecuteResult {
+        eprintln!("Failed to {}: {:?}", action, error);
+        ExecuteResult::ExecuteFailed
+    }
+}
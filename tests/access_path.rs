@@ -0,0 +1,91 @@
+mod common;
+
+use common::{open_database, plan, query};
+use crablite::value::Value;
+
+
+fn insert_users(database: &mut crablite::database::Database, count: i64) {
+    for id in 1..=count {
+        query(database, &format!("insert into users values ({}, 'user{}', 'user{}@example.com')", id, id, id));
+    }
+}
+
+
+#[test]
+fn primary_key_equality_searches_the_table() {
+    let (_file, mut database) = open_database();
+    insert_users(&mut database, 20);
+
+    assert_eq!(plan(&mut database, "select * from users where id = 7"), ["SEARCH users USING PRIMARY KEY (id=?)"]);
+    let rows = query(&mut database, "select username from users where id = 7");
+    assert_eq!(rows, [[Value::Text("user7".to_string())]]);
+    assert!(query(&mut database, "select * from users where id = 21").is_empty());
+}
+
+
+#[test]
+fn primary_key_range_searches_the_table() {
+    let (_file, mut database) = open_database();
+    insert_users(&mut database, 20);
+
+    assert_eq!(
+        plan(&mut database, "select id from users where id > 5 and id <= 8"),
+        ["SEARCH users USING PRIMARY KEY (id>? AND id<=?)"],
+    );
+    let ids = query(&mut database, "select id from users where id > 5 and id <= 8");
+    assert_eq!(ids, [[Value::Integer(6)], [Value::Integer(7)], [Value::Integer(8)]]);
+
+    let ids = query(&mut database, "select id from users where 18 < id");
+    assert_eq!(ids, [[Value::Integer(19)], [Value::Integer(20)]]);
+}
+
+
+#[test]
+fn unbounded_columns_are_scanned() {
+    let (_file, mut database) = open_database();
+    insert_users(&mut database, 3);
+
+    assert_eq!(plan(&mut database, "select * from users where username = 'user2'"), ["SCAN users"]);
+}
+
+
+#[test]
+fn index_equality_is_shown_with_its_column() {
+    let (_file, mut database) = open_database();
+    insert_users(&mut database, 5);
+    query(&mut database, "create index users_username on users (username)");
+
+    assert_eq!(
+        plan(&mut database, "select * from users where username = 'user2' and id > 1"),
+        ["SEARCH users USING INDEX users_username (username=?)"],
+    );
+}
+
+
+#[test]
+fn updates_and_deletes_search_like_selects() {
+    let (_file, mut database) = open_database();
+    insert_users(&mut database, 20);
+    query(&mut database, "create index users_username on users (username)");
+
+    assert_eq!(
+        plan(&mut database, "update users set email = 'x' where id = 3"),
+        ["SEARCH users USING PRIMARY KEY (id=?) FOR UPDATE"],
+    );
+    assert_eq!(
+        plan(&mut database, "delete from users where username = 'user4'"),
+        ["SEARCH users USING INDEX users_username (username=?) FOR DELETE"],
+    );
+    assert_eq!(plan(&mut database, "delete from users where email = 'x'"), ["SCAN users FOR DELETE"]);
+
+    query(&mut database, "update users set email = 'x' where id >= 18");
+    query(&mut database, "update users set username = 'renamed' where username = 'user2'");
+    query(&mut database, "delete from users where id < 3 and username = 'renamed'");
+    query(&mut database, "delete from users where username = 'user4'");
+
+    let ids = query(&mut database, "select id from users where email = 'x'");
+    assert_eq!(ids, [[Value::Integer(18)], [Value::Integer(19)], [Value::Integer(20)]]);
+    let ids = query(&mut database, "select id from users where id <= 5");
+    assert_eq!(ids, [[Value::Integer(1)], [Value::Integer(3)], [Value::Integer(5)]]);
+    assert!(query(&mut database, "select * from users where username = 'renamed'").is_empty());
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::{open_database, plan, query, text, TempFile};
+use crablite::database::Database;
+use crablite::value::Value;
+
+
+/// Orders with a NULL customer and a NULL amount.
+fn orders() -> (TempFile, Database) {
+    let (file, mut database) = open_database();
+    query(&mut database, "create table o (id integer primary key, cust text, amount integer)");
+    for values in ["1, 'a', 10", "2, null, 5", "3, 'b', null", "4, 'a', 30", "5, null, 7"] {
+        query(&mut database, &format!("insert into o values ({})", values));
+    }
+    (file, database)
+}
+
+
+/// The rows sorted, since groups come out in no particular order.
+fn sorted(mut rows: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+    rows.sort_by_key(|row| format!("{:?}", row));
+    rows
+}
+
+
+#[test]
+fn nulls_form_one_group_and_are_skipped_by_aggregates() {
+    let (_file, mut database) = orders();
+
+    let rows = query(&mut database, "select cust, count(*), count(amount), sum(amount), min(amount), max(amount), avg(amount) from o group by cust");
+    assert_eq!(sorted(rows), sorted(vec![
+        vec![text("a"), Value::Integer(2), Value::Integer(2), Value::Integer(40), Value::Integer(10), Value::Integer(30), Value::Real(20.0)],
+        vec![Value::Null, Value::Integer(2), Value::Integer(2), Value::Integer(12), Value::Integer(5), Value::Integer(7), Value::Real(6.0)],
+        vec![text("b"), Value::Integer(1), Value::Integer(0), Value::Null, Value::Null, Value::Null, Value::Null],
+    ]));
+}
+
+
+#[test]
+fn having_filters_groups_including_the_null_group() {
+    let (_file, mut database) = orders();
+
+    let rows = query(&mut database, "select cust, sum(amount) from o group by cust having sum(amount) > 10");
+    assert_eq!(sorted(rows), sorted(vec![vec![text("a"), Value::Integer(40)], vec![Value::Null, Value::Integer(12)]]));
+    let rows = query(&mut database, "select cust from o group by cust having count(*) = 1");
+    assert_eq!(rows, [[text("b")]]);
+    assert!(query(&mut database, "select cust from o group by cust having count(*) > 2").is_empty());
+}
+
+
+#[test]
+fn aggregates_without_group_by_return_one_row_even_without_rows() {
+    let (_file, mut database) = orders();
+
+    assert_eq!(query(&mut database, "select count(*) from o"), [[Value::Integer(5)]]);
+    assert_eq!(query(&mut database, "select sum(amount) from o where cust is null"), [[Value::Integer(12)]]);
+    assert_eq!(
+        query(&mut database, "select count(*), sum(amount), avg(amount), min(amount) from o where id > 100"),
+        [[Value::Integer(0), Value::Null, Value::Null, Value::Null]],
+    );
+    assert!(query(&mut database, "select cust, count(*) from o where id > 100 group by cust").is_empty());
+}
+
+
+#[test]
+fn count_star_reads_the_leaf_headers_of_every_leaf() {
+    let (_file, mut database) = open_database();
+    for id in 1..=50 {
+        query(&mut database, &format!("insert into users values ({}, 'u', 'e')", id));
+    }
+    query(&mut database, "delete from users where id > 40");
+
+    assert_eq!(plan(&mut database, "select count(*) from users"), ["COUNT ROWS OF users FROM LEAF HEADERS"]);
+    assert_eq!(query(&mut database, "select count(*) from users"), [[Value::Integer(40)]]);
+    assert_eq!(plan(&mut database, "select count(*) from users where id > 30"), ["SEARCH users USING PRIMARY KEY (id>?)", "USE HASH AGGREGATION"]);
+    assert_eq!(query(&mut database, "select count(*) from users where id > 30"), [[Value::Integer(10)]]);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{execute, open_database, query};
+use crablite::database::Database;
+use crablite::table::ExecuteResult;
+use crablite::value::Value;
+
+
+#[test]
+fn omitted_ids_follow_the_largest_and_autoincrement_never_reuses_them() {
+    let (file, mut database) = open_database();
+    query(&mut database, "create table plain (id integer primary key, name text)");
+    query(&mut database, "create table counted (id integer primary key autoincrement, name text)");
+    for table in ["plain", "counted"] {
+        query(&mut database, &format!("insert into {} (name) values ('a')", table));
+        query(&mut database, &format!("insert into {} values (10, 'b')", table));
+        query(&mut database, &format!("delete from {} where id = 10", table));
+    }
+    // The high-water mark is kept in the catalog
+    database.db_close().unwrap();
+
+    let mut database = Database::db_open(file.path_str()).unwrap();
+    for table in ["plain", "counted"] {
+        query(&mut database, &format!("insert into {} (name) values ('c')", table));
+    }
+    assert_eq!(query(&mut database, "select id from plain"), [[Value::Integer(1)], [Value::Integer(2)]]);
+    assert_eq!(query(&mut database, "select id from counted"), [[Value::Integer(1)], [Value::Integer(11)]]);
+}
+
+#[test]
+fn rowids_past_the_largest_are_reused_only_without_autoincrement() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table plain (id integer primary key, name text)");
+    query(&mut database, "create table counted (id integer primary key autoincrement, name text)");
+
+    for table in ["plain", "counted"] {
+        query(&mut database, &format!("insert into {} values (1, 'first')", table));
+        query(&mut database, &format!("insert into {} values ({}, 'last')", table, i64::MAX));
+    }
+
+    query(&mut database, "insert into plain (name) values ('reused')");
+    assert_eq!(query(&mut database, "select id from plain where name = 'reused'"), [[Value::Integer(2)]]);
+
+    let result = execute(&mut database, "insert into counted (name) values ('none')");
+    assert!(matches!(result, ExecuteResult::ExecuteNoFreeRowid));
+    assert_eq!(result.to_string(), "No free rowid");
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{ids, open_database, query};
+use crablite::cursor::Cursor;
+use crablite::database::Database;
+use crablite::leaf_node::LeafNode;
+use crablite::table::ExecuteResult;
+use crablite::value::Value;
+
+
+const NUM_ROWS: i64 = 3000;
+
+
+/// The ids 0..NUM_ROWS in a scrambled order, so that splits happen all over the tree.
+fn scrambled_ids() -> impl Iterator<Item = i64> {
+    (0..NUM_ROWS).map(|i| i * 1231 % NUM_ROWS)
+}
+
+
+fn fill(database: &mut Database) {
+    let mut table = database.table("users").unwrap();
+    for id in scrambled_ids() {
+        let result = table.insert_row_str(id, &format!("user{}", id), &format!("user{}@example.com", id));
+        assert!(matches!(result, ExecuteResult::ExecuteSuccess), "insert {} failed: {}", id, result);
+    }
+}
+
+
+fn tree_depth(database: &mut Database) -> usize {
+    database.table("users").unwrap().tree_depth().unwrap()
+}
+
+
+#[test]
+fn splits_keep_every_row_in_key_order() {
+    let (_file, mut database) = open_database();
+    fill(&mut database);
+
+    assert!(tree_depth(&mut database) >= 3, "internal nodes should have split too");
+    assert_eq!(database.table("users").unwrap().count_rows().unwrap(), NUM_ROWS as usize);
+    assert_eq!(ids(&mut database, "select id from users"), (0..NUM_ROWS).collect::<Vec<_>>());
+}
+
+
+#[test]
+fn duplicate_keys_are_rejected_after_splits() {
+    let (_file, mut database) = open_database();
+    fill(&mut database);
+
+    let result = database.table("users").unwrap().insert_row_str(NUM_ROWS / 2, "again", "again@example.com");
+    assert!(matches!(result, ExecuteResult::ExecuteDuplicateKey));
+}
+
+
+#[test]
+fn deletes_keep_the_remaining_rows_in_order() {
+    let (_file, mut database) = open_database();
+    fill(&mut database);
+
+    query(&mut database, "delete from users where id % 10 <> 0");
+    assert_eq!(ids(&mut database, "select id from users"), (0..NUM_ROWS).step_by(10).collect::<Vec<_>>());
+    assert_eq!(query(&mut database, "select username from users where id = 2000"), [[Value::Text("user2000".to_string())]]);
+
+    // Rows can still be added in the gaps left behind
+    query(&mut database, "insert into users values (1995, 'back', 'back@example.com')");
+    assert_eq!(query(&mut database, "select id from users where id > 1980 and id < 2010"), [[Value::Integer(1990)], [Value::Integer(1995)], [Value::Integer(2000)]]);
+}
+
+
+#[test]
+fn deleting_every_row_collapses_the_tree_to_a_leaf() {
+    let (_file, mut database) = open_database();
+    fill(&mut database);
+
+    query(&mut database, "delete from users where id <> 1234");
+    assert_eq!(tree_depth(&mut database), 1);
+    assert_eq!(ids(&mut database, "select id from users"), [1234]);
+
+    query(&mut database, "delete from users");
+    assert_eq!(ids(&mut database, "select id from users"), Vec::<i64>::new());
+
+    fill(&mut database);
+    assert_eq!(ids(&mut database, "select id from users"), (0..NUM_ROWS).collect::<Vec<_>>());
+}
+
+
+#[test]
+fn the_tree_reads_back_after_reopening() {
+    let (file, mut database) = open_database();
+    fill(&mut database);
+    query(&mut database, "delete from users where id >= 1000 and id < 2000");
+    database.db_close().unwrap();
+
+    let mut database = Database::db_open(file.path_str()).unwrap();
+    let expected: Vec<i64> = (0..1000).chain(2000..NUM_ROWS).collect();
+    assert_eq!(ids(&mut database, "select id from users"), expected);
+}
+
+
+/// Number of cells in every leaf, from left to right.
+fn leaf_sizes(database: &mut Database) -> Vec<usize> {
+    let mut table = database.table("users").unwrap();
+    let mut page_num = Cursor::table_start(&mut table).page_num;
+    let mut sizes = Vec::new();
+
+    loop {
+        let node = table.pager.get_page(page_num).unwrap();
+        sizes.push(LeafNode::leaf_node_num_cells(node) as usize);
+        match LeafNode::leaf_node_next_leaf(node) {
+            0 => return sizes,
+            next_leaf => page_num = next_leaf as usize,
+        }
+    }
+}
+
+
+#[test]
+fn deletes_merge_and_refill_leaves_left_less_than_half_full() {
+    let (_file, mut database) = open_database();
+    fill(&mut database);
+    let depth = tree_depth(&mut database);
+
+    query(&mut database, "delete from users where id % 5 <> 0");
+    let sizes = leaf_sizes(&mut database);
+    assert_eq!(sizes.iter().sum::<usize>(), NUM_ROWS as usize / 5);
+    assert!(sizes.iter().all(|&size| size >= LeafNode::LEAF_NODE_MAX_CELLS / 2), "{:?}", sizes);
+    assert!(tree_depth(&mut database) < depth, "merged internal nodes should shorten the tree");
+    assert_eq!(ids(&mut database, "select id from users"), (0..NUM_ROWS).step_by(5).collect::<Vec<_>>());
+
+    fill_again(&mut database);
+    assert_eq!(ids(&mut database, "select id from users"), (0..NUM_ROWS).collect::<Vec<_>>());
+}
+
+
+/// Inserts the ids a delete of `id % 5 <> 0` removed.
+fn fill_again(database: &mut Database) {
+    let mut table = database.table("users").unwrap();
+    for id in scrambled_ids().filter(|id| id % 5 != 0) {
+        let result = table.insert_row_str(id, &format!("user{}", id), &format!("user{}@example.com", id));
+        assert!(matches!(result, ExecuteResult::ExecuteSuccess), "insert {} failed: {}", id, result);
+    }
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use common::TempFile;
+
+
+/// Runs the commands with `-c` on the database file, returning the output and the exit code.
+fn run(database: &TempFile, commands: &str) -> (String, i32) {
+    let output = Command::new(env!("CARGO_BIN_EXE_crablite"))
+        .args([database.path_str(), "-c", commands])
+        .output()
+        .unwrap();
+    (String::from_utf8(output.stdout).unwrap(), output.status.code().unwrap())
+}
+
+
+/// Runs the script piped into stdin on the database file, returning the output and the exit code.
+fn run_piped(database: &TempFile, script: &str) -> (String, i32) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crablite"))
+        .arg(database.path_str())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (String::from_utf8(output.stdout).unwrap(), output.status.code().unwrap())
+}
+
+
+#[test]
+fn statements_without_rows_only_report_success_in_every_mode() {
+    for mode in ["tuple", "table", "csv", "json", "line", "markdown"] {
+        let database = TempFile::new("db");
+        let commands = format!(
+            ".mode {}\n.headers on\n\
+             insert into users values (1, 'alice', 'alice@example.com');\n\
+             update users set email = 'a@example.com';\n\
+             create table t (a integer);\n\
+             create index t_a on t (a);\n\
+             delete from users;",
+            mode,
+        );
+
+        let (output, code) = run(&database, &commands);
+        assert_eq!(output, "Executed.\n".repeat(5), "mode {}", mode);
+        assert_eq!(code, 0);
+    }
+}
+
+
+#[test]
+fn an_empty_select_keeps_its_header() {
+    let database = TempFile::new("db");
+
+    let (output, _) = run(&database, ".mode csv\n.headers on\nselect id, email from users;");
+    assert_eq!(output, "id,email\r\n");
+    let (output, _) = run(&database, ".mode json\nselect id, email from users;");
+    assert_eq!(output, "[]\n");
+}
+
+
+#[test]
+fn scripts_that_succeed_exit_with_zero() {
+    let database = TempFile::new("db");
+
+    let (output, code) = run(&database, "insert into users values (1, 'a', 'a@x');\n-- a comment\nselect id from users;");
+    assert_eq!((output.as_str(), code), ("Executed.\n(1)\nExecuted.\n", 0));
+    let (output, code) = run_piped(&database, "select username\nfrom users;\n.exit\nselect 1;");
+    assert_eq!((output.as_str(), code), ("(a)\nExecuted.\n", 0));
+}
+
+
+#[test]
+fn scripts_stop_at_the_first_error_and_exit_with_one() {
+    for script in [
+        "insert into users values (1, 'a', 'a@x');\nselect nothing from users;\ninsert into users values (2, 'b', 'b@x');",
+        "insert into users values (1, 'a', 'a@x');\ninsert into users values (1, 'a', 'a@x');\ninsert into users values (2, 'b', 'b@x');",
+        "insert into users values (1, 'a', 'a@x');\n.unknown\ninsert into users values (2, 'b', 'b@x');",
+    ] {
+        let database = TempFile::new("db");
+
+        let (_, code) = run_piped(&database, script);
+        assert_eq!(code, 1, "{}", script);
+        let (output, code) = run(&database, "select id from users;");
+        assert_eq!((output.as_str(), code), ("(1)\nExecuted.\n", 0), "{}", script);
+    }
+}
+
+
+#[test]
+fn bad_arguments_exit_with_two() {
+    let database = TempFile::new("db");
+    let status = |arguments: &[&str]| Command::new(env!("CARGO_BIN_EXE_crablite"))
+        .arg(database.path_str())
+        .args(arguments)
+        .output()
+        .unwrap()
+        .status
+        .code();
+
+    assert_eq!(status(&["-x"]), Some(2));
+    assert_eq!(status(&["-c"]), Some(2));
+    assert_eq!(status(&["-c", "select 1;", "extra"]), Some(2));
+}
+
+
+#[test]
+fn schema_prints_a_composite_primary_key_as_a_table_constraint() {
+    let database = TempFile::new("db");
+
+    let (output, code) = run(&database, "create table t (a integer, b text not null, c real, primary key (b, a));\n.schema t");
+    assert_eq!(code, 0);
+    assert_eq!(output, "Executed.\nt:\n  a INTEGER\n  b TEXT\n  c REAL\n  PRIMARY KEY (b, a)\n");
+}
+
+
+#[test]
+fn statements_run_once_their_semicolon_is_read() {
+    let database = TempFile::new("db");
+
+    let (output, code) = run_piped(&database, "insert into users\nvalues (1, 'a;b',\n'c'); select username\nfrom users;\n");
+    assert_eq!((output.as_str(), code), ("Executed.\n(a;b)\nExecuted.\n", 0));
+}
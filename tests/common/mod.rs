@@ -0,0 +1,98 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crablite::database::Database;
+use crablite::table::{ExecuteResult, Row};
+use crablite::value::Value;
+
+
+/// A file in the temp directory that is removed when dropped.
+pub struct TempFile {
+    pub path: PathBuf,
+}
+
+
+impl TempFile {
+    pub fn new(extension: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("crablite-test-{}-{}.{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed), extension);
+        let path = env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+
+        TempFile { path }
+    }
+
+
+    pub fn path_str(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+}
+
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+
+/// Opens a new database in a temp file, which is removed with the returned file.
+pub fn open_database() -> (TempFile, Database) {
+    let file = TempFile::new("db");
+    let database = Database::db_open(file.path_str()).unwrap();
+    (file, database)
+}
+
+
+/// Runs the statement and returns its result rows, panicking if it fails.
+pub fn query(database: &mut Database, sql: &str) -> Vec<Vec<Value>> {
+    let statement = database.prepare(sql).unwrap();
+    let mut rows = Vec::new();
+    let result = statement.execute(database, &mut |row: &Row| rows.push(row.values.clone())).unwrap();
+    assert!(matches!(result, ExecuteResult::ExecuteSuccess), "{} failed: {}", sql, result);
+    rows
+}
+
+
+/// The integer first column of every result row, such as the selected ids.
+pub fn ids(database: &mut Database, sql: &str) -> Vec<i64> {
+    query(database, sql).into_iter()
+        .map(|row| match row[0] {
+            Value::Integer(id) => id,
+            ref value => panic!("{} returned {:?}", sql, value),
+        })
+        .collect()
+}
+
+
+/// The plan of the statement, one step per line.
+pub fn plan(database: &mut Database, sql: &str) -> Vec<String> {
+    query(database, &format!("explain {}", sql)).into_iter()
+        .map(|row| row[0].to_string())
+        .collect()
+}
+
+
+/// Runs the statement and returns how it ended, dropping any result rows.
+pub fn execute(database: &mut Database, sql: &str) -> ExecuteResult {
+    let statement = database.prepare(sql).unwrap();
+    statement.execute(database, &mut |_: &Row| ()).unwrap()
+}
+
+
+pub fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+
+/// Opens a new database with users 1 to `count`, named `user<id>` with email `user<id>@x`.
+pub fn users(count: i64) -> (TempFile, Database) {
+    let (file, mut database) = open_database();
+    for id in 1..=count {
+        query(&mut database, &format!("insert into users values ({}, 'user{}', 'user{}@x')", id, id, id));
+    }
+    (file, database)
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{execute, open_database, query};
+use crablite::database::Database;
+use crablite::table::ExecuteResult;
+use crablite::value::Value;
+
+
+#[test]
+fn unique_columns_get_an_index_each() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table t (a integer, b text unique, c text unique)");
+
+    let entry = database.catalog.find("t").unwrap();
+    let names: Vec<&str> = entry.indexes.iter().map(|index| index.name()).collect();
+    assert_eq!(names, ["autoindex_t_1", "autoindex_t_2"]);
+
+    query(&mut database, "insert into t values (1, 'x', 'y')");
+    assert!(matches!(execute(&mut database, "insert into t values (2, 'x', 'z')"), ExecuteResult::ExecuteUniqueViolation(_)));
+}
+
+
+#[test]
+fn a_failed_autoindex_leaves_no_table() {
+    let (file, mut database) = open_database();
+    query(&mut database, "create index autoindex_t_2 on users (email)");
+
+    let result = execute(&mut database, "create table t (a integer, b text unique, c text unique)");
+    assert!(matches!(result, ExecuteResult::ExecuteIndexExists(ref name) if name == "autoindex_t_2"));
+    assert!(database.catalog.find("t").is_none());
+    assert!(database.catalog.find_index("autoindex_t_1").is_none());
+
+    // Nothing of the table was saved either, so it can be created once the name is free
+    database.db_close().unwrap();
+    let mut database = Database::db_open(file.path_str()).unwrap();
+    assert!(database.catalog.find("t").is_none());
+    query(&mut database, "create table t (a integer, b text unique)");
+    query(&mut database, "insert into t values (1, 'x')");
+    assert_eq!(query(&mut database, "select b from t"), [[Value::Text("x".to_string())]]);
+}
+
+
+#[test]
+fn files_of_another_catalog_format_are_rejected() {
+    let (file, mut database) = open_database();
+    database.db_close().unwrap();
+
+    let mut bytes = std::fs::read(file.path_str()).unwrap();
+    bytes[8..12].copy_from_slice(&2u32.to_le_bytes());
+    std::fs::write(file.path_str(), bytes).unwrap();
+
+    let error = Database::db_open(file.path_str()).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "Unsupported file format version 2.");
+}
+
+
+#[test]
+fn unique_indexes_stay_unique_after_reopening() {
+    let (file, mut database) = open_database();
+    query(&mut database, "create unique index users_username on users (username)");
+    query(&mut database, "create index users_email on users (email)");
+    query(&mut database, "insert into users values (1, 'a', 'x')");
+    database.db_close().unwrap();
+
+    let mut database = Database::db_open(file.path_str()).unwrap();
+    let unique: Vec<bool> = database.catalog.find("users").unwrap().indexes.iter().map(|index| index.unique).collect();
+    assert_eq!(unique, [true, false]);
+    assert!(matches!(execute(&mut database, "insert into users values (2, 'a', 'y')"), ExecuteResult::ExecuteUniqueViolation(_)));
+    query(&mut database, "insert into users values (2, 'b', 'x')");
+}
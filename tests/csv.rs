@@ -0,0 +1,90 @@
+mod common;
+
+use common::{open_database, query};
+use crablite::csv::{self, CsvReader};
+use crablite::value::Value;
+
+
+fn records(input: &str) -> Vec<Vec<Option<String>>> {
+    let mut reader = CsvReader::new(input.as_bytes());
+    std::iter::from_fn(|| reader.read_record().unwrap()).collect()
+}
+
+
+fn fields(fields: &[Option<&str>]) -> Vec<Option<String>> {
+    fields.iter().map(|field| field.map(String::from)).collect()
+}
+
+
+#[test]
+fn quoted_fields_hold_commas_quotes_and_line_breaks() {
+    let input = "a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",,\"\"\n";
+
+    assert_eq!(records(input), [
+        fields(&[Some("a"), Some("b,c"), Some("say \"hi\"")]),
+        fields(&[Some("two\nlines"), None, Some("")]),
+    ]);
+}
+
+
+#[test]
+fn records_remember_the_line_they_start_on() {
+    let mut reader = CsvReader::new("a\n\n\"b\nb\"\nc".as_bytes());
+    let mut lines = Vec::new();
+    while let Some(record) = reader.read_record().unwrap() {
+        lines.push((record, reader.record_line()));
+    }
+
+    assert_eq!(lines, [
+        (fields(&[Some("a")]), 1),
+        (fields(&[Some("b\nb")]), 3),
+        (fields(&[Some("c")]), 5),
+    ]);
+}
+
+
+#[test]
+fn an_unterminated_quote_is_an_error() {
+    let mut reader = CsvReader::new("a\n\"b,c\n".as_bytes());
+
+    assert_eq!(reader.read_record().unwrap(), Some(fields(&[Some("a")])));
+    assert!(reader.read_record().is_err());
+}
+
+
+#[test]
+fn written_records_read_back_the_same() {
+    let record = fields(&[Some("plain"), None, Some(""), Some("a,b"), Some("\"q\""), Some("x\r\ny")]);
+    let mut out = Vec::new();
+    csv::write_record(&mut out, &record).unwrap();
+
+    assert_eq!(String::from_utf8(out.clone()).unwrap(), "plain,,\"\",\"a,b\",\"\"\"q\"\"\",\"x\r\ny\"\r\n");
+    assert_eq!(records(&String::from_utf8(out).unwrap()), [record]);
+}
+
+
+#[test]
+fn imports_match_fields_to_the_header_and_report_bad_records() {
+    let (_file, mut database) = open_database();
+    let input = "email,id\na@x,1\nb@x\nc@x,three\n,4\n";
+
+    let report = database.import_csv("users", input.as_bytes()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.errors, [
+        (3, "Expected 2 fields, found 1".to_string()),
+        (4, "Invalid INTEGER value 'three' for column 'id'".to_string()),
+    ]);
+    assert_eq!(query(&mut database, "select id, username, email from users"), [
+        vec![Value::Integer(1), Value::Null, Value::Text("a@x".to_string())],
+        vec![Value::Integer(4), Value::Null, Value::Null],
+    ]);
+}
+
+
+#[test]
+fn a_header_naming_an_unknown_column_fails_the_import() {
+    let (_file, mut database) = open_database();
+
+    assert!(database.import_csv("users", "id,age\n1,20\n".as_bytes()).is_err());
+    assert!(query(&mut database, "select id from users").is_empty());
+}
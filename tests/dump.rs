@@ -0,0 +1,83 @@
+mod common;
+
+use common::{open_database, query};
+use crablite::database::Database;
+use crablite::value::Value;
+
+
+fn dump(database: &mut Database) -> String {
+    let mut out = Vec::new();
+    database.dump(None, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+
+/// Runs the dump line by line, applying `.sequence` lines the way `.read` does.
+fn read(database: &mut Database, dump: &str) {
+    for line in dump.lines() {
+        match line.strip_prefix(".sequence ").and_then(|rest| rest.split_once(' ')) {
+            Some((name, sequence)) => database.set_sequence(name, sequence.parse().unwrap()).unwrap(),
+            None => {
+                query(database, line);
+            }
+        }
+    }
+}
+
+
+#[test]
+fn a_dump_read_into_a_new_database_dumps_the_same() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "insert into users values (1, 'alice', 'alice@example.com')");
+    query(&mut database, "insert into users values (-9223372036854775808, 'min', 'it''s \"quoted\"')");
+    query(&mut database, "create table \"order\" (id integer primary key autoincrement, total real not null, note text unique, data blob)");
+    query(&mut database, "insert into \"order\" values (null, 2.5, null, x'00ff')");
+    query(&mut database, "insert into \"order\" values (null, -0.125, 'rush', null)");
+    query(&mut database, "create index order_total on \"order\" (total)");
+    let first = dump(&mut database);
+    assert!(first.starts_with("CREATE TABLE IF NOT EXISTS users "), "{}", first);
+
+    let (_copy_file, mut copy) = open_database();
+    read(&mut copy, &first);
+    assert_eq!(dump(&mut copy), first);
+
+    let rows = query(&mut copy, "select note from \"order\" where total < 0");
+    assert_eq!(rows, [[Value::Text("rush".to_string())]]);
+    query(&mut copy, "insert into \"order\" (total) values (1.0)");
+    assert_eq!(query(&mut copy, "select max(id) from \"order\""), [[Value::Integer(3)]]);
+}
+
+
+#[test]
+fn create_table_if_not_exists_keeps_the_table() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "insert into users values (1, 'alice', 'alice@example.com')");
+
+    query(&mut database, "create table if not exists users (a integer)");
+    assert_eq!(query(&mut database, "select count(*) from users"), [[Value::Integer(1)]]);
+    query(&mut database, "create table if not exists t (a integer)");
+    assert!(database.catalog.find("t").is_some());
+}
+
+
+#[test]
+fn a_dump_keeps_the_rowids_of_deleted_rows_unused() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table log (id integer primary key autoincrement, message text not null)");
+    for message in ["a", "b", "c"] {
+        query(&mut database, &format!("insert into log (message) values ('{}')", message));
+    }
+    query(&mut database, "delete from log where id > 1");
+    let script = dump(&mut database);
+    assert!(script.ends_with("INSERT INTO log VALUES (1, 'a');\n.sequence log 3\n"), "{}", script);
+
+    let (_copy_file, mut copy) = open_database();
+    read(&mut copy, &script);
+    query(&mut copy, "insert into log (message) values ('d')");
+    assert_eq!(query(&mut copy, "select id from log where message = 'd'"), [[Value::Integer(4)]]);
+
+    // A lower mark is ignored
+    copy.set_sequence("log", 1).unwrap();
+    assert_eq!(dump(&mut copy).lines().last(), Some(".sequence log 4"));
+    assert!(copy.set_sequence("users", 1).is_err());
+}
@@ -0,0 +1,96 @@
+use crablite::formatter::{Formatter, OutputMode};
+use crablite::table::Row;
+use crablite::value::Value;
+
+
+/// Formats two rows, one with a NULL and one with text that needs quoting or escaping.
+fn format(mode: OutputMode, headers: bool) -> String {
+    let rows = [
+        Row::new(vec![Value::Integer(1), Value::Text("alice".to_string()), Value::Null]),
+        Row::new(vec![Value::Integer(22), Value::Text("bob, \"jr\" | sr".to_string()), Value::Real(2.5)]),
+    ];
+    format_rows(mode, headers, &rows)
+}
+
+
+fn format_rows(mode: OutputMode, headers: bool, rows: &[Row]) -> String {
+    let column_names = vec!["id".to_string(), "name".to_string(), "score".to_string()];
+    let mut out = Vec::new();
+    let mut formatter = Formatter::new(&mut out, mode, headers, column_names);
+    for row in rows {
+        formatter.write_row(row).unwrap();
+    }
+    formatter.finish().unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+
+#[test]
+fn tuple_rows_are_one_per_line() {
+    assert_eq!(format(OutputMode::Tuple, false), "(1, alice, NULL)\n(22, bob, \"jr\" | sr, 2.5)\n");
+    assert_eq!(format(OutputMode::Tuple, true), "(id, name, score)\n(1, alice, NULL)\n(22, bob, \"jr\" | sr, 2.5)\n");
+}
+
+
+#[test]
+fn table_columns_are_aligned_with_numbers_on_the_right() {
+    let expected = concat!(
+        "+----+----------------+-------+\n",
+        "| id | name           | score |\n",
+        "+----+----------------+-------+\n",
+        "|  1 | alice          | NULL  |\n",
+        "| 22 | bob, \"jr\" | sr |   2.5 |\n",
+        "+----+----------------+-------+\n",
+    );
+    assert_eq!(format(OutputMode::Table, true), expected);
+
+    let without_header = format(OutputMode::Table, false);
+    assert!(without_header.starts_with("+----+----------------+------+\n|  1 | alice"), "{}", without_header);
+}
+
+
+#[test]
+fn csv_quotes_fields_and_leaves_null_empty() {
+    assert_eq!(format(OutputMode::Csv, false), "1,alice,\r\n22,\"bob, \"\"jr\"\" | sr\",2.5\r\n");
+    assert!(format(OutputMode::Csv, true).starts_with("id,name,score\r\n1,alice,\r\n"));
+}
+
+
+#[test]
+fn json_is_an_array_of_objects() {
+    let expected = "[{\"id\":1,\"name\":\"alice\",\"score\":null},\n{\"id\":22,\"name\":\"bob, \\\"jr\\\" | sr\",\"score\":2.5}]\n";
+
+    assert_eq!(format(OutputMode::Json, false), expected);
+    assert_eq!(format_rows(OutputMode::Json, false, &[]), "[]\n");
+}
+
+
+#[test]
+fn line_mode_writes_a_value_per_line() {
+    let expected = "   id = 1\n name = alice\nscore = NULL\n\n   id = 22\n name = bob, \"jr\" | sr\nscore = 2.5\n";
+
+    assert_eq!(format(OutputMode::Line, false), expected);
+}
+
+
+#[test]
+fn markdown_always_has_a_header_and_escapes_pipes() {
+    let expected = concat!(
+        "| id  | name            | score |\n",
+        "|-----|-----------------|-------|\n",
+        "|   1 | alice           | NULL  |\n",
+        "|  22 | bob, \"jr\" \\| sr |   2.5 |\n",
+    );
+
+    assert_eq!(format(OutputMode::Markdown, false), expected);
+    assert_eq!(format_rows(OutputMode::Markdown, false, &[]), "| id  | name | score |\n|-----|------|-------|\n");
+}
+
+
+#[test]
+fn modes_are_found_by_name() {
+    for mode in OutputMode::ALL {
+        assert_eq!(OutputMode::from_name(&mode.name().to_uppercase()), Some(mode));
+    }
+    assert_eq!(OutputMode::from_name("html"), None);
+}
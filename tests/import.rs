@@ -0,0 +1,78 @@
+mod common;
+
+use common::{open_database, query};
+use crablite::data_consts::PAGER_CACHE_PAGES;
+use crablite::database::Database;
+use crablite::value::Value;
+
+
+fn csv(ids: impl IntoIterator<Item = i64>) -> String {
+    let mut csv = String::from("id,username,email\n");
+    for id in ids {
+        csv.push_str(&format!("{},user{},user{}@example.com\n", id, id, id));
+    }
+    csv
+}
+
+
+#[test]
+fn unsorted_rows_are_loaded_into_an_empty_table() {
+    let (_file, mut database) = open_database();
+    let input = "id,username,email\n3,c,c@x\n1,a,a@x\n3,again,again@x\n2,b,b@x\n";
+
+    let report = database.import_csv("users", input.as_bytes()).unwrap();
+    assert_eq!(report.imported, 3);
+    assert_eq!(report.errors, [(4, "Duplicate key".to_string())]);
+
+    let rows = query(&mut database, "select id, username from users");
+    let expected: Vec<Vec<Value>> = [(1, "a"), (2, "b"), (3, "c")].iter()
+        .map(|&(id, name)| vec![Value::Integer(id), Value::Text(name.to_string())])
+        .collect();
+    assert_eq!(rows, expected);
+}
+
+
+#[test]
+fn rows_are_inserted_into_a_table_with_rows() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "insert into users values (2, 'b', 'b@x')");
+
+    let report = database.import_csv("users", csv([3, 2, 1]).as_bytes()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.errors, [(3, "Duplicate key".to_string())]);
+    assert_eq!(query(&mut database, "select count(*) from users"), [[Value::Integer(3)]]);
+}
+
+
+#[test]
+fn rows_without_a_rowid_follow_the_rows_before_them() {
+    let (_file, mut database) = open_database();
+    let input = "{\"id\": 5, \"username\": \"e\", \"email\": \"e@x\"}\n{\"username\": \"f\", \"email\": \"f@x\"}\n";
+
+    let report = database.import_json("users", input.as_bytes()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(query(&mut database, "select id from users where username = 'f'"), [[Value::Integer(6)]]);
+}
+
+
+#[test]
+fn large_imports_keep_a_bounded_page_cache() {
+    let (file, mut database) = open_database();
+    let count = 60_000;
+
+    let report = database.import_csv("users", csv((1..=count).rev()).as_bytes()).unwrap();
+    assert_eq!(report.imported, count as usize);
+    assert!(report.errors.is_empty());
+    let cached = database.pager.pages.iter().filter(|page| page.is_some()).count();
+    assert!(cached <= PAGER_CACHE_PAGES, "{} pages cached", cached);
+
+    query(&mut database, "insert into users values (0, 'zero', 'zero@x')");
+    database.db_close().unwrap();
+    let mut database = Database::db_open(file.path_str()).unwrap();
+    assert_eq!(query(&mut database, "select count(*), min(id), max(id) from users"), [[
+        Value::Integer(count + 1),
+        Value::Integer(0),
+        Value::Integer(count),
+    ]]);
+    assert_eq!(query(&mut database, "select username from users where id = 4321"), [[Value::Text("user4321".to_string())]]);
+}
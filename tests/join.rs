@@ -0,0 +1,64 @@
+mod common;
+
+use common::{plan, query, text, users, TempFile};
+use crablite::database::Database;
+use crablite::value::Value;
+
+
+/// Users 1 to 3, where user2 has no orders, and an order of a user that does not exist.
+fn users_and_orders() -> (TempFile, Database) {
+    let (file, mut database) = users(3);
+    query(&mut database, "create table orders (id integer primary key, user_id integer, total real)");
+    for values in ["10, 1, 5.0", "11, 1, 7.5", "12, 3, 1.0", "13, 9, 2.0"] {
+        query(&mut database, &format!("insert into orders values ({})", values));
+    }
+    (file, database)
+}
+
+
+#[test]
+fn left_join_pads_rows_without_a_match_with_nulls() {
+    let (_file, mut database) = users_and_orders();
+
+    assert_eq!(query(&mut database, "select u.username, o.id, o.total from users u left join orders o on o.user_id = u.id"), [
+        [text("user1"), Value::Integer(10), Value::Real(5.0)],
+        [text("user1"), Value::Integer(11), Value::Real(7.5)],
+        [text("user2"), Value::Null, Value::Null],
+        [text("user3"), Value::Integer(12), Value::Real(1.0)],
+    ]);
+    // Conditions in ON only decide the match, so rows failing them are still padded
+    assert_eq!(query(&mut database, "select u.username, o.id from users u left join orders o on o.user_id = u.id and o.total > 6"), [
+        [text("user1"), Value::Integer(11)],
+        [text("user2"), Value::Null],
+        [text("user3"), Value::Null],
+    ]);
+    assert_eq!(query(&mut database, "select u.username from users u left join orders o on o.user_id = u.id where o.id is null"), [[text("user2")]]);
+}
+
+
+#[test]
+fn inner_join_drops_rows_without_a_match() {
+    let (_file, mut database) = users_and_orders();
+
+    assert_eq!(query(&mut database, "select u.username, o.id from users u join orders o on o.user_id = u.id"), [
+        [text("user1"), Value::Integer(10)],
+        [text("user1"), Value::Integer(11)],
+        [text("user3"), Value::Integer(12)],
+    ]);
+}
+
+
+#[test]
+fn joins_on_a_primary_key_search_it_for_every_row() {
+    let (_file, mut database) = users_and_orders();
+    let sql = "select o.id, u.username from orders o left join users u on u.id = o.user_id";
+
+    assert_eq!(plan(&mut database, sql), ["SCAN orders", "  SEARCH users USING PRIMARY KEY (id=?)"]);
+    assert_eq!(query(&mut database, sql), [
+        [Value::Integer(10), text("user1")],
+        [Value::Integer(11), text("user1")],
+        [Value::Integer(12), text("user3")],
+        [Value::Integer(13), Value::Null],
+    ]);
+    assert_eq!(plan(&mut database, "select * from users u left join orders o on o.user_id = u.id"), ["SCAN users", "  SCAN orders"]);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{open_database, query};
+use crablite::json::JsonValue;
+use crablite::value::Value;
+
+
+#[test]
+fn values_parse_with_escapes_and_integer_numbers() {
+    let text = r#" {"a": [1, -2.5, 1e3, 9223372036854775808], "b": "tab\tquote\"é😀", "c": {"d": null, "e": true}} "#;
+
+    assert_eq!(JsonValue::parse(text), Ok(JsonValue::Object(vec![
+        ("a".to_string(), JsonValue::Array(vec![
+            JsonValue::Integer(1),
+            JsonValue::Real(-2.5),
+            JsonValue::Real(1000.0),
+            JsonValue::Real(9223372036854775808.0),
+        ])),
+        ("b".to_string(), JsonValue::String("tab\tquote\"é😀".to_string())),
+        ("c".to_string(), JsonValue::Object(vec![
+            ("d".to_string(), JsonValue::Null),
+            ("e".to_string(), JsonValue::Boolean(true)),
+        ])),
+    ])));
+}
+
+
+#[test]
+fn invalid_json_is_rejected_with_its_column() {
+    assert_eq!(JsonValue::parse("{\"a\": 1,}"), Err("Expected '\"' at column 9".to_string()));
+    assert_eq!(JsonValue::parse("[1 2]"), Err("Expected ',' or ']' at column 4".to_string()));
+    assert_eq!(JsonValue::parse("01"), Err("Invalid number '01' at column 1".to_string()));
+    assert_eq!(JsonValue::parse("\"abc"), Err("Unterminated string".to_string()));
+    assert_eq!(JsonValue::parse("{} x"), Err("Unexpected character at column 4".to_string()));
+}
+
+
+#[test]
+fn printed_values_parse_back_the_same() {
+    let value = JsonValue::Object(vec![
+        ("text".to_string(), JsonValue::String("line\nbreak \\ \u{1}".to_string())),
+        ("list".to_string(), JsonValue::Array(vec![JsonValue::Integer(-3), JsonValue::Real(0.5), JsonValue::Null])),
+    ]);
+
+    assert_eq!(JsonValue::parse(&value.to_string()), Ok(value));
+}
+
+
+#[test]
+fn ndjson_imports_match_members_to_columns_and_report_bad_lines() {
+    let (_file, mut database) = open_database();
+    let input = concat!(
+        "{\"id\": 1, \"username\": \"a\", \"email\": \"a@x\"}\n",
+        "\n",
+        "{\"email\": \"b@x\", \"id\": 2}\n",
+        "{\"id\": 3, \"age\": 20}\n",
+        "{\"id\": \"four\"}\n",
+        "[5]\n",
+        "{\"id\": 6,\n",
+    );
+
+    let report = database.import_json("users", input.as_bytes()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.errors, [
+        (4, "No column 'age'".to_string()),
+        (5, "Invalid INTEGER value '\"four\"' for column 'id'".to_string()),
+        (6, "Expected a JSON object".to_string()),
+        (7, "Unexpected end of JSON".to_string()),
+    ]);
+    assert_eq!(query(&mut database, "select id, username, email from users"), [
+        vec![Value::Integer(1), Value::Text("a".to_string()), Value::Text("a@x".to_string())],
+        vec![Value::Integer(2), Value::Null, Value::Text("b@x".to_string())],
+    ]);
+}
+
+
+#[test]
+fn exported_ndjson_imports_into_an_empty_table() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "insert into users values (1, 'a \"quoted\" name', NULL)");
+    query(&mut database, "insert into users values (2, 'b', 'b@x')");
+    let mut ndjson = Vec::new();
+    assert_eq!(database.export_json("users", &mut ndjson).unwrap(), 2);
+
+    let (_other_file, mut other) = open_database();
+    let report = other.import_json("users", ndjson.as_slice()).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(query(&mut other, "select * from users"), query(&mut database, "select * from users"));
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use std::cmp::Ordering;
+use common::{execute, ids, open_database, plan, query, text};
+use crablite::key::{Key, KeyComparator};
+use crablite::table::ExecuteResult;
+use crablite::value::Value;
+
+
+/// Checks that the encoded values sort bytewise in the order given.
+fn assert_encoded_in_order(values: &[Vec<Value>]) {
+    for pair in values.windows(2) {
+        let (a, b) = (Key::encode(&pair[0]), Key::encode(&pair[1]));
+        assert!(a < b, "{:?} should sort before {:?}", pair[0], pair[1]);
+    }
+}
+
+
+#[test]
+fn integers_sort_by_value_across_the_sign() {
+    let values = [i64::MIN, -1_000_000, -256, -1, 0, 1, 255, 256, i64::MAX];
+
+    assert_encoded_in_order(&values.map(|i| vec![Value::Integer(i)]));
+}
+
+
+#[test]
+fn reals_sort_by_value_across_the_sign() {
+    let values = [f64::NEG_INFINITY, -1e300, -2.5, -0.001, 0.0, 1e-300, 0.5, 2.5, 1e300, f64::INFINITY];
+
+    assert_encoded_in_order(&values.map(|r| vec![Value::Real(r)]));
+}
+
+
+#[test]
+fn text_sorts_bytewise_with_prefixes_first() {
+    let values = ["", "\0", "\0\0", "\0a", "a", "a\0", "a\0b", "ab", "b"];
+
+    assert_encoded_in_order(&values.map(|s| vec![Value::Text(s.to_string())]));
+}
+
+
+#[test]
+fn blobs_sort_bytewise_with_prefixes_first() {
+    let values: [&[u8]; 5] = [&[], &[0], &[0, 0xff], &[1], &[0xff, 0]];
+
+    assert_encoded_in_order(&values.map(|b| vec![Value::Blob(b.to_vec())]));
+}
+
+
+#[test]
+fn composite_keys_sort_by_their_first_value_then_the_next() {
+    let values = [
+        vec![Value::Integer(1), text("")],
+        vec![Value::Integer(1), text("a")],
+        vec![Value::Integer(1), text("a"), Value::Integer(-1)],
+        vec![Value::Integer(1), text("a"), Value::Integer(0)],
+        vec![Value::Integer(1), text("b")],
+        vec![Value::Integer(2), text("")],
+    ];
+
+    assert_encoded_in_order(&values);
+}
+
+
+#[test]
+fn null_sorts_first_and_values_round_trip() {
+    let values = vec![
+        Value::Null,
+        Value::Boolean(true),
+        Value::Integer(-7),
+        Value::Real(-0.25),
+        text("a\0b"),
+        Value::Blob(vec![0, 1, 0]),
+    ];
+
+    assert!(Key::encode(&[Value::Null]) < Key::encode(&[Value::Integer(i64::MIN)]));
+    assert_eq!(Key::decode(&Key::encode(&values)), values);
+}
+
+
+/// Orders keys from the largest to the smallest.
+struct ReversedComparator;
+
+
+impl KeyComparator for ReversedComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+
+#[test]
+fn a_reversed_comparator_keeps_ranges_and_rowids_correct() {
+    let (_file, mut database) = open_database();
+    database.set_key_comparator(Box::new(ReversedComparator));
+    for id in [3, 1, 5, 2, 4] {
+        query(&mut database, &format!("insert into users values ({}, 'user{}', 'user{}@x')", id, id, id));
+    }
+
+    assert_eq!(ids(&mut database, "select id from users"), [5, 4, 3, 2, 1]);
+    assert_eq!(plan(&mut database, "select id from users where id > 2 and id <= 4"), ["SCAN users"]);
+    assert_eq!(ids(&mut database, "select id from users where id > 2 and id <= 4"), [4, 3]);
+    assert_eq!(ids(&mut database, "select id from users where id = 1"), [1]);
+
+    // The largest rowid is in the leftmost leaf
+    query(&mut database, "insert into users (username, email) values ('next', 'next@x')");
+    assert_eq!(ids(&mut database, "select id from users where username = 'next'"), [6]);
+
+    query(&mut database, "delete from users where id >= 2 and id < 5");
+    query(&mut database, "update users set email = 'x' where id < 6");
+    assert_eq!(query(&mut database, "select id, email from users where id < 6"), [
+        [Value::Integer(5), text("x")],
+        [Value::Integer(1), text("x")],
+    ]);
+}
+
+
+#[test]
+fn negative_zero_is_the_same_real_key_as_zero() {
+    assert_eq!(Key::encode(&[Value::Real(-0.0)]), Key::encode(&[Value::Real(0.0)]));
+
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table r (k real primary key, v text)");
+    query(&mut database, "insert into r values (0.0, 'zero')");
+    let result = execute(&mut database, "insert into r values (-0.0, 'negative zero')");
+    assert!(matches!(result, ExecuteResult::ExecuteDuplicateKey), "{}", result);
+
+    for sql in ["select v from r where k = 0", "select v from r where k = -0.0"] {
+        assert_eq!(query(&mut database, sql), [[text("zero")]], "{}", sql);
+    }
+}
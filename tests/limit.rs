@@ -0,0 +1,50 @@
+mod common;
+
+use common::{ids, users};
+use crablite::prepare::PrepareResult;
+use crablite::table::{ExecuteResult, Row};
+use crablite::value::Value;
+
+
+#[test]
+fn limit_and_offset_page_through_the_rows() {
+    let (_file, mut database) = users(50);
+
+    assert_eq!(ids(&mut database, "select id from users limit 3"), [1, 2, 3]);
+    assert_eq!(ids(&mut database, "select id from users limit 3 offset 20"), [21, 22, 23]);
+    assert_eq!(ids(&mut database, "select id from users limit 5 offset 48"), [49, 50]);
+    assert_eq!(ids(&mut database, "select id from users where id > 10 limit 2 offset 1"), [12, 13]);
+    assert_eq!(ids(&mut database, "select id from users order by id desc limit 2 offset 1"), [49, 48]);
+    assert_eq!(ids(&mut database, "select id from users limit 100").len(), 50);
+}
+
+
+#[test]
+fn limit_zero_and_offsets_past_the_end_return_no_rows() {
+    let (_file, mut database) = users(50);
+
+    assert!(ids(&mut database, "select id from users limit 0").is_empty());
+    assert!(ids(&mut database, "select id from users limit 0 offset 5").is_empty());
+    assert!(ids(&mut database, "select count(*) from users limit 0").is_empty());
+    assert!(ids(&mut database, "select id from users limit 1 offset 50").is_empty());
+    assert!(ids(&mut database, "select id from users limit 1 offset 1000").is_empty());
+    assert_eq!(ids(&mut database, "select count(*) from users limit 1"), [50]);
+}
+
+
+#[test]
+fn negative_counts_are_rejected() {
+    let (_file, mut database) = users(50);
+
+    for sql in ["select id from users limit -1", "select id from users limit 1 offset -1", "select id from users limit 'a'"] {
+        let result = database.prepare(sql).err();
+        assert!(matches!(result, Some(PrepareResult::PrepareExpressionError(_))), "{}: {:?}", sql, result);
+    }
+
+    // Bound counts are checked when the statement runs
+    let mut select = database.prepare("select id from users limit ?").unwrap();
+    select.bind(1, Value::Integer(-1)).unwrap();
+    let result = select.execute(&mut database, &mut |_: &Row| ()).unwrap();
+    assert!(matches!(result, ExecuteResult::ExecuteExpressionError(_)));
+    assert_eq!(result.to_string(), "LIMIT must be a non-negative integer");
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::fs;
+use common::TempFile;
+use crablite::input_buffer::ReadResult;
+use crablite::line_editor::LineEditor;
+
+
+/// Types the keys into a new editor with the history file and returns the lines it
+/// reads until the keys run out, and what it drew.
+fn type_keys(history: &TempFile, keys: &str) -> (Vec<String>, String) {
+    let mut screen = Vec::new();
+    let mut editor = LineEditor::new(keys.as_bytes(), &mut screen, Some(history.path.clone()));
+    let no_completions = |_: &str, _: &str| Vec::new();
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    while let ReadResult::ReadSuccess = editor.read_line("db > ", &mut line, &no_completions).unwrap() {
+        editor.add_history(&line);
+        lines.push(line.clone());
+    }
+    drop(editor);
+    (lines, String::from_utf8(screen).unwrap())
+}
+
+
+#[test]
+fn history_is_saved_and_recalled_with_up_in_a_new_editor() {
+    let history = TempFile::new("history");
+
+    let (lines, _) = type_keys(&history, "select 1;\rselect 'a\\b';\r");
+    assert_eq!(lines, ["select 1;", "select 'a\\b';"]);
+    assert_eq!(fs::read_to_string(&history.path).unwrap(), "select 1;\nselect 'a\\\\b';\n");
+
+    // Up twice, then down once, goes back to the newest entry; a repeat is not added again
+    let (lines, _) = type_keys(&history, "\x1b[A\x1b[A\x1b[B\r\x1b[A\x1b[A\r");
+    assert_eq!(lines, ["select 'a\\b';", "select 1;"]);
+    assert_eq!(fs::read_to_string(&history.path).unwrap(), "select 1;\nselect 'a\\\\b';\nselect 1;\n");
+}
+
+
+#[test]
+fn multi_line_statements_are_kept_as_one_entry() {
+    let history = TempFile::new("history");
+    let mut editor = LineEditor::new(&b""[..], Vec::new(), Some(history.path.clone()));
+    editor.add_history("select *\nfrom users;");
+    drop(editor);
+    assert_eq!(fs::read_to_string(&history.path).unwrap(), "select *\\nfrom users;\n");
+
+    let (lines, _) = type_keys(&history, "\x1b[A\r");
+    assert_eq!(lines, ["select *\nfrom users;"]);
+}
+
+
+#[test]
+fn reverse_search_finds_older_matches_and_runs_or_edits_them() {
+    let history = TempFile::new("history");
+    fs::write(&history.path, "select id from users;\ninsert into users values (1, 'a', 'b');\nselect email from users;\n").unwrap();
+
+    // Ctrl-R with the newest match, then Enter
+    let (lines, screen) = type_keys(&history, "\x12sel\r");
+    assert_eq!(lines, ["select email from users;"]);
+    assert!(screen.contains("(reverse-i-search)`sel': select email from users;"), "{:?}", screen);
+
+    // Ctrl-R again moves on to an older match, and End leaves it to be edited
+    let (lines, _) = type_keys(&history, "\x12sel\x12\x1b[F limit 1\r");
+    assert_eq!(lines, ["select id from users; limit 1"]);
+
+    // Ctrl-G gives up and keeps the line typed before the search
+    let (lines, _) = type_keys(&history, "abc\x12insert\x07\r");
+    assert_eq!(lines, ["abc"]);
+}
@@ -0,0 +1,36 @@
+use crablite::input_buffer::InputBuffer;
+use crablite::lexer::Lexer;
+
+
+/// The first statement of the input, if its `;` has been read.
+fn first_statement(input: &str) -> Option<&str> {
+    Lexer::statement_end(input).map(|end| &input[..end])
+}
+
+
+#[test]
+fn semicolons_inside_quotes_and_comments_do_not_end_a_statement() {
+    assert_eq!(first_statement("select 'a;b' from t; select 2;"), Some("select 'a;b' from t;"));
+    assert_eq!(first_statement("select \"a;b\" from t;"), Some("select \"a;b\" from t;"));
+    assert_eq!(first_statement("select `a;b` from t;"), Some("select `a;b` from t;"));
+    assert_eq!(first_statement("select 'it''s;' from t;"), Some("select 'it''s;' from t;"));
+    assert_eq!(first_statement("select 'a\\';' from t;"), Some("select 'a\\';' from t;"));
+    assert_eq!(first_statement("select 1 -- not yet;\n;"), Some("select 1 -- not yet;\n;"));
+}
+
+
+#[test]
+fn statements_wait_for_their_semicolon_across_lines() {
+    assert_eq!(first_statement("select *\nfrom users"), None);
+    assert_eq!(first_statement("insert into t values ('a;\nb"), None);
+    assert_eq!(first_statement("insert into t values ('a;\nb');\n"), Some("insert into t values ('a;\nb');"));
+}
+
+
+#[test]
+fn the_prompt_continues_an_unfinished_statement() {
+    assert_eq!(InputBuffer::prompt(""), "db > ");
+    assert_eq!(InputBuffer::prompt("  -- a comment\n"), "db > ");
+    assert_eq!(InputBuffer::prompt("select *\n"), "   ...> ");
+    assert_eq!(InputBuffer::prompt("select 'a;\n"), InputBuffer::CONTINUATION_PROMPT);
+}
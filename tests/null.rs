@@ -0,0 +1,47 @@
+mod common;
+
+use common::{execute, ids, open_database, query};
+use crablite::table::ExecuteResult;
+use crablite::value::Value;
+
+
+#[test]
+fn is_null_finds_missing_and_explicit_nulls() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table t (id integer primary key, a text not null, b integer)");
+    query(&mut database, "insert into t values (1, 'x', null)");
+    query(&mut database, "insert into t (id, a) values (2, 'y')");
+    query(&mut database, "insert into t values (3, 'z', 7)");
+
+    assert_eq!(query(&mut database, "select * from t where id = 2"), [[Value::Integer(2), Value::Text("y".to_string()), Value::Null]]);
+    assert_eq!(ids(&mut database, "select id from t where b is null"), [1, 2]);
+    assert_eq!(ids(&mut database, "select id from t where b is not null"), [3]);
+    // A comparison with NULL is never true
+    assert!(query(&mut database, "select id from t where b = null").is_empty());
+}
+
+
+#[test]
+fn not_null_columns_reject_nulls_on_insert_and_update() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table t (id integer primary key, a text not null, b integer)");
+    query(&mut database, "create table k (name text primary key, v integer)");
+    query(&mut database, "insert into t values (1, 'x', 1)");
+
+    for (sql, column) in [
+        ("insert into t values (2, null, 1)", "a"),
+        ("insert into t (id, b) values (2, 1)", "a"),
+        ("update t set a = null", "a"),
+        ("insert into k values (null, 1)", "name"),
+    ] {
+        let result = execute(&mut database, sql);
+        assert!(matches!(&result, ExecuteResult::ExecuteNotNullViolation(name) if name == column), "{}: {}", sql, result);
+    }
+    assert_eq!(
+        execute(&mut database, "insert into t values (2, null, 1)").to_string(),
+        "NOT NULL constraint failed for column 'a'",
+    );
+
+    assert_eq!(query(&mut database, "select a from t"), [[Value::Text("x".to_string())]]);
+    assert!(query(&mut database, "select * from k").is_empty());
+}
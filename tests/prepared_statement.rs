@@ -0,0 +1,133 @@
+mod common;
+
+use common::{open_database, plan, query, text};
+use crablite::prepare::PrepareResult;
+use crablite::table::{ExecuteResult, Row};
+use crablite::value::Value;
+
+
+#[test]
+fn executes_repeatedly_with_new_bindings() {
+    let (_file, mut database) = open_database();
+    let mut insert = database.prepare("insert into users values (?, :name, :name)").unwrap();
+    assert_eq!(insert.parameter_count(), 2);
+    assert_eq!(insert.parameter_index("name"), Some(2));
+
+    for (id, name) in [(1, "ada"), (2, "bob"), (3, "cy")] {
+        insert.bind(1, Value::Integer(id)).unwrap();
+        insert.bind_name(":name", text(name)).unwrap();
+        let result = insert.execute(&mut database, &mut |_| ()).unwrap();
+        assert!(matches!(result, ExecuteResult::ExecuteSuccess));
+    }
+
+    let rows = query(&mut database, "select id, email from users");
+    assert_eq!(rows, [
+        [Value::Integer(1), text("ada")],
+        [Value::Integer(2), text("bob")],
+        [Value::Integer(3), text("cy")],
+    ]);
+}
+
+
+#[test]
+fn parameters_bound_the_primary_key_seek() {
+    let (_file, mut database) = open_database();
+    for id in 1..=10 {
+        query(&mut database, &format!("insert into users values ({}, 'u{}', 'e{}')", id, id, id));
+    }
+
+    assert_eq!(plan(&mut database, "select * from users where id = ?"), ["SEARCH users USING PRIMARY KEY (id=?)"]);
+
+    let mut select = database.prepare("select username from users where id >= ? and id < ? limit ?").unwrap();
+    select.bind(1, Value::Integer(4)).unwrap();
+    select.bind(2, Value::Integer(9)).unwrap();
+    select.bind(3, Value::Integer(2)).unwrap();
+    let mut rows = Vec::new();
+    select.execute(&mut database, &mut |row: &Row| rows.push(row.values.clone())).unwrap();
+    assert_eq!(rows, [[text("u4")], [text("u5")]]);
+
+    // A bound of another type does not narrow the range, the WHERE clause still applies
+    select.bind(1, Value::Real(8.5)).unwrap();
+    select.bind(2, Value::Integer(10)).unwrap();
+    rows.clear();
+    select.execute(&mut database, &mut |row: &Row| rows.push(row.values.clone())).unwrap();
+    assert_eq!(rows, [[text("u9")]]);
+}
+
+
+#[test]
+fn updates_and_deletes_use_bound_values() {
+    let (_file, mut database) = open_database();
+    for id in 1..=3 {
+        query(&mut database, &format!("insert into users values ({}, 'u{}', 'e{}')", id, id, id));
+    }
+
+    let mut update = database.prepare("update users set email = ? where id = ?").unwrap();
+    update.bind(1, text("new")).unwrap();
+    update.bind(2, Value::Integer(2)).unwrap();
+    update.execute(&mut database, &mut |_| ()).unwrap();
+
+    let mut delete = database.prepare("delete from users where id = ?").unwrap();
+    delete.bind(1, Value::Integer(1)).unwrap();
+    delete.execute(&mut database, &mut |_| ()).unwrap();
+
+    let rows = query(&mut database, "select id, email from users");
+    assert_eq!(rows, [[Value::Integer(2), text("new")], [Value::Integer(3), text("e3")]]);
+}
+
+
+#[test]
+fn bound_values_are_converted_to_the_column_type() {
+    let (_file, mut database) = open_database();
+    let mut insert = database.prepare("insert into users values (?, 'a', 'b')").unwrap();
+
+    insert.bind(1, text("seven")).unwrap();
+    assert!(matches!(insert.execute(&mut database, &mut |_| ()).unwrap(), ExecuteResult::ExecuteTypeMismatch));
+
+    query(&mut database, "create table measurements (id integer primary key, reading real)");
+    let mut insert = database.prepare("insert into measurements values (1, ?)").unwrap();
+    insert.bind(1, Value::Integer(7)).unwrap();
+    assert!(matches!(insert.execute(&mut database, &mut |_| ()).unwrap(), ExecuteResult::ExecuteSuccess));
+    assert_eq!(query(&mut database, "select reading from measurements"), [[Value::Real(7.0)]]);
+}
+
+
+#[test]
+fn unbound_parameters_fail() {
+    let (_file, mut database) = open_database();
+    let mut select = database.prepare("select * from users where id = :id").unwrap();
+
+    let result = select.execute(&mut database, &mut |_| ());
+    assert!(matches!(result, Err(PrepareResult::PrepareExpressionError(e)) if e == "no value is bound to :id"));
+
+    select.bind_name("id", Value::Integer(1)).unwrap();
+    select.clear_bindings();
+    assert!(select.execute(&mut database, &mut |_| ()).is_err());
+    assert!(matches!(select.bind(2, Value::Null), Err(PrepareResult::PrepareUnknownParameter(_))));
+}
+
+
+#[test]
+fn statements_are_checked_when_prepared() {
+    let (_file, database) = open_database();
+
+    assert!(matches!(database.prepare("select * from missing"), Err(PrepareResult::PrepareUnknownTable(_))));
+    assert!(matches!(database.prepare("select nope from users"), Err(PrepareResult::PrepareUnknownColumn(_))));
+}
+
+
+#[test]
+fn records_made_from_bound_values_are_size_checked() {
+    let (_file, mut database) = open_database();
+    let long = "a".repeat(70000);
+
+    let mut insert = database.prepare("insert into users values (1, ?, 'e')").unwrap();
+    insert.bind(1, text(&long)).unwrap();
+    assert!(matches!(insert.execute(&mut database, &mut |_| ()).unwrap(), ExecuteResult::ExecuteRowTooLarge));
+    assert!(query(&mut database, "select id from users").is_empty());
+
+    query(&mut database, "insert into users values (1, 'u1', 'e1')");
+    let result = database.prepare(&format!("update users set username = '{}'", long)).unwrap().execute(&mut database, &mut |_| ()).unwrap();
+    assert!(matches!(result, ExecuteResult::ExecuteRowTooLarge));
+    assert_eq!(query(&mut database, "select username from users"), [[text("u1")]]);
+}
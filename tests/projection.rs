@@ -0,0 +1,46 @@
+mod common;
+
+use common::{query, text, users};
+use crablite::database::Database;
+use crablite::parser::Parser;
+use crablite::prepare::PrepareResult;
+use crablite::statement::Statement;
+use crablite::value::Value;
+
+
+/// The names the columns of the select get in headers.
+fn column_names(database: &Database, sql: &str) -> Vec<String> {
+    let mut statement = Statement::default();
+    let result = database.prepare_parsed(Parser::parse(sql).unwrap(), &mut statement);
+    assert!(matches!(result, PrepareResult::PrepareSuccess), "{}: {:?}", sql, result);
+    statement.column_names
+}
+
+
+#[test]
+fn only_the_selected_columns_are_returned_in_their_order() {
+    let (_file, mut database) = users(2);
+
+    assert_eq!(query(&mut database, "select email, username from users"), [[text("user1@x"), text("user1")], [text("user2@x"), text("user2")]]);
+    assert_eq!(query(&mut database, "select id, id from users where id = 2"), [[Value::Integer(2), Value::Integer(2)]]);
+    // Columns the select list leaves out can still be filtered and sorted on
+    assert_eq!(query(&mut database, "select username from users where email <> 'x' order by email desc"), [[text("user2")], [text("user1")]]);
+    assert_eq!(column_names(&database, "select email, username from users"), ["email", "username"]);
+    assert_eq!(column_names(&database, "select * from users"), ["id", "username", "email"]);
+}
+
+
+#[test]
+fn expressions_are_evaluated_for_every_row_and_named_after_their_text() {
+    let (_file, mut database) = users(2);
+
+    assert_eq!(query(&mut database, "select id * 10 + 1, -id, 'k', null, id > 1 from users"), [
+        [Value::Integer(11), Value::Integer(-1), text("k"), Value::Null, Value::Boolean(false)],
+        [Value::Integer(21), Value::Integer(-2), text("k"), Value::Null, Value::Boolean(true)],
+    ]);
+    assert_eq!(query(&mut database, "select (id + 1) * 2 as doubled from users where id = 1"), [[Value::Integer(4)]]);
+    assert_eq!(
+        column_names(&database, "select (id + 1) * 2, id * 10 as n, 'k', null from users"),
+        ["(id + 1) * 2", "n", "'k'", "NULL"],
+    );
+}
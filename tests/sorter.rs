@@ -0,0 +1,72 @@
+use crablite::sorter::{SortedRows, Sorter};
+use crablite::table::Row;
+use crablite::value::Value;
+
+
+/// Sorts (key, tag) pairs by key with a memory limit small enough to spill every few rows.
+fn sort(descending: bool, rows: impl IntoIterator<Item = (Value, i64)>, memory_limit: usize) -> SortedRows {
+    let mut sorter = Sorter::new(vec![descending], memory_limit);
+    for (key, tag) in rows {
+        sorter.add(vec![key.clone()], Row::new(vec![key, Value::Integer(tag)])).unwrap();
+    }
+    sorter.finish().unwrap()
+}
+
+
+fn values(rows: SortedRows) -> Vec<Vec<Value>> {
+    rows.map(|row| row.unwrap().values).collect()
+}
+
+
+#[test]
+fn rows_past_the_memory_limit_are_spilled_and_merged_in_order() {
+    let keys = (0..2000).map(|i| i * 7919 % 2000);
+
+    let rows = sort(false, keys.map(|i| (Value::Integer(i), i)), 1024);
+    assert!(matches!(rows, SortedRows::Merge { .. }), "the rows should have been spilled");
+
+    let expected: Vec<Vec<Value>> = (0..2000).map(|i| vec![Value::Integer(i), Value::Integer(i)]).collect();
+    assert_eq!(values(rows), expected);
+}
+
+
+#[test]
+fn spilled_sorts_are_stable_and_honour_descending_keys() {
+    // Every key appears in several runs, tagged with its position in the input
+    let input: Vec<(Value, i64)> = (0..600).map(|i| (Value::Text(format!("key{}", i % 3)), i)).collect();
+
+    let rows = sort(true, input, 512);
+    assert!(matches!(rows, SortedRows::Merge { .. }));
+
+    let rows = values(rows);
+    let expected_keys = ["key2", "key1", "key0"].iter().flat_map(|key| std::iter::repeat_n(*key, 200));
+    for (row, key) in rows.iter().zip(expected_keys) {
+        assert_eq!(row[0], Value::Text(key.to_string()));
+    }
+    for run in rows.chunks(200) {
+        let tags: Vec<i64> = run.iter().map(|row| match row[1] {
+            Value::Integer(tag) => tag,
+            ref value => panic!("Expected an integer tag, got {}", value),
+        }).collect();
+        assert!(tags.is_sorted(), "equal keys should keep their input order");
+    }
+}
+
+
+#[test]
+fn small_sorts_stay_in_memory_and_order_mixed_types() {
+    let input = [
+        (Value::Text("b".to_string()), 0),
+        (Value::Integer(2), 1),
+        (Value::Null, 2),
+        (Value::Real(1.5), 3),
+        (Value::Blob(vec![1]), 4),
+        (Value::Text("a".to_string()), 5),
+    ];
+
+    let rows = sort(false, input, 1 << 20);
+    assert!(matches!(rows, SortedRows::Memory(_)));
+
+    let tags: Vec<Value> = values(rows).into_iter().map(|row| row[1].clone()).collect();
+    assert_eq!(tags, [2, 3, 1, 5, 0, 4].map(Value::Integer));
+}
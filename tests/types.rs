@@ -0,0 +1,72 @@
+mod common;
+
+use common::{execute, open_database, query};
+use crablite::database::Database;
+use crablite::prepare::PrepareResult;
+use crablite::table::{ExecuteResult, Row};
+use crablite::value::Value;
+
+
+#[test]
+fn every_column_type_round_trips_through_the_file() {
+    let (file, mut database) = open_database();
+    query(&mut database, "create table t (i integer, r real, s text, b blob, f boolean)");
+    query(&mut database, "insert into t values (-9223372036854775808, -0.125, 'it''s', x'00ff', true)");
+    query(&mut database, "insert into t values (9223372036854775807, 1e300, '', x'', false)");
+    database.db_close().unwrap();
+
+    let mut database = Database::db_open(file.path_str()).unwrap();
+    assert_eq!(query(&mut database, "select * from t"), [
+        vec![Value::Integer(i64::MIN), Value::Real(-0.125), Value::Text("it's".to_string()), Value::Blob(vec![0, 0xff]), Value::Boolean(true)],
+        vec![Value::Integer(i64::MAX), Value::Real(1e300), Value::Text(String::new()), Value::Blob(vec![]), Value::Boolean(false)],
+    ]);
+}
+
+
+#[test]
+fn integers_are_stored_in_real_and_boolean_columns() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table t (r real, f boolean)");
+    query(&mut database, "insert into t values (2, 1)");
+
+    assert_eq!(query(&mut database, "select r, f from t"), [[Value::Real(2.0), Value::Boolean(true)]]);
+}
+
+
+#[test]
+fn values_of_the_wrong_type_are_rejected() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create table t (i integer, s text, b blob)");
+    query(&mut database, "insert into t values (1, 'a', x'01')");
+
+    for (sql, column) in [
+        ("insert into t values (1.5, 'a', x'01')", "i"),
+        ("insert into t values (1, 2, x'01')", "s"),
+        ("insert into t values (1, 'a', 'b')", "b"),
+    ] {
+        let result = database.prepare(sql).err();
+        assert!(matches!(&result, Some(PrepareResult::PrepareTypeMismatch(name)) if name == column), "{}: {:?}", sql, result);
+    }
+
+    // Values computed while running are checked when the row is written
+    let result = execute(&mut database, "update t set i = i + 0.5");
+    assert!(matches!(result, ExecuteResult::ExecuteTypeMismatch));
+    assert_eq!(result.to_string(), "Row does not match table schema");
+    assert_eq!(query(&mut database, "select i from t"), [[Value::Integer(1)]]);
+}
+
+
+#[test]
+fn corrupt_records_are_reported_rather_than_read() {
+    let row = Row::new(vec![Value::Integer(7), Value::Text("seven".to_string())]);
+    let mut record = vec![0; row.serialized_size()];
+    row.serialize_row(&mut record);
+    assert_eq!(Row::deserialize_row(&record).unwrap(), row);
+
+    // A value cut off by the end of the record, and a tag no value has
+    for bytes in [&record[..record.len() - 1], &[1, 0, b'a', 0][..], &[][..]] {
+        let error = Row::deserialize_row(bytes).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "{:?}", bytes);
+    }
+    assert!(Value::deserialize(b"a").unwrap_err().to_string().contains("unknown value tag 97"));
+}
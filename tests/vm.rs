@@ -0,0 +1,178 @@
+mod common;
+
+use common::{open_database, query, text};
+use crablite::ast::BinaryOperator;
+use crablite::database::Database;
+use crablite::parser::Parser;
+use crablite::prepare::PrepareResult;
+use crablite::statement::Statement;
+use crablite::table::{ExecuteResult, Row};
+use crablite::value::Value;
+use crablite::vm::{Instruction, Program};
+
+
+/// Compiles the statement the way the REPL does before running or explaining it.
+fn compile(database: &Database, sql: &str) -> Program {
+    let mut statement = Statement::default();
+    let result = database.prepare_parsed(Parser::parse(sql).unwrap(), &mut statement);
+    assert!(matches!(result, PrepareResult::PrepareSuccess), "{}: {:?}", sql, result);
+    Program::compile(&statement, &database.catalog)
+}
+
+
+/// The program as EXPLAIN prints it, without the addresses.
+fn listing(program: &Program) -> Vec<String> {
+    program.instructions.iter().map(Instruction::to_string).collect()
+}
+
+
+fn run(database: &mut Database, program: &Program) -> (ExecuteResult, Vec<Vec<Value>>) {
+    let mut rows = Vec::new();
+    let result = program.execute(database, &mut |row: &Row| rows.push(row.values.clone()));
+    (result, rows)
+}
+
+
+#[test]
+fn a_written_program_loops_over_the_rows_it_filters() {
+    let (_file, mut database) = open_database();
+    for id in 1..=4 {
+        query(&mut database, &format!("insert into users values ({}, 'u{}', 'e{}')", id, id, id));
+    }
+
+    // select username from users where id > 2
+    let program = Program {
+        instructions: vec![
+            Instruction::OpenRead { cursor: 0, table: "users".to_string() },
+            Instruction::Value { value: Value::Integer(2), target: 1 },
+            Instruction::Rewind { cursor: 0, if_empty: 8 },
+            Instruction::Column { cursor: 0, column: 0, target: 0 },
+            Instruction::Compare { operator: BinaryOperator::Greater, left: 0, right: 1, if_false: 7 },
+            Instruction::Column { cursor: 0, column: 1, target: 2 },
+            Instruction::ResultRow { start: 2, count: 1 },
+            Instruction::Next { cursor: 0, if_more: 3 },
+            Instruction::Halt,
+        ],
+        num_registers: 3,
+        num_cursors: 1,
+        ..Program::default()
+    };
+
+    let (result, rows) = run(&mut database, &program);
+    assert!(matches!(result, ExecuteResult::ExecuteSuccess));
+    assert_eq!(rows, [[text("u3")], [text("u4")]]);
+    assert_eq!(program.plan(), ["SCAN users"]);
+}
+
+
+#[test]
+fn a_written_program_inserts_a_record_with_a_new_rowid() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "insert into users values (7, 'a', 'a@x')");
+
+    let program = Program {
+        instructions: vec![
+            Instruction::OpenWrite { cursor: 0, table: "users".to_string() },
+            Instruction::NewRowid { cursor: 0, target: 0 },
+            Instruction::Value { value: text("b"), target: 1 },
+            Instruction::Value { value: text("b@x"), target: 2 },
+            Instruction::MakeRecord { start: 0, count: 3, target: 3 },
+            Instruction::Insert { cursor: 0, record: 3 },
+            Instruction::Halt,
+        ],
+        num_registers: 4,
+        num_cursors: 1,
+        ..Program::default()
+    };
+
+    let (result, _) = run(&mut database, &program);
+    assert!(matches!(result, ExecuteResult::ExecuteSuccess));
+    assert_eq!(query(&mut database, "select id, username from users where id = 8"), [[Value::Integer(8), text("b")]]);
+    // Running it again inserts the next rowid
+    run(&mut database, &program);
+    assert_eq!(query(&mut database, "select count(*) from users"), [[Value::Integer(3)]]);
+}
+
+
+#[test]
+fn explain_lists_the_compiled_select_and_insert() {
+    let (_file, database) = open_database();
+
+    let select = compile(&database, "select username from users where email = 'x'");
+    assert_eq!(select.plan(), ["SCAN users"]);
+    assert_eq!(listing(&select), [
+        "OpenRead     cursor=0 table=users",
+        "Rewind       cursor=0 if_empty=11",
+        "Column       cursor=0 column=0 target=r0",
+        "Column       cursor=0 column=1 target=r1",
+        "Column       cursor=0 column=2 target=r2",
+        "Eval         row=r0 target=r3 expression=#2",
+        "Eval         row=r0 target=r4 expression='x'",
+        "Compare      r3 = r4 if_false=10",
+        "Eval         row=r0 target=r5 expression=#1",
+        "ResultRow    start=r5 count=1",
+        "Next         cursor=0 if_more=2",
+        "Halt",
+    ]);
+
+    let insert = compile(&database, "insert into users (username, email) values ('a', 'b')");
+    assert_eq!(insert.plan(), ["INSERT INTO users"]);
+    assert_eq!(listing(&insert), [
+        "OpenWrite    cursor=0 table=users",
+        "Value        value=NULL target=r0",
+        "Value        value='a' target=r1",
+        "Value        value='b' target=r2",
+        "NotNull      register=r0 target=6",
+        "NewRowid     cursor=0 target=r0",
+        "MakeRecord   start=r0 count=3 target=r3",
+        "Insert       cursor=0 record=r3",
+        "Halt",
+    ]);
+}
+
+
+#[test]
+fn updates_and_deletes_collect_rows_before_changing_them() {
+    let (_file, database) = open_database();
+    let opcodes = |program: &Program| -> Vec<String> {
+        listing(program).iter().map(|line| line.split(' ').next().unwrap().to_string()).collect()
+    };
+
+    let delete = compile(&database, "delete from users where id > 2");
+    assert_eq!(delete.plan(), ["SEARCH users USING PRIMARY KEY (id>?) FOR DELETE"]);
+    let delete = opcodes(&delete);
+    assert!(delete.ends_with(&["RowSetAdd", "Next", "RowSetRewind", "RowSetSeek", "Delete", "RowSetNext", "Halt"].map(String::from)), "{:?}", delete);
+
+    let update = compile(&database, "update users set email = 'y' where username = 'a'");
+    assert_eq!(update.plan(), ["SCAN users FOR UPDATE"]);
+    let update = opcodes(&update);
+    let changes = [
+        "RowSetCheck", "RowSetRewind", "RowSetSeek", "RowSetRecord", "RowSetStays", "Delete", "Goto", "UpdateRow", "RowSetNext",
+        "RowSetRewind", "RowSetStays", "RowSetRecord", "Insert", "RowSetNext", "Halt",
+    ];
+    assert!(update.ends_with(&changes.map(String::from)), "{:?}", update);
+}
+
+
+#[test]
+fn repeated_updates_keeping_the_key_write_rows_over_without_new_pages() {
+    let (_file, mut database) = open_database();
+    query(&mut database, "create unique index users_username on users (username)");
+    for id in 1..=200 {
+        query(&mut database, &format!("insert into users values ({}, 'user{}', 'user{}@example.com')", id, id, id));
+    }
+
+    // Index entries are left alone when no indexed column changes
+    let num_pages = database.pager.num_pages;
+    for i in 0..5 {
+        query(&mut database, &format!("update users set email = 'x{}'", i));
+        assert_eq!(database.pager.num_pages, num_pages, "update {}", i);
+    }
+    query(&mut database, "update users set username = 'seven' where id = 7");
+    assert_eq!(query(&mut database, "select id, email from users where username = 'seven'"), [[Value::Integer(7), text("x4")]]);
+
+    // Rows whose key moves are deleted and inserted again, after the others are written over
+    query(&mut database, "update users set id = 101 - id, email = 'moved' where id <= 100");
+    assert_eq!(query(&mut database, "select count(*), min(id), max(id) from users"), [[Value::Integer(200), Value::Integer(1), Value::Integer(200)]]);
+    assert_eq!(query(&mut database, "select id, email from users where username = 'seven'"), [[Value::Integer(94), text("moved")]]);
+}